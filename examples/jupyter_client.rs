@@ -0,0 +1,97 @@
+// examples/jupyter_client.rs
+//
+// A minimal Jupyter client for checking `numerus kernel` over loopback sockets:
+//
+//     numerus kernel --connection-file kernel.json &
+//     cargo run --example jupyter_client kernel.json
+
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::time::Duration;
+
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+fn sign(key: &[u8], frames: &[&[u8]]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    for frame in frames {
+        mac.update(frame);
+    }
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn send(socket: &zmq::Socket, key: &[u8], id: usize, msg_type: &str, content: Value) {
+    let header = json!({
+        "msg_id": format!("client-{}", id),
+        "session": "client",
+        "username": "example",
+        "msg_type": msg_type,
+        "version": "5.3",
+    })
+    .to_string();
+    let content = content.to_string();
+    let parts = [header.as_bytes(), b"{}", b"{}", content.as_bytes()];
+    let mut frames = vec![DELIMITER.to_vec(), sign(key, &parts).into_bytes()];
+    frames.extend(parts.iter().map(|part| part.to_vec()));
+    socket.send_multipart(frames, 0).unwrap();
+}
+
+fn receive(socket: &zmq::Socket) -> (String, Value) {
+    let frames = socket.recv_multipart(0).unwrap();
+    let delimiter = frames.iter().position(|f| f.as_slice() == DELIMITER).unwrap();
+    let header: Value = serde_json::from_slice(&frames[delimiter + 2]).unwrap();
+    let content: Value = serde_json::from_slice(&frames[delimiter + 5]).unwrap();
+    (header["msg_type"].as_str().unwrap().to_string(), content)
+}
+
+fn main() {
+    let path = std::env::args().nth(1).expect("usage: jupyter_client <connection file>");
+    let info: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let key = info["key"].as_str().unwrap_or("").as_bytes().to_vec();
+    let endpoint = |port: &str| format!("tcp://{}:{}", info["ip"].as_str().unwrap(), info[port]);
+
+    let context = zmq::Context::new();
+    let shell = context.socket(zmq::DEALER).unwrap();
+    shell.connect(&endpoint("shell_port")).unwrap();
+    let iopub = context.socket(zmq::SUB).unwrap();
+    iopub.connect(&endpoint("iopub_port")).unwrap();
+    iopub.set_subscribe(b"").unwrap();
+    let heartbeat = context.socket(zmq::REQ).unwrap();
+    heartbeat.connect(&endpoint("hb_port")).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+
+    heartbeat.send("ping", 0).unwrap();
+    println!("heartbeat: {}", heartbeat.recv_string(0).unwrap().unwrap());
+
+    let requests = [
+        ("kernel_info_request", json!({})),
+        (
+            "execute_request",
+            json!({ "code": "A = [4 3; 6 3];\ninv(A)", "silent": false }),
+        ),
+        ("execute_request", json!({ "code": "det(B)", "silent": false })),
+        ("complete_request", json!({ "code": "x = in", "cursor_pos": 6 })),
+        ("inspect_request", json!({ "code": "sqrtm(A)", "cursor_pos": 2 })),
+        ("is_complete_request", json!({ "code": "A = [1 2" })),
+    ];
+    for (id, (msg_type, content)) in requests.into_iter().enumerate() {
+        send(&shell, &key, id, msg_type, content);
+        let (reply_type, reply) = receive(&shell);
+        println!("{}: {}", reply_type, reply);
+        loop {
+            let (msg_type, content) = receive(&iopub);
+            println!("  iopub {}: {}", msg_type, content);
+            if msg_type == "status" && content["execution_state"] == "idle" {
+                break;
+            }
+        }
+    }
+
+    send(&shell, &key, 99, "shutdown_request", json!({ "restart": false }));
+    let (reply_type, reply) = receive(&shell);
+    println!("{}: {}", reply_type, reply);
+}
@@ -13,7 +13,7 @@ fn main() {
     let b = VectorDouble::from_slice(&b_data);
 
     // Create LU decomposition object
-    let lu = LU::new(&a);
+    let lu = LU::new(&a).expect("A has no zero row");
 
     // Solve the system Ax = b
     let mut x = VectorDouble::new(3);
@@ -31,7 +31,7 @@ fn main() {
 
     // Calculate the inverse of A
     let mut a_inv = MatrixDouble::new(3, 3);
-    lu.inverse(&mut a_inv);
+    lu.inverse(&mut a_inv).expect("not cancelled");
     println!("Inverse of matrix A:");
     for i in 0..a_inv.nrows() {
         for j in 0..a_inv.ncols() {
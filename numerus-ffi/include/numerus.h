@@ -0,0 +1,199 @@
+#ifndef NUMERUS_H
+#define NUMERUS_H
+
+/* Regenerate from src/lib.rs with: cbindgen --config cbindgen.toml --output include/numerus.h */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Order of the elements in arrays passed to and from Numerus. C arrays are row-major,
+ * Fortran arrays column-major. Functions take the layout as a plain `int`, so a value
+ * from C or Fortran that is not one of these is rejected instead of being undefined.
+ */
+typedef enum NumerusLayout {
+  NUMERUS_LAYOUT_ROW_MAJOR = 0,
+  NUMERUS_LAYOUT_COL_MAJOR = 1,
+} NumerusLayout;
+
+/**
+ * Result of every fallible call. Anything but `Ok` leaves the output arguments untouched.
+ */
+typedef enum NumerusStatus {
+  NUMERUS_STATUS_OK = 0,
+  NUMERUS_STATUS_NULL_POINTER = 1,
+  NUMERUS_STATUS_DIMENSION_MISMATCH = 2,
+  NUMERUS_STATUS_SINGULAR = 3,
+  NUMERUS_STATUS_OUT_OF_BOUNDS = 4,
+  /**
+   * A bug in Numerus. The panic was caught at the boundary instead of unwinding into C.
+   */
+  NUMERUS_STATUS_PANIC = 5,
+  /**
+   * An argument outside its documented values, such as an unknown layout.
+   */
+  NUMERUS_STATUS_INVALID_ARGUMENT = 6,
+} NumerusStatus;
+
+/**
+ * Opaque handle to the LU decomposition of a square matrix.
+ */
+typedef struct NumerusLu NumerusLu;
+
+/**
+ * Opaque handle to a dense matrix of doubles.
+ */
+typedef struct NumerusMatrix NumerusMatrix;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a `rows` x `cols` matrix of zeros. Free it with `numerus_matrix_free`. Returns
+ * null if `rows * cols` is more than 100 million elements.
+ */
+struct NumerusMatrix *numerus_matrix_new(size_t rows, size_t cols);
+
+/**
+ * Creates a matrix from `rows * cols` doubles laid out as `layout` says. Returns null if
+ * `data` is null, `layout` is not a `NumerusLayout` or `rows * cols` overflows.
+ *
+ * # Safety
+ *
+ * `data` must point to `rows * cols` readable doubles.
+ */
+struct NumerusMatrix *numerus_matrix_from_data(size_t rows,
+                                               size_t cols,
+                                               const double *data,
+                                               int layout);
+
+/**
+ * Frees a matrix. Null is ignored.
+ *
+ * # Safety
+ *
+ * `matrix` must be null or come from Numerus, and must not be used afterwards.
+ */
+void numerus_matrix_free(struct NumerusMatrix *matrix);
+
+/**
+ * Number of rows, or 0 for null.
+ *
+ * # Safety
+ *
+ * `matrix` must be null or a live matrix handle.
+ */
+size_t numerus_matrix_rows(const struct NumerusMatrix *matrix);
+
+/**
+ * Number of columns, or 0 for null.
+ *
+ * # Safety
+ *
+ * `matrix` must be null or a live matrix handle.
+ */
+size_t numerus_matrix_cols(const struct NumerusMatrix *matrix);
+
+/**
+ * Reads element (`row`, `col`), counting from zero.
+ *
+ * # Safety
+ *
+ * `matrix` must be null or a live matrix handle, and `value` null or writable.
+ */
+enum NumerusStatus numerus_matrix_get(const struct NumerusMatrix *matrix,
+                                      size_t row,
+                                      size_t col,
+                                      double *value);
+
+/**
+ * Writes element (`row`, `col`), counting from zero.
+ *
+ * # Safety
+ *
+ * `matrix` must be null or a live matrix handle.
+ */
+enum NumerusStatus numerus_matrix_set(struct NumerusMatrix *matrix,
+                                      size_t row,
+                                      size_t col,
+                                      double value);
+
+/**
+ * Copies the `rows * cols` elements into `data` in the given layout. Returns
+ * `InvalidArgument` if `layout` is not a `NumerusLayout`.
+ *
+ * # Safety
+ *
+ * `matrix` must be null or a live matrix handle, and `data` null or point to
+ * `rows * cols` writable doubles.
+ */
+enum NumerusStatus numerus_matrix_copy_to(const struct NumerusMatrix *matrix,
+                                          double *data,
+                                          int layout);
+
+/**
+ * Computes `a * b` into a new matrix stored in `*product`.
+ *
+ * # Safety
+ *
+ * `a` and `b` must be null or live matrix handles, and `product` null or writable.
+ */
+enum NumerusStatus numerus_matmul(const struct NumerusMatrix *a,
+                                  const struct NumerusMatrix *b,
+                                  struct NumerusMatrix **product);
+
+/**
+ * Factors the square matrix `a` with partial pivoting into a new handle stored in `*lu`.
+ * Free it with `numerus_lu_free`. Returns `Singular` when a pivot is zero or below
+ * `n * eps * ||A||`, since solutions would be meaningless.
+ *
+ * # Safety
+ *
+ * `a` must be null or a live matrix handle, and `lu` null or writable.
+ */
+enum NumerusStatus numerus_lu_factor(const struct NumerusMatrix *a, struct NumerusLu **lu);
+
+/**
+ * Frees an LU decomposition. Null is ignored.
+ *
+ * # Safety
+ *
+ * `lu` must be null or come from `numerus_lu_factor`, and must not be used afterwards.
+ */
+void numerus_lu_free(struct NumerusLu *lu);
+
+/**
+ * Solves `A x = b` for every column of `b` into a new matrix stored in `*x`.
+ *
+ * # Safety
+ *
+ * `lu` and `b` must be null or live handles, and `x` null or writable.
+ */
+enum NumerusStatus numerus_lu_solve(const struct NumerusLu *lu,
+                                    const struct NumerusMatrix *b,
+                                    struct NumerusMatrix **x);
+
+/**
+ * Stores the determinant of the factored matrix in `*det`.
+ *
+ * # Safety
+ *
+ * `lu` must be null or a live LU handle, and `det` null or writable.
+ */
+enum NumerusStatus numerus_lu_det(const struct NumerusLu *lu, double *det);
+
+/**
+ * A static, NUL-terminated description of a status code. Takes an `int` like the layout
+ * arguments, so any value is safe to pass; unknown ones give "unknown status".
+ */
+const char *numerus_status_message(int status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NUMERUS_H */
@@ -0,0 +1,336 @@
+use numerus::decomp::LU;
+use numerus::math_utilities::{MatrixDouble, MAX_ELEMENTS};
+use numerus::ops::multiply_matrices;
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// Result of every fallible call. Anything but `Ok` leaves the output arguments untouched.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumerusStatus {
+    Ok = 0,
+    NullPointer = 1,
+    DimensionMismatch = 2,
+    Singular = 3,
+    OutOfBounds = 4,
+    /// A bug in Numerus. The panic was caught at the boundary instead of unwinding into C.
+    Panic = 5,
+    /// An argument outside its documented values, such as an unknown layout.
+    InvalidArgument = 6,
+}
+
+/// Order of the elements in arrays passed to and from Numerus. C arrays are row-major,
+/// Fortran arrays column-major. Functions take the layout as a plain `int`, so a value
+/// from C or Fortran that is not one of these is rejected instead of being undefined.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumerusLayout {
+    RowMajor = 0,
+    ColMajor = 1,
+}
+
+/// Opaque handle to a dense matrix of doubles.
+pub struct NumerusMatrix(MatrixDouble);
+
+/// Opaque handle to the LU decomposition of a square matrix.
+pub struct NumerusLu {
+    lu: LU,
+    n: usize,
+}
+
+fn guard(f: impl FnOnce() -> NumerusStatus) -> NumerusStatus {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(NumerusStatus::Panic)
+}
+
+fn boxed<T>(value: T) -> *mut T {
+    Box::into_raw(Box::new(value))
+}
+
+// Like `guard` for the constructors, which report failure as null.
+fn allocate(f: impl FnOnce() -> MatrixDouble) -> *mut NumerusMatrix {
+    panic::catch_unwind(AssertUnwindSafe(|| boxed(NumerusMatrix(f())))).unwrap_or(ptr::null_mut())
+}
+
+fn layout(value: c_int) -> Option<NumerusLayout> {
+    match value {
+        0 => Some(NumerusLayout::RowMajor),
+        1 => Some(NumerusLayout::ColMajor),
+        _ => None,
+    }
+}
+
+/// Creates a `rows` x `cols` matrix of zeros. Free it with `numerus_matrix_free`. Returns
+/// null if `rows * cols` is more than 100 million elements.
+#[no_mangle]
+pub extern "C" fn numerus_matrix_new(rows: usize, cols: usize) -> *mut NumerusMatrix {
+    if rows
+        .checked_mul(cols)
+        .is_none_or(|count| count > MAX_ELEMENTS)
+    {
+        return ptr::null_mut();
+    }
+    allocate(|| MatrixDouble::new(rows, cols))
+}
+
+/// Creates a matrix from `rows * cols` doubles laid out as `layout` says. Returns null if
+/// `data` is null, `layout` is not a `NumerusLayout` or `rows * cols` overflows.
+///
+/// # Safety
+///
+/// `data` must point to `rows * cols` readable doubles.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matrix_from_data(
+    rows: usize,
+    cols: usize,
+    data: *const f64,
+    layout: c_int,
+) -> *mut NumerusMatrix {
+    let (Some(len), Some(layout)) = (rows.checked_mul(cols), self::layout(layout)) else {
+        return ptr::null_mut();
+    };
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+    let data = slice::from_raw_parts(data, len);
+    allocate(|| match layout {
+        NumerusLayout::RowMajor => MatrixDouble::from_slice(rows, cols, data),
+        NumerusLayout::ColMajor => MatrixDouble::from_fn(rows, cols, |i, j| data[j * rows + i]),
+    })
+}
+
+/// Frees a matrix. Null is ignored.
+///
+/// # Safety
+///
+/// `matrix` must be null or come from Numerus, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matrix_free(matrix: *mut NumerusMatrix) {
+    if !matrix.is_null() {
+        drop(Box::from_raw(matrix));
+    }
+}
+
+/// Number of rows, or 0 for null.
+///
+/// # Safety
+///
+/// `matrix` must be null or a live matrix handle.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matrix_rows(matrix: *const NumerusMatrix) -> usize {
+    matrix.as_ref().map_or(0, |matrix| matrix.0.nrows())
+}
+
+/// Number of columns, or 0 for null.
+///
+/// # Safety
+///
+/// `matrix` must be null or a live matrix handle.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matrix_cols(matrix: *const NumerusMatrix) -> usize {
+    matrix.as_ref().map_or(0, |matrix| matrix.0.ncols())
+}
+
+/// Reads element (`row`, `col`), counting from zero.
+///
+/// # Safety
+///
+/// `matrix` must be null or a live matrix handle, and `value` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matrix_get(
+    matrix: *const NumerusMatrix,
+    row: usize,
+    col: usize,
+    value: *mut f64,
+) -> NumerusStatus {
+    let (Some(matrix), Some(value)) = (matrix.as_ref(), value.as_mut()) else {
+        return NumerusStatus::NullPointer;
+    };
+    if row >= matrix.0.nrows() || col >= matrix.0.ncols() {
+        return NumerusStatus::OutOfBounds;
+    }
+    *value = matrix.0[row][col];
+    NumerusStatus::Ok
+}
+
+/// Writes element (`row`, `col`), counting from zero.
+///
+/// # Safety
+///
+/// `matrix` must be null or a live matrix handle.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matrix_set(
+    matrix: *mut NumerusMatrix,
+    row: usize,
+    col: usize,
+    value: f64,
+) -> NumerusStatus {
+    let Some(matrix) = matrix.as_mut() else {
+        return NumerusStatus::NullPointer;
+    };
+    if row >= matrix.0.nrows() || col >= matrix.0.ncols() {
+        return NumerusStatus::OutOfBounds;
+    }
+    matrix.0[row][col] = value;
+    NumerusStatus::Ok
+}
+
+/// Copies the `rows * cols` elements into `data` in the given layout. Returns
+/// `InvalidArgument` if `layout` is not a `NumerusLayout`.
+///
+/// # Safety
+///
+/// `matrix` must be null or a live matrix handle, and `data` null or point to
+/// `rows * cols` writable doubles.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matrix_copy_to(
+    matrix: *const NumerusMatrix,
+    data: *mut f64,
+    layout: c_int,
+) -> NumerusStatus {
+    let Some(matrix) = matrix.as_ref() else {
+        return NumerusStatus::NullPointer;
+    };
+    if data.is_null() {
+        return NumerusStatus::NullPointer;
+    }
+    let Some(layout) = self::layout(layout) else {
+        return NumerusStatus::InvalidArgument;
+    };
+    let (rows, cols) = (matrix.0.nrows(), matrix.0.ncols());
+    let data = slice::from_raw_parts_mut(data, rows * cols);
+    match layout {
+        NumerusLayout::RowMajor => data.copy_from_slice(matrix.0.data()),
+        NumerusLayout::ColMajor => {
+            for i in 0..rows {
+                for j in 0..cols {
+                    data[j * rows + i] = matrix.0[i][j];
+                }
+            }
+        }
+    }
+    NumerusStatus::Ok
+}
+
+/// Computes `a * b` into a new matrix stored in `*product`.
+///
+/// # Safety
+///
+/// `a` and `b` must be null or live matrix handles, and `product` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_matmul(
+    a: *const NumerusMatrix,
+    b: *const NumerusMatrix,
+    product: *mut *mut NumerusMatrix,
+) -> NumerusStatus {
+    let (Some(a), Some(b), Some(product)) = (a.as_ref(), b.as_ref(), product.as_mut()) else {
+        return NumerusStatus::NullPointer;
+    };
+    guard(|| match multiply_matrices(&a.0, &b.0) {
+        Ok(result) => {
+            *product = boxed(NumerusMatrix(result));
+            NumerusStatus::Ok
+        }
+        Err(_) => NumerusStatus::DimensionMismatch,
+    })
+}
+
+/// Factors the square matrix `a` with partial pivoting into a new handle stored in `*lu`.
+/// Free it with `numerus_lu_free`. Returns `Singular` when a pivot is zero or below
+/// `n * eps * ||A||`, since solutions would be meaningless.
+///
+/// # Safety
+///
+/// `a` must be null or a live matrix handle, and `lu` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_lu_factor(
+    a: *const NumerusMatrix,
+    lu: *mut *mut NumerusLu,
+) -> NumerusStatus {
+    let (Some(a), Some(lu)) = (a.as_ref(), lu.as_mut()) else {
+        return NumerusStatus::NullPointer;
+    };
+    let n = a.0.nrows();
+    if a.0.ncols() != n {
+        return NumerusStatus::DimensionMismatch;
+    }
+    guard(|| match LU::new(&a.0) {
+        Ok(factors) if !factors.is_singular() => {
+            *lu = boxed(NumerusLu { lu: factors, n });
+            NumerusStatus::Ok
+        }
+        _ => NumerusStatus::Singular,
+    })
+}
+
+/// Frees an LU decomposition. Null is ignored.
+///
+/// # Safety
+///
+/// `lu` must be null or come from `numerus_lu_factor`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_lu_free(lu: *mut NumerusLu) {
+    if !lu.is_null() {
+        drop(Box::from_raw(lu));
+    }
+}
+
+/// Solves `A x = b` for every column of `b` into a new matrix stored in `*x`.
+///
+/// # Safety
+///
+/// `lu` and `b` must be null or live handles, and `x` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_lu_solve(
+    lu: *const NumerusLu,
+    b: *const NumerusMatrix,
+    x: *mut *mut NumerusMatrix,
+) -> NumerusStatus {
+    let (Some(lu), Some(b), Some(x)) = (lu.as_ref(), b.as_ref(), x.as_mut()) else {
+        return NumerusStatus::NullPointer;
+    };
+    if b.0.nrows() != lu.n {
+        return NumerusStatus::DimensionMismatch;
+    }
+    guard(|| {
+        let mut solution = MatrixDouble::new(lu.n, b.0.ncols());
+        // Solving only fails when cancelled, and C callers have no way to cancel.
+        lu.lu
+            .solve_matrix(&b.0, &mut solution)
+            .expect("no cancellation token across the C API");
+        *x = boxed(NumerusMatrix(solution));
+        NumerusStatus::Ok
+    })
+}
+
+/// Stores the determinant of the factored matrix in `*det`.
+///
+/// # Safety
+///
+/// `lu` must be null or a live LU handle, and `det` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn numerus_lu_det(lu: *const NumerusLu, det: *mut f64) -> NumerusStatus {
+    let (Some(lu), Some(det)) = (lu.as_ref(), det.as_mut()) else {
+        return NumerusStatus::NullPointer;
+    };
+    *det = lu.lu.det();
+    NumerusStatus::Ok
+}
+
+/// A static, NUL-terminated description of a status code. Takes an `int` like the layout
+/// arguments, so any value is safe to pass; unknown ones give "unknown status".
+#[no_mangle]
+pub extern "C" fn numerus_status_message(status: c_int) -> *const c_char {
+    let message: &'static CStr = match status {
+        0 => c"ok",
+        1 => c"null pointer argument",
+        2 => c"matrix dimensions do not match",
+        3 => c"matrix is singular",
+        4 => c"index out of bounds",
+        5 => c"internal error in numerus",
+        6 => c"invalid argument",
+        _ => c"unknown status",
+    };
+    message.as_ptr()
+}
@@ -0,0 +1,166 @@
+/* Exercises the C API. Built and run by tests/c_api.rs, or by hand:
+ *
+ *     cargo build -p numerus-ffi
+ *     cc -I include tests/c/test_numerus.c ../target/debug/libnumerus_ffi.a \
+ *        -lpthread -ldl -lm -o test_numerus && ./test_numerus
+ */
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "numerus.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                   \
+  do {                                                                     \
+    if (!(condition)) {                                                    \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,    \
+              #condition);                                                 \
+      failures++;                                                          \
+    }                                                                      \
+  } while (0)
+
+#define CHECK_STATUS(call, expected)                                       \
+  do {                                                                     \
+    NumerusStatus status = (call);                                         \
+    if (status != (expected)) {                                            \
+      fprintf(stderr, "%s:%d: %s returned %s\n", __FILE__, __LINE__,       \
+              #call, numerus_status_message(status));                      \
+      failures++;                                                          \
+    }                                                                      \
+  } while (0)
+
+static void test_matrix_handles(void) {
+  /* The same matrix in C and Fortran order. */
+  const double row_major[6] = {1, 2, 3, 4, 5, 6};
+  const double col_major[6] = {1, 4, 2, 5, 3, 6};
+  NumerusMatrix *a = numerus_matrix_from_data(2, 3, row_major, NUMERUS_LAYOUT_ROW_MAJOR);
+  NumerusMatrix *b = numerus_matrix_from_data(2, 3, col_major, NUMERUS_LAYOUT_COL_MAJOR);
+  CHECK(numerus_matrix_rows(a) == 2 && numerus_matrix_cols(a) == 3);
+
+  double value = 0;
+  CHECK_STATUS(numerus_matrix_get(b, 1, 2, &value), NUMERUS_STATUS_OK);
+  CHECK(value == 6);
+  CHECK_STATUS(numerus_matrix_get(a, 2, 0, &value), NUMERUS_STATUS_OUT_OF_BOUNDS);
+  CHECK_STATUS(numerus_matrix_set(a, 0, 0, 10), NUMERUS_STATUS_OK);
+
+  double out[6];
+  CHECK_STATUS(numerus_matrix_copy_to(a, out, NUMERUS_LAYOUT_COL_MAJOR), NUMERUS_STATUS_OK);
+  CHECK(out[0] == 10 && out[1] == 4 && out[2] == 2);
+
+  CHECK_STATUS(numerus_matrix_copy_to(a, out, 2), NUMERUS_STATUS_INVALID_ARGUMENT);
+  CHECK_STATUS(numerus_matrix_copy_to(a, out, -1), NUMERUS_STATUS_INVALID_ARGUMENT);
+
+  CHECK(numerus_matrix_from_data(2, 2, NULL, NUMERUS_LAYOUT_ROW_MAJOR) == NULL);
+  CHECK(numerus_matrix_from_data(2, 3, row_major, 7) == NULL);
+  CHECK(numerus_matrix_from_data(SIZE_MAX, 2, row_major, NUMERUS_LAYOUT_ROW_MAJOR) == NULL);
+  CHECK(numerus_matrix_new(SIZE_MAX, 2) == NULL);
+  /* Fits in size_t but is over the element limit. */
+  CHECK(numerus_matrix_new(SIZE_MAX / 2, 2) == NULL);
+  CHECK(numerus_matrix_new(10001, 10000) == NULL);
+  CHECK(numerus_matrix_rows(NULL) == 0);
+  numerus_matrix_free(a);
+  numerus_matrix_free(b);
+  numerus_matrix_free(NULL);
+}
+
+static void test_matmul(void) {
+  const double a_data[4] = {1, 2, 3, 4};
+  const double b_data[4] = {5, 6, 7, 8};
+  NumerusMatrix *a = numerus_matrix_from_data(2, 2, a_data, NUMERUS_LAYOUT_ROW_MAJOR);
+  NumerusMatrix *b = numerus_matrix_from_data(2, 2, b_data, NUMERUS_LAYOUT_ROW_MAJOR);
+  NumerusMatrix *c = NULL;
+  CHECK_STATUS(numerus_matmul(a, b, &c), NUMERUS_STATUS_OK);
+
+  double out[4];
+  CHECK_STATUS(numerus_matrix_copy_to(c, out, NUMERUS_LAYOUT_ROW_MAJOR), NUMERUS_STATUS_OK);
+  CHECK(out[0] == 19 && out[1] == 22 && out[2] == 43 && out[3] == 50);
+
+  NumerusMatrix *row = numerus_matrix_new(1, 3);
+  NumerusMatrix *unchanged = NULL;
+  CHECK_STATUS(numerus_matmul(a, row, &unchanged), NUMERUS_STATUS_DIMENSION_MISMATCH);
+  CHECK(unchanged == NULL);
+  CHECK_STATUS(numerus_matmul(a, NULL, &unchanged), NUMERUS_STATUS_NULL_POINTER);
+
+  numerus_matrix_free(a);
+  numerus_matrix_free(b);
+  numerus_matrix_free(c);
+  numerus_matrix_free(row);
+}
+
+static void test_lu(void) {
+  const double a_data[9] = {2, 1, 1, 4, -6, 0, -2, 7, 2};
+  const double b_data[3] = {5, -2, 9};
+  NumerusMatrix *a = numerus_matrix_from_data(3, 3, a_data, NUMERUS_LAYOUT_ROW_MAJOR);
+  NumerusMatrix *b = numerus_matrix_from_data(3, 1, b_data, NUMERUS_LAYOUT_ROW_MAJOR);
+
+  NumerusLu *lu = NULL;
+  CHECK_STATUS(numerus_lu_factor(a, &lu), NUMERUS_STATUS_OK);
+
+  double det = 0;
+  CHECK_STATUS(numerus_lu_det(lu, &det), NUMERUS_STATUS_OK);
+  CHECK(fabs(det - -16) < 1e-12);
+
+  NumerusMatrix *x = NULL;
+  CHECK_STATUS(numerus_lu_solve(lu, b, &x), NUMERUS_STATUS_OK);
+  double solution[3];
+  CHECK_STATUS(numerus_matrix_copy_to(x, solution, NUMERUS_LAYOUT_ROW_MAJOR), NUMERUS_STATUS_OK);
+  CHECK(fabs(solution[0] - 1) < 1e-12);
+  CHECK(fabs(solution[1] - 1) < 1e-12);
+  CHECK(fabs(solution[2] - 2) < 1e-12);
+
+  NumerusMatrix *wrong = numerus_matrix_new(2, 1);
+  NumerusMatrix *unchanged = NULL;
+  CHECK_STATUS(numerus_lu_solve(lu, wrong, &unchanged), NUMERUS_STATUS_DIMENSION_MISMATCH);
+
+  NumerusMatrix *rectangular = numerus_matrix_new(2, 3);
+  NumerusLu *none = NULL;
+  CHECK_STATUS(numerus_lu_factor(rectangular, &none), NUMERUS_STATUS_DIMENSION_MISMATCH);
+
+  const double singular_data[4] = {1, 2, 0, 0};
+  NumerusMatrix *singular = numerus_matrix_from_data(2, 2, singular_data, NUMERUS_LAYOUT_ROW_MAJOR);
+  CHECK_STATUS(numerus_lu_factor(singular, &none), NUMERUS_STATUS_SINGULAR);
+  CHECK(none == NULL);
+
+  /* Rank deficient without a zero row: elimination leaves a zero pivot. */
+  const double rank_one_data[4] = {1, 2, 2, 4};
+  NumerusMatrix *rank_one = numerus_matrix_from_data(2, 2, rank_one_data, NUMERUS_LAYOUT_ROW_MAJOR);
+  CHECK_STATUS(numerus_lu_factor(rank_one, &none), NUMERUS_STATUS_SINGULAR);
+  /* Rounding leaves a pivot around 1e-17 instead of zero. */
+  const double nearly_data[4] = {0.1, 0.3, 0.2, 0.6};
+  NumerusMatrix *nearly = numerus_matrix_from_data(2, 2, nearly_data, NUMERUS_LAYOUT_ROW_MAJOR);
+  CHECK_STATUS(numerus_lu_factor(nearly, &none), NUMERUS_STATUS_SINGULAR);
+  CHECK(none == NULL);
+
+  numerus_lu_free(lu);
+  numerus_lu_free(NULL);
+  numerus_matrix_free(a);
+  numerus_matrix_free(b);
+  numerus_matrix_free(x);
+  numerus_matrix_free(wrong);
+  numerus_matrix_free(rectangular);
+  numerus_matrix_free(singular);
+  numerus_matrix_free(rank_one);
+  numerus_matrix_free(nearly);
+}
+
+static void test_status_messages(void) {
+  CHECK(strcmp(numerus_status_message(NUMERUS_STATUS_SINGULAR), "matrix is singular") == 0);
+  CHECK(strcmp(numerus_status_message(42), "unknown status") == 0);
+  CHECK(strcmp(numerus_status_message(-1), "unknown status") == 0);
+}
+
+int main(void) {
+  test_matrix_handles();
+  test_matmul();
+  test_lu();
+  test_status_messages();
+  if (failures > 0) {
+    fprintf(stderr, "%d checks failed\n", failures);
+    return 1;
+  }
+  printf("ok\n");
+  return 0;
+}
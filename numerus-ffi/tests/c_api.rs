@@ -0,0 +1,64 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Integration tests live in target/<profile>/deps, next to the libraries of this crate.
+fn profile_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+// Links against the shared library and finds it again through an rpath, which Windows lacks.
+#[cfg(unix)]
+#[test]
+fn c_test_program_passes() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out = profile_dir();
+    let program = out.join("test_numerus_c");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(compiler)
+        .arg("-Wall")
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg(manifest.join("tests/c/test_numerus.c"))
+        // The shared library brings its own dependencies along. A workspace-wide build unifies
+        // features, which can leave the static archive needing GUI and ZeroMQ libraries too.
+        .arg(out.join(format!(
+            "{}numerus_ffi{}",
+            env::consts::DLL_PREFIX,
+            env::consts::DLL_SUFFIX
+        )))
+        .arg(format!("-Wl,-rpath,{}", out.display()))
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("could not run the C compiler");
+    assert!(status.success(), "compiling tests/c/test_numerus.c failed");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn header_declares_every_exported_function() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let source = fs::read_to_string(manifest.join("src/lib.rs")).unwrap();
+    let header = fs::read_to_string(manifest.join("include/numerus.h")).unwrap();
+    let exported = source
+        .split("extern \"C\" fn ")
+        .skip(1)
+        .map(|rest| &rest[..rest.find('(').unwrap()]);
+    for name in exported {
+        assert!(
+            header.contains(&format!(" {}(", name)) || header.contains(&format!("*{}(", name)),
+            "include/numerus.h is missing {}; regenerate it with cbindgen",
+            name
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use numerus::decomp::LU;
+use numerus::interpreter::{evaluate_command, is_identifier, matrix_to_string};
+use numerus::math_utilities::MatrixDouble;
+use numerus::ops;
+use numerus::output::Value;
+use numerus::script::split_statements;
+use numpy::ndarray::ArrayView2;
+use numpy::{AllowTypeChange, PyArray2, PyArrayLike1, PyArrayLike2};
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::IntoPyDict;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+
+fn value_error(err: impl ToString) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+/// A dense matrix of floats, stored row by row.
+#[pyclass(name = "Matrix", module = "numerus")]
+#[derive(Clone)]
+struct PyMatrix {
+    inner: MatrixDouble,
+}
+
+impl PyMatrix {
+    fn new_py(py: Python<'_>, inner: MatrixDouble) -> PyResult<PyObject> {
+        Ok(Py::new(py, PyMatrix { inner })?.into_py(py))
+    }
+}
+
+// Accepts a Matrix, or anything NumPy can turn into a 1-D or 2-D float array. 1-D arrays
+// become column vectors. Numerus matrices own their storage, so this always copies; only
+// `to_numpy` and `__array__` share memory.
+fn to_matrix(obj: &PyAny) -> PyResult<MatrixDouble> {
+    if let Ok(matrix) = obj.extract::<PyRef<PyMatrix>>() {
+        return Ok(matrix.inner.clone());
+    }
+    if let Ok(array) = obj.extract::<PyArrayLike2<f64, AllowTypeChange>>() {
+        let view = array.as_array();
+        let (rows, cols) = view.dim();
+        // Row-major arrays are copied in one go, other layouts element by element.
+        return Ok(match view.as_slice() {
+            Some(slice) => MatrixDouble::from_slice(rows, cols, slice),
+            None => MatrixDouble::from_fn(rows, cols, |i, j| view[[i, j]]),
+        });
+    }
+    let vector = obj.extract::<PyArrayLike1<f64, AllowTypeChange>>()?;
+    let view = vector.as_array();
+    Ok(MatrixDouble::from_fn(view.len(), 1, |i, _| view[i]))
+}
+
+#[pymethods]
+impl PyMatrix {
+    #[new]
+    fn new(data: &PyAny) -> PyResult<Self> {
+        Ok(PyMatrix {
+            inner: to_matrix(data)?,
+        })
+    }
+
+    #[staticmethod]
+    fn zeros(rows: usize, cols: usize) -> Self {
+        PyMatrix {
+            inner: MatrixDouble::zeros(rows, cols),
+        }
+    }
+
+    #[staticmethod]
+    fn identity(n: usize) -> Self {
+        PyMatrix {
+            inner: MatrixDouble::identity(n),
+        }
+    }
+
+    #[getter]
+    fn shape(&self) -> (usize, usize) {
+        (self.inner.nrows(), self.inner.ncols())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.nrows()
+    }
+
+    fn __getitem__(&self, index: (usize, usize)) -> PyResult<f64> {
+        let (i, j) = index;
+        if i >= self.inner.nrows() || j >= self.inner.ncols() {
+            return Err(PyIndexError::new_err("matrix index out of range"));
+        }
+        Ok(self.inner[i][j])
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Matrix({}x{}):\n{}",
+            self.inner.nrows(),
+            self.inner.ncols(),
+            matrix_to_string(&self.inner).trim_end()
+        )
+    }
+
+    /// A read-only NumPy view of the elements. It shares memory with the matrix, which it
+    /// keeps alive.
+    fn to_numpy<'py>(slf: &'py PyCell<Self>) -> PyResult<&'py PyArray2<f64>> {
+        let py = slf.py();
+        let matrix = slf.borrow();
+        let shape = (matrix.inner.nrows(), matrix.inner.ncols());
+        let view = ArrayView2::from_shape(shape, matrix.inner.data()).map_err(value_error)?;
+        // SAFETY: the array holds a reference to `slf` as its base object, so the matrix
+        // outlives the view. PyMatrix has no `&mut self` methods and never hands out
+        // `PyRefMut`, so `inner` is not resized, reallocated or written while the view exists.
+        // A method that mutates the matrix in place would break this.
+        let array = unsafe { PyArray2::borrow_from_array(&view, slf) };
+        array.call_method("setflags", (), Some([("write", false)].into_py_dict(py)))?;
+        Ok(array)
+    }
+
+    // Lets `np.asarray(m)` share memory with the matrix. Asking for another dtype, or for
+    // `copy=True`, copies.
+    #[pyo3(signature = (dtype = None, copy = None))]
+    fn __array__<'py>(
+        slf: &'py PyCell<Self>,
+        dtype: Option<&PyAny>,
+        copy: Option<bool>,
+    ) -> PyResult<&'py PyAny> {
+        let array: &PyAny = Self::to_numpy(slf)?;
+        match (dtype, copy) {
+            (Some(dtype), _) => array.call_method1("astype", (dtype,)),
+            (None, Some(true)) => array.call_method0("copy"),
+            (None, _) => Ok(array),
+        }
+    }
+
+    fn __add__(&self, other: &PyAny) -> PyResult<Self> {
+        add(&self.inner, other)
+    }
+
+    fn __sub__(&self, other: &PyAny) -> PyResult<Self> {
+        subtract(&self.inner, other)
+    }
+
+    fn __matmul__(&self, other: &PyAny) -> PyResult<Self> {
+        matmul(&self.inner, other)
+    }
+}
+
+fn add(a: &MatrixDouble, b: &PyAny) -> PyResult<PyMatrix> {
+    let inner = ops::add_matrices(a, &to_matrix(b)?).map_err(value_error)?;
+    Ok(PyMatrix { inner })
+}
+
+fn subtract(a: &MatrixDouble, b: &PyAny) -> PyResult<PyMatrix> {
+    let inner = ops::subtract_matrices(a, &to_matrix(b)?).map_err(value_error)?;
+    Ok(PyMatrix { inner })
+}
+
+fn matmul(a: &MatrixDouble, b: &PyAny) -> PyResult<PyMatrix> {
+    let inner = ops::multiply_matrices(a, &to_matrix(b)?).map_err(value_error)?;
+    Ok(PyMatrix { inner })
+}
+
+/// LU decomposition with partial pivoting of a square matrix.
+#[pyclass(name = "LU", module = "numerus")]
+struct PyLu {
+    inner: LU,
+    n: usize,
+}
+
+#[pymethods]
+impl PyLu {
+    #[new]
+    fn new(a: &PyAny) -> PyResult<Self> {
+        let a = to_matrix(a)?;
+        if a.nrows() != a.ncols() {
+            return Err(value_error("LU decomposition needs a square matrix"));
+        }
+        let inner = LU::new(&a).map_err(value_error)?;
+        // A rank-deficient matrix factors with a negligible pivot, and solving with it would
+        // return huge values instead of raising.
+        if inner.is_singular() {
+            return Err(value_error("Singular matrix in LU decomposition"));
+        }
+        Ok(PyLu { inner, n: a.nrows() })
+    }
+
+    /// Solves A X = B. A 1-D right-hand side is taken as a column vector.
+    fn solve(&self, b: &PyAny) -> PyResult<PyMatrix> {
+        let b = to_matrix(b)?;
+        if b.nrows() != self.n {
+            return Err(value_error(format!(
+                "Right-hand side has {} rows, expected {}",
+                b.nrows(),
+                self.n
+            )));
+        }
+        let mut x = MatrixDouble::new(self.n, b.ncols());
+        self.inner.solve_matrix(&b, &mut x).map_err(value_error)?;
+        Ok(PyMatrix { inner: x })
+    }
+
+    fn det(&self) -> f64 {
+        self.inner.det()
+    }
+
+    fn inverse(&self) -> PyResult<PyMatrix> {
+        let mut inverse = MatrixDouble::new(self.n, self.n);
+        self.inner.inverse(&mut inverse).map_err(value_error)?;
+        Ok(PyMatrix { inner: inverse })
+    }
+
+    /// The row interchanged with row i at elimination step i.
+    fn pivots(&self) -> Vec<usize> {
+        self.inner.pivots()
+    }
+}
+
+/// A workspace of named matrices, evaluated with the Numerus command language.
+#[pyclass(name = "Interpreter", module = "numerus")]
+#[derive(Default)]
+struct PyInterpreter {
+    matrices: HashMap<String, MatrixDouble>,
+}
+
+#[pymethods]
+impl PyInterpreter {
+    #[new]
+    fn new() -> Self {
+        PyInterpreter::default()
+    }
+
+    /// Runs one or more statements and returns the value of the last one: a float for
+    /// scalars, a Matrix for matrices, and the printed text otherwise.
+    fn eval(&mut self, py: Python<'_>, source: &str) -> PyResult<PyObject> {
+        let mut result = py.None();
+        for statement in split_statements(source) {
+            // A panic would surface as pyo3's PanicException, which is not an Exception.
+            let output = panic::catch_unwind(AssertUnwindSafe(|| {
+                evaluate_command(&statement.text, &mut self.matrices)
+            }))
+            .unwrap_or_else(|_| Err("Internal error while evaluating the statement.".to_string()))
+            .map_err(value_error)?;
+            result = match output.value {
+                Value::Scalar(x) => x.into_py(py),
+                Value::Matrix(m) | Value::Lu { lu: m, .. } => PyMatrix::new_py(py, m)?,
+                Value::Vector(v) => {
+                    PyMatrix::new_py(py, MatrixDouble::from_slice(v.size(), 1, v.data()))?
+                }
+                Value::Plot(_) | Value::None => output.text.into_py(py),
+            };
+        }
+        Ok(result)
+    }
+
+    fn __getitem__(&self, name: &str) -> PyResult<PyMatrix> {
+        match self.matrices.get(name) {
+            Some(matrix) => Ok(PyMatrix {
+                inner: matrix.clone(),
+            }),
+            None => Err(PyKeyError::new_err(name.to_string())),
+        }
+    }
+
+    fn __setitem__(&mut self, name: &str, value: &PyAny) -> PyResult<()> {
+        if !is_identifier(name) {
+            return Err(value_error(format!("Invalid variable name: {}", name)));
+        }
+        self.matrices.insert(name.to_string(), to_matrix(value)?);
+        Ok(())
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.matrices.contains_key(name)
+    }
+
+    fn variables(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.matrices.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+#[pyfunction(name = "add")]
+fn py_add(a: &PyAny, b: &PyAny) -> PyResult<PyMatrix> {
+    add(&to_matrix(a)?, b)
+}
+
+#[pyfunction(name = "subtract")]
+fn py_subtract(a: &PyAny, b: &PyAny) -> PyResult<PyMatrix> {
+    subtract(&to_matrix(a)?, b)
+}
+
+#[pyfunction(name = "matmul")]
+fn py_matmul(a: &PyAny, b: &PyAny) -> PyResult<PyMatrix> {
+    matmul(&to_matrix(a)?, b)
+}
+
+#[pyfunction]
+fn expm(a: &PyAny) -> PyResult<PyMatrix> {
+    let inner = ops::expm(&to_matrix(a)?).map_err(value_error)?;
+    Ok(PyMatrix { inner })
+}
+
+#[pyfunction]
+fn logm(a: &PyAny) -> PyResult<PyMatrix> {
+    let inner = ops::logm(&to_matrix(a)?).map_err(value_error)?;
+    Ok(PyMatrix { inner })
+}
+
+#[pyfunction]
+fn sqrtm(a: &PyAny) -> PyResult<PyMatrix> {
+    let inner = ops::sqrtm(&to_matrix(a)?).map_err(value_error)?;
+    Ok(PyMatrix { inner })
+}
+
+#[pyfunction]
+fn powm(a: &PyAny, p: f64) -> PyResult<PyMatrix> {
+    let inner = ops::powm(&to_matrix(a)?, p).map_err(value_error)?;
+    Ok(PyMatrix { inner })
+}
+
+/// Evaluates statements in a fresh workspace, e.g. `eval("A = magic(4); A * A")`.
+#[pyfunction]
+fn eval(py: Python<'_>, source: &str) -> PyResult<PyObject> {
+    PyInterpreter::default().eval(py, source)
+}
+
+#[pymodule]
+#[pyo3(name = "numerus")]
+fn numerus_py(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyMatrix>()?;
+    m.add_class::<PyLu>()?;
+    m.add_class::<PyInterpreter>()?;
+    m.add_function(wrap_pyfunction!(py_add, m)?)?;
+    m.add_function(wrap_pyfunction!(py_subtract, m)?)?;
+    m.add_function(wrap_pyfunction!(py_matmul, m)?)?;
+    m.add_function(wrap_pyfunction!(expm, m)?)?;
+    m.add_function(wrap_pyfunction!(logm, m)?)?;
+    m.add_function(wrap_pyfunction!(sqrtm, m)?)?;
+    m.add_function(wrap_pyfunction!(powm, m)?)?;
+    m.add_function(wrap_pyfunction!(eval, m)?)?;
+    Ok(())
+}
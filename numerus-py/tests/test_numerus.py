@@ -0,0 +1,90 @@
+import numpy as np
+import pytest
+
+import numerus
+
+
+def test_matrix_round_trips_through_numpy():
+    a = np.arange(6, dtype=np.float64).reshape(2, 3)
+    m = numerus.Matrix(a)
+    assert m.shape == (2, 3)
+    assert m[1, 2] == 5.0
+    np.testing.assert_array_equal(np.asarray(m), a)
+
+
+def test_matrix_accepts_lists_integers_and_fortran_order():
+    assert numerus.Matrix([[1, 2], [3, 4]])[1, 0] == 3.0
+    f = np.asfortranarray([[1.0, 2.0], [3.0, 4.0]])
+    np.testing.assert_array_equal(np.asarray(numerus.Matrix(f)), f)
+
+
+def test_numpy_view_shares_memory_and_is_read_only():
+    m = numerus.Matrix([[1.0, 2.0], [3.0, 4.0]])
+    view = m.to_numpy()
+    assert np.shares_memory(view, np.asarray(m))
+    assert not view.flags.writeable
+    with pytest.raises(ValueError):
+        view[0, 0] = 10.0
+    del m
+    assert view[1, 1] == 4.0
+
+
+def test_operations_match_numpy():
+    rng = np.random.default_rng(1)
+    a, b = rng.random((4, 4)), rng.random((4, 4))
+    np.testing.assert_allclose(np.asarray(numerus.Matrix(a) @ b), a @ b)
+    np.testing.assert_allclose(np.asarray(numerus.add(a, b)), a + b)
+    np.testing.assert_allclose(np.asarray(numerus.subtract(a, b)), a - b)
+    np.testing.assert_allclose(np.asarray(numerus.matmul(a, b)), a @ b)
+    with pytest.raises(ValueError):
+        numerus.matmul(a, np.ones((3, 2)))
+
+
+def test_lu_matches_numpy_solve_and_det():
+    rng = np.random.default_rng(2)
+    a = rng.random((5, 5)) + 5 * np.eye(5)
+    b = rng.random(5)
+    lu = numerus.LU(a)
+    np.testing.assert_allclose(np.asarray(lu.solve(b))[:, 0], np.linalg.solve(a, b))
+    np.testing.assert_allclose(lu.det(), np.linalg.det(a))
+    np.testing.assert_allclose(np.asarray(lu.inverse()), np.linalg.inv(a))
+
+
+def test_lu_rejects_singular_and_rectangular_matrices():
+    with pytest.raises(ValueError):
+        numerus.LU([[1.0, 2.0], [0.0, 0.0]])
+    with pytest.raises(ValueError):
+        numerus.LU([[1.0, 2.0], [2.0, 4.0]])
+    with pytest.raises(ValueError):
+        numerus.LU([[0.1, 0.3], [0.2, 0.6]])
+    with pytest.raises(ValueError):
+        numerus.LU(np.ones((2, 3)))
+
+
+def test_matrix_functions_match_scipy():
+    linalg = pytest.importorskip("scipy.linalg")
+    a = np.array([[1.0, 0.5], [0.25, 2.0]])
+    np.testing.assert_allclose(np.asarray(numerus.expm(a)), linalg.expm(a))
+    np.testing.assert_allclose(np.asarray(numerus.sqrtm(a)), linalg.sqrtm(a))
+    np.testing.assert_allclose(np.asarray(numerus.logm(a)), linalg.logm(a), atol=1e-12)
+
+
+def test_eval_returns_floats_and_matrices():
+    assert numerus.eval("2 + 3") == 5.0
+    m = numerus.eval("A = magic(3); A * eye(3)")
+    np.testing.assert_array_equal(np.asarray(m), [[8, 1, 6], [3, 5, 7], [4, 9, 2]])
+    with pytest.raises(ValueError):
+        numerus.eval("undefined_matrix")
+    with pytest.raises(ValueError):
+        numerus.eval("inv([0 0; 0 0])")
+
+
+def test_interpreter_keeps_a_workspace():
+    interp = numerus.Interpreter()
+    interp["B"] = np.eye(2)
+    interp.eval("C = B + B")
+    assert "C" in interp
+    assert interp.variables() == ["B", "C"]
+    np.testing.assert_array_equal(np.asarray(interp["C"]), 2 * np.eye(2))
+    with pytest.raises(KeyError):
+        interp["D"]
@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The error reported by computations that stopped because they were cancelled.
+pub const CANCELLED: &str = "Computation cancelled";
+
+/// Asks a running computation to stop. Clones share the same flag, so one can be handed to
+/// the thread doing the work and the other kept to cancel it.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+// Puts the previous token back when dropped, also when `f` panics.
+struct Restore(Option<CancelToken>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// Runs `f` with `token` as the token that `cancelled` checks on this thread.
+pub fn with_token<R>(token: &CancelToken, f: impl FnOnce() -> R) -> R {
+    let _restore = Restore(CURRENT.with(|current| current.replace(Some(token.clone()))));
+    f()
+}
+
+/// True once the token of the computation running on this thread has been cancelled.
+/// Long loops (LU, matrix products, iterative matrix functions) check this and return
+/// `Err(CANCELLED)`.
+pub fn cancelled() -> bool {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lu_decomposition::LU;
+    use crate::math_utilities::MatrixDouble;
+    use crate::matrix_operations::multiply_matrices;
+    use std::panic;
+
+    #[test]
+    fn library_kernels_report_cancellation() {
+        let token = CancelToken::new();
+        token.cancel();
+        let a = MatrixDouble::identity(3);
+        with_token(&token, || {
+            assert_eq!(multiply_matrices(&a, &a).unwrap_err(), CANCELLED);
+            assert_eq!(LU::new(&a).err(), Some(CANCELLED));
+        });
+        let lu = LU::new(&a).unwrap();
+        let mut x = MatrixDouble::new(3, 3);
+        with_token(&token, || {
+            assert_eq!(lu.solve_matrix(&a, &mut x), Err(CANCELLED));
+            assert_eq!(lu.inverse(&mut x), Err(CANCELLED));
+        });
+        assert!(multiply_matrices(&a, &a).is_ok());
+    }
+
+    #[test]
+    fn with_token_restores_the_previous_token_after_a_panic() {
+        let outer = CancelToken::new();
+        let inner = CancelToken::new();
+        inner.cancel();
+        with_token(&outer, || {
+            let result = panic::catch_unwind(|| with_token(&inner, || panic!("kernel bug")));
+            assert!(result.is_err());
+            assert!(!cancelled());
+        });
+        assert!(!cancelled());
+    }
+}
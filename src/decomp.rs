@@ -0,0 +1 @@
+pub use crate::lu_decomposition::LU;
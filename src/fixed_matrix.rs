@@ -0,0 +1,514 @@
+use crate::math_utilities::{Matrix, Vector};
+use num_traits::{Float, Num};
+use std::ops::{Add, Index, IndexMut, Mul, Neg, Sub};
+
+/// A matrix whose dimensions are part of its type, so that mismatched sizes fail to compile.
+/// Elements are stored inline row by row, which keeps small matrices on the stack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SMatrix<T, const R: usize, const C: usize> {
+    data: [[T; C]; R],
+}
+
+/// A vector whose length is part of its type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SVector<T, const N: usize> {
+    data: [T; N],
+}
+
+pub type Matrix2d = SMatrix<f64, 2, 2>;
+pub type Matrix3d = SMatrix<f64, 3, 3>;
+pub type Matrix4d = SMatrix<f64, 4, 4>;
+pub type Vector2d = SVector<f64, 2>;
+pub type Vector3d = SVector<f64, 3>;
+pub type Vector4d = SVector<f64, 4>;
+
+impl<T: Copy, const R: usize, const C: usize> SMatrix<T, R, C> {
+    pub const fn from_rows(data: [[T; C]; R]) -> Self {
+        SMatrix { data }
+    }
+
+    /// Builds a matrix whose element (i, j) is `f(i, j)`.
+    pub fn from_fn<F: FnMut(usize, usize) -> T>(mut f: F) -> Self {
+        SMatrix {
+            data: std::array::from_fn(|i| std::array::from_fn(|j| f(i, j))),
+        }
+    }
+
+    pub const fn nrows(&self) -> usize {
+        R
+    }
+
+    pub const fn ncols(&self) -> usize {
+        C
+    }
+
+    pub fn rows(&self) -> &[[T; C]; R] {
+        &self.data
+    }
+
+    pub fn transpose(&self) -> SMatrix<T, C, R> {
+        SMatrix::from_fn(|i, j| self.data[j][i])
+    }
+
+    pub fn column(&self, j: usize) -> SVector<T, R> {
+        SVector::from_fn(|i| self.data[i][j])
+    }
+}
+
+impl<T: Num + Copy, const R: usize, const C: usize> SMatrix<T, R, C> {
+    pub fn zeros() -> Self {
+        SMatrix::from_fn(|_, _| T::zero())
+    }
+
+    /// Ones on the main diagonal and zeros elsewhere, also for non-square sizes.
+    pub fn identity() -> Self {
+        SMatrix::from_fn(|i, j| if i == j { T::one() } else { T::zero() })
+    }
+}
+
+impl<T: Num + Copy, const N: usize> SMatrix<T, N, N> {
+    pub fn from_diag(diag: [T; N]) -> Self {
+        SMatrix::from_fn(|i, j| if i == j { diag[i] } else { T::zero() })
+    }
+
+    pub fn trace(&self) -> T {
+        (0..N).fold(T::zero(), |sum, i| sum + self.data[i][i])
+    }
+}
+
+impl<T: Float, const R: usize, const C: usize> SMatrix<T, R, C> {
+    /// Square root of the sum of the squared elements.
+    pub fn frobenius_norm(&self) -> T {
+        self.data
+            .iter()
+            .flatten()
+            .fold(T::zero(), |sum, &x| sum + x * x)
+            .sqrt()
+    }
+}
+
+impl<T: Float, const N: usize> SMatrix<T, N, N> {
+    // The pivot test of `LU::is_singular`, |pivot| <= n * eps * ||A|| with the maximum
+    // absolute row sum as the norm, raised to the power n to compare with the determinant.
+    fn is_singular(&self, det: T) -> bool {
+        let norm = self.data.iter().fold(T::zero(), |norm, row| {
+            norm.max(row.iter().fold(T::zero(), |sum, &x| sum + x.abs()))
+        });
+        let n = T::from(N).unwrap();
+        det.abs() <= n * T::epsilon() * norm.powi(N as i32)
+    }
+}
+
+// Closed-form determinants and inverses for the sizes where they beat elimination. The
+// inverses return None when the matrix is singular to working precision. That includes
+// matrices whose norm underflows when raised to the power n: `Matrix2d::from_diag([1e-200,
+// 1e-200])` is well conditioned, but gives None, so scale such matrices first or go through
+// `LU`.
+
+impl<T: Num + Copy> SMatrix<T, 1, 1> {
+    pub fn det(&self) -> T {
+        self.data[0][0]
+    }
+}
+
+impl<T: Float> SMatrix<T, 1, 1> {
+    /// None when the matrix is singular to working precision, like `LU::is_singular`.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.det();
+        (!self.is_singular(det)).then(|| SMatrix::from_rows([[T::one() / det]]))
+    }
+}
+
+impl<T: Num + Copy> SMatrix<T, 2, 2> {
+    pub fn det(&self) -> T {
+        let [[a, b], [c, d]] = self.data;
+        a * d - b * c
+    }
+}
+
+impl<T: Float> SMatrix<T, 2, 2> {
+    /// None when the matrix is singular to working precision, like `LU::is_singular`.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.det();
+        if self.is_singular(det) {
+            return None;
+        }
+        let [[a, b], [c, d]] = self.data;
+        let zero = T::zero();
+        Some(SMatrix::from_rows([
+            [d / det, (zero - b) / det],
+            [(zero - c) / det, a / det],
+        ]))
+    }
+}
+
+impl<T: Num + Copy> SMatrix<T, 3, 3> {
+    pub fn det(&self) -> T {
+        let [[a, b, c], [d, e, f], [g, h, i]] = self.data;
+        a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g)
+    }
+}
+
+impl<T: Float> SMatrix<T, 3, 3> {
+    /// None when the matrix is singular to working precision, like `LU::is_singular`.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.det();
+        if self.is_singular(det) {
+            return None;
+        }
+        let [[a, b, c], [d, e, f], [g, h, i]] = self.data;
+        // The adjugate is the transposed matrix of cofactors.
+        Some(
+            SMatrix::from_rows([
+                [e * i - f * h, c * h - b * i, b * f - c * e],
+                [f * g - d * i, a * i - c * g, c * d - a * f],
+                [d * h - e * g, b * g - a * h, a * e - b * d],
+            ]) * (T::one() / det),
+        )
+    }
+}
+
+impl<T: Num + Copy> SMatrix<T, 4, 4> {
+    // Determinants of the 2x2 minors of the top two rows (s) and the bottom two rows (c),
+    // from which both the determinant and the adjugate follow (Laplace expansion).
+    fn minors(&self) -> ([T; 6], [T; 6]) {
+        let m = &self.data;
+        let s = [
+            m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            m[0][0] * m[1][2] - m[1][0] * m[0][2],
+            m[0][0] * m[1][3] - m[1][0] * m[0][3],
+            m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            m[0][1] * m[1][3] - m[1][1] * m[0][3],
+            m[0][2] * m[1][3] - m[1][2] * m[0][3],
+        ];
+        let c = [
+            m[2][0] * m[3][1] - m[3][0] * m[2][1],
+            m[2][0] * m[3][2] - m[3][0] * m[2][2],
+            m[2][0] * m[3][3] - m[3][0] * m[2][3],
+            m[2][1] * m[3][2] - m[3][1] * m[2][2],
+            m[2][1] * m[3][3] - m[3][1] * m[2][3],
+            m[2][2] * m[3][3] - m[3][2] * m[2][3],
+        ];
+        (s, c)
+    }
+
+    pub fn det(&self) -> T {
+        let (s, c) = self.minors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+}
+
+impl<T: Float> SMatrix<T, 4, 4> {
+    /// None when the matrix is singular to working precision, like `LU::is_singular`.
+    pub fn inverse(&self) -> Option<Self> {
+        let (s, c) = self.minors();
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if self.is_singular(det) {
+            return None;
+        }
+        let m = &self.data;
+        let zero = T::zero();
+        let adjugate = SMatrix::from_rows([
+            [
+                m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3],
+                zero - m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3],
+                m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3],
+                zero - m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3],
+            ],
+            [
+                zero - m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1],
+                m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1],
+                zero - m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1],
+                m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1],
+            ],
+            [
+                m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0],
+                zero - m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0],
+                m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0],
+                zero - m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0],
+            ],
+            [
+                zero - m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0],
+                m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0],
+                zero - m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0],
+                m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0],
+            ],
+        ]);
+        Some(adjugate * (T::one() / det))
+    }
+}
+
+impl<T, const R: usize, const C: usize> Index<usize> for SMatrix<T, R, C> {
+    type Output = [T; C];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.data[index]
+    }
+}
+
+impl<T, const R: usize, const C: usize> IndexMut<usize> for SMatrix<T, R, C> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.data[index]
+    }
+}
+
+impl<T: Num + Copy, const R: usize, const C: usize> Add for SMatrix<T, R, C> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        SMatrix::from_fn(|i, j| self.data[i][j] + other.data[i][j])
+    }
+}
+
+impl<T: Num + Copy, const R: usize, const C: usize> Sub for SMatrix<T, R, C> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        SMatrix::from_fn(|i, j| self.data[i][j] - other.data[i][j])
+    }
+}
+
+impl<T: Neg<Output = T> + Copy, const R: usize, const C: usize> Neg for SMatrix<T, R, C> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        SMatrix::from_fn(|i, j| -self.data[i][j])
+    }
+}
+
+impl<T: Num + Copy, const R: usize, const C: usize> Mul<T> for SMatrix<T, R, C> {
+    type Output = Self;
+
+    fn mul(self, scalar: T) -> Self {
+        SMatrix::from_fn(|i, j| self.data[i][j] * scalar)
+    }
+}
+
+impl<T: Num + Copy, const R: usize, const K: usize, const C: usize> Mul<SMatrix<T, K, C>>
+    for SMatrix<T, R, K>
+{
+    type Output = SMatrix<T, R, C>;
+
+    fn mul(self, other: SMatrix<T, K, C>) -> SMatrix<T, R, C> {
+        SMatrix::from_fn(|i, j| {
+            (0..K).fold(T::zero(), |sum, k| sum + self.data[i][k] * other.data[k][j])
+        })
+    }
+}
+
+impl<T: Num + Copy, const R: usize, const C: usize> Mul<SVector<T, C>> for SMatrix<T, R, C> {
+    type Output = SVector<T, R>;
+
+    fn mul(self, vector: SVector<T, C>) -> SVector<T, R> {
+        SVector::from_fn(|i| (0..C).fold(T::zero(), |sum, k| sum + self.data[i][k] * vector[k]))
+    }
+}
+
+impl<T: Copy, const N: usize> SVector<T, N> {
+    pub const fn new(data: [T; N]) -> Self {
+        SVector { data }
+    }
+
+    pub fn from_fn<F: FnMut(usize) -> T>(f: F) -> Self {
+        SVector {
+            data: std::array::from_fn(f),
+        }
+    }
+
+    pub const fn size(&self) -> usize {
+        N
+    }
+
+    pub fn data(&self) -> &[T; N] {
+        &self.data
+    }
+}
+
+impl<T: Num + Copy, const N: usize> SVector<T, N> {
+    pub fn zeros() -> Self {
+        SVector::from_fn(|_| T::zero())
+    }
+
+    pub fn dot(&self, other: &Self) -> T {
+        (0..N).fold(T::zero(), |sum, i| sum + self.data[i] * other.data[i])
+    }
+}
+
+impl<T: Float, const N: usize> SVector<T, N> {
+    pub fn norm(&self) -> T {
+        self.dot(self).sqrt()
+    }
+
+    /// The unit vector in the same direction. The zero vector has none and gives NaNs.
+    pub fn normalize(&self) -> Self {
+        *self * (T::one() / self.norm())
+    }
+}
+
+impl<T: Num + Copy> SVector<T, 3> {
+    pub fn cross(&self, other: &Self) -> Self {
+        let [a1, a2, a3] = self.data;
+        let [b1, b2, b3] = other.data;
+        SVector::new([a2 * b3 - a3 * b2, a3 * b1 - a1 * b3, a1 * b2 - a2 * b1])
+    }
+}
+
+impl<T, const N: usize> Index<usize> for SVector<T, N> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.data[index]
+    }
+}
+
+impl<T, const N: usize> IndexMut<usize> for SVector<T, N> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.data[index]
+    }
+}
+
+impl<T: Num + Copy, const N: usize> Add for SVector<T, N> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        SVector::from_fn(|i| self.data[i] + other.data[i])
+    }
+}
+
+impl<T: Num + Copy, const N: usize> Sub for SVector<T, N> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        SVector::from_fn(|i| self.data[i] - other.data[i])
+    }
+}
+
+impl<T: Neg<Output = T> + Copy, const N: usize> Neg for SVector<T, N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        SVector::from_fn(|i| -self.data[i])
+    }
+}
+
+impl<T: Num + Copy, const N: usize> Mul<T> for SVector<T, N> {
+    type Output = Self;
+
+    fn mul(self, scalar: T) -> Self {
+        SVector::from_fn(|i| self.data[i] * scalar)
+    }
+}
+
+impl<T: Copy, const R: usize, const C: usize> From<SMatrix<T, R, C>> for Matrix<T> {
+    fn from(matrix: SMatrix<T, R, C>) -> Matrix<T> {
+        Matrix::from_fn(R, C, |i, j| matrix.data[i][j])
+    }
+}
+
+impl<T: Copy + Default, const R: usize, const C: usize> TryFrom<&Matrix<T>> for SMatrix<T, R, C> {
+    type Error = &'static str;
+
+    fn try_from(matrix: &Matrix<T>) -> Result<Self, Self::Error> {
+        if matrix.nrows() != R || matrix.ncols() != C {
+            return Err("Matrix dimensions do not match the fixed-size matrix");
+        }
+        Ok(SMatrix::from_fn(|i, j| matrix[i][j]))
+    }
+}
+
+impl<T: Copy, const N: usize> From<SVector<T, N>> for Vector<T> {
+    fn from(vector: SVector<T, N>) -> Vector<T> {
+        Vector::from_slice(&vector.data)
+    }
+}
+
+impl<T: Copy, const N: usize> TryFrom<&Vector<T>> for SVector<T, N> {
+    type Error = &'static str;
+
+    fn try_from(vector: &Vector<T>) -> Result<Self, Self::Error> {
+        if vector.size() != N {
+            return Err("Vector length does not match the fixed-size vector");
+        }
+        Ok(SVector::from_fn(|i| vector[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity<const N: usize>(m: SMatrix<f64, N, N>) {
+        let error = (m - SMatrix::identity()).frobenius_norm();
+        assert!(error < 1e-12, "{:?} is not the identity", m);
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        let m1 = SMatrix::from_rows([[4.0]]);
+        let m2 = Matrix2d::from_rows([[4.0, 7.0], [2.0, 6.0]]);
+        let m3 = Matrix3d::from_rows([[2.0, 1.0, 1.0], [4.0, -6.0, 0.0], [-2.0, 7.0, 2.0]]);
+        let m4 = Matrix4d::from_rows([
+            [4.0, 3.0, 2.0, 1.0],
+            [1.0, 5.0, 2.0, 3.0],
+            [2.0, 1.0, 6.0, 1.0],
+            [1.0, 2.0, 3.0, 7.0],
+        ]);
+        assert_identity(m1 * m1.inverse().unwrap());
+        assert_identity(m2 * m2.inverse().unwrap());
+        assert_identity(m2.inverse().unwrap() * m2);
+        assert_identity(m3 * m3.inverse().unwrap());
+        assert_identity(m3.inverse().unwrap() * m3);
+        assert_identity(m4 * m4.inverse().unwrap());
+        assert_identity(m4.inverse().unwrap() * m4);
+        assert_eq!(m3.det(), -16.0);
+        assert!((m4.det() - m4.transpose().det()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert_eq!(SMatrix::from_rows([[0.0]]).inverse(), None);
+        assert_eq!(
+            Matrix2d::from_rows([[1.0, 2.0], [2.0, 4.0]]).inverse(),
+            None
+        );
+        let m3 = Matrix3d::from_rows([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]);
+        assert_eq!(m3.inverse(), None);
+        let mut m4 = Matrix4d::identity();
+        m4[3] = m4[1];
+        assert_eq!(m4.inverse(), None);
+        // Not exactly singular, but the determinant is only rounding error.
+        let nearly = Matrix2d::from_rows([[1.0, 2.0], [2.0, 4.0 + 1e-15]]);
+        assert!(nearly.det() != 0.0);
+        assert_eq!(nearly.inverse(), None);
+        assert_eq!(
+            Matrix2d::from_rows([[1.0, 2.0], [2.0, 4.0 + 1e-16]]).inverse(),
+            None
+        );
+        assert_eq!(Matrix2d::from_diag([1e10, 1e-10]).inverse(), None);
+        // Small but well conditioned is fine.
+        assert!((Matrix3d::identity() * 1e-50).inverse().is_some());
+    }
+
+    #[test]
+    fn underflowing_determinant_gives_none() {
+        let tiny = Matrix2d::from_diag([1e-200, 1e-200]);
+        assert_eq!(tiny.inverse(), None);
+        let inverse = (tiny * 1e200).inverse().unwrap() * 1e200;
+        assert_identity(tiny * inverse);
+    }
+
+    #[test]
+    fn conversions_check_dimensions() {
+        let m = Matrix::from_slice(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let fixed = SMatrix::<f64, 2, 3>::try_from(&m).unwrap();
+        assert_eq!(fixed[1][2], 6.0);
+        assert_eq!(Matrix::from(fixed), m);
+        assert!(SMatrix::<f64, 3, 2>::try_from(&m).is_err());
+
+        let v = Vector::from_slice(&[1.0, 2.0, 3.0]);
+        let fixed = Vector3d::try_from(&v).unwrap();
+        assert_eq!(fixed, Vector3d::new([1.0, 2.0, 3.0]));
+        assert_eq!(Vector::from(fixed), v);
+        assert!(Vector2d::try_from(&v).is_err());
+    }
+}
@@ -0,0 +1,522 @@
+use crate::math_utilities::MatrixDouble;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// Fixed point with 4 decimals.
+    #[default]
+    Short,
+    /// Fixed point with 15 decimals.
+    Long,
+    /// Scientific notation.
+    Sci,
+    /// Engineering notation: the exponent is a multiple of 3.
+    Eng,
+    /// Rational approximation such as `1/3`.
+    Rat,
+    /// IEEE 754 bit pattern in hexadecimal.
+    Hex,
+}
+
+impl NumberFormat {
+    pub const ALL: [NumberFormat; 6] = [
+        NumberFormat::Short,
+        NumberFormat::Long,
+        NumberFormat::Sci,
+        NumberFormat::Eng,
+        NumberFormat::Rat,
+        NumberFormat::Hex,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumberFormat::Short => "short",
+            NumberFormat::Long => "long",
+            NumberFormat::Sci => "sci",
+            NumberFormat::Eng => "eng",
+            NumberFormat::Rat => "rat",
+            NumberFormat::Hex => "hex",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<NumberFormat> {
+        NumberFormat::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+    }
+
+    fn decimals(self) -> usize {
+        match self {
+            NumberFormat::Long => 15,
+            _ => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisplayOptions {
+    pub format: NumberFormat,
+    /// Larger matrices show their first and last rows around a `...` row.
+    pub max_rows: usize,
+    /// Larger matrices show their first and last columns around a `...` column.
+    pub max_cols: usize,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> DisplayOptions {
+        DisplayOptions {
+            format: NumberFormat::Short,
+            max_rows: 20,
+            max_cols: 10,
+        }
+    }
+}
+
+// Integers up to this size print without decimals or a scale factor.
+const MAX_PLAIN_INTEGER: f64 = 1e9;
+
+fn non_finite(x: f64) -> Option<String> {
+    if x.is_nan() {
+        Some("NaN".to_string())
+    } else if x.is_infinite() {
+        Some(if x > 0.0 { "Inf" } else { "-Inf" }.to_string())
+    } else {
+        None
+    }
+}
+
+fn is_plain_integer(x: f64) -> bool {
+    x.fract() == 0.0 && x.abs() < MAX_PLAIN_INTEGER
+}
+
+// Formats with a signed two-digit exponent, e.g. `1.2346e+03`.
+fn with_exponent(mantissa: f64, exponent: i32, decimals: usize) -> String {
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!(
+        "{:.*}e{}{:02}",
+        decimals,
+        mantissa,
+        sign,
+        exponent.unsigned_abs()
+    )
+}
+
+fn scientific(x: f64, decimals: usize) -> String {
+    if x == 0.0 {
+        return with_exponent(0.0, 0, decimals);
+    }
+    let mut exponent = x.abs().log10().floor() as i32;
+    let mut mantissa = x / 10f64.powi(exponent);
+    // Rounding can carry the mantissa up to 10.
+    if format!("{:.*}", decimals, mantissa.abs()).starts_with("10") {
+        exponent += 1;
+        mantissa /= 10.0;
+    }
+    with_exponent(mantissa, exponent, decimals)
+}
+
+fn engineering(x: f64, decimals: usize) -> String {
+    if x == 0.0 {
+        return with_exponent(0.0, 0, decimals);
+    }
+    let mut exponent = (x.abs().log10().floor() as i32).div_euclid(3) * 3;
+    let mut mantissa = x / 10f64.powi(exponent);
+    if format!("{:.*}", decimals, mantissa.abs()).starts_with("1000") {
+        exponent += 3;
+        mantissa /= 1000.0;
+    }
+    with_exponent(mantissa, exponent, decimals)
+}
+
+/// Best rational approximation of `x` within `tolerance`, by continued fractions.
+pub fn rational(x: f64, tolerance: f64) -> (i64, i64) {
+    let (mut h0, mut h1) = (0i64, 1i64);
+    let (mut k0, mut k1) = (1i64, 0i64);
+    let mut remainder = x;
+    for _ in 0..20 {
+        let a = remainder.floor();
+        if a.abs() > i64::MAX as f64 / 2.0 {
+            break;
+        }
+        let a = a as i64;
+        let (Some(h), Some(k)) = (
+            a.checked_mul(h1).and_then(|v| v.checked_add(h0)),
+            a.checked_mul(k1).and_then(|v| v.checked_add(k0)),
+        ) else {
+            break;
+        };
+        (h0, h1, k0, k1) = (h1, h, k1, k);
+        let fraction = remainder - a as f64;
+        if (x - h1 as f64 / k1 as f64).abs() <= tolerance || fraction == 0.0 {
+            break;
+        }
+        remainder = 1.0 / fraction;
+    }
+    (h1, k1)
+}
+
+fn format_rational(x: f64) -> String {
+    if x.abs() >= MAX_PLAIN_INTEGER {
+        return scientific(x, 4);
+    }
+    let (numerator, denominator) = rational(x, 1e-6 * x.abs().max(1.0));
+    if denominator == 1 {
+        numerator.to_string()
+    } else {
+        format!("{}/{}", numerator, denominator)
+    }
+}
+
+/// Formats a single number on its own, as for a scalar result.
+pub fn format_number(x: f64, format: NumberFormat) -> String {
+    if let Some(text) = non_finite(x) {
+        return text;
+    }
+    match format {
+        NumberFormat::Short | NumberFormat::Long => {
+            let decimals = format.decimals();
+            // Rounding can carry 99999.99999 up to 1e5, so the rounded value picks the form.
+            let fixed = format!("{:.*}", decimals, x);
+            if is_plain_integer(x) {
+                format!("{}", x)
+            } else if x.abs() < 1e-4 || fixed.parse::<f64>().is_ok_and(|y| y.abs() >= 1e5) {
+                scientific(x, decimals)
+            } else {
+                fixed
+            }
+        }
+        NumberFormat::Sci => scientific(x, 4),
+        NumberFormat::Eng => engineering(x, 4),
+        NumberFormat::Rat => format_rational(x),
+        NumberFormat::Hex => format!("{:016x}", x.to_bits()),
+    }
+}
+
+// Indices to show along one dimension: all of them, or the first and last few with `None`
+// standing for the elided middle.
+fn visible(len: usize, max: usize) -> Vec<Option<usize>> {
+    if len <= max.max(2) {
+        return (0..len).map(Some).collect();
+    }
+    let head = max.max(2) / 2;
+    let tail = max.max(2) - head;
+    (0..head)
+        .map(Some)
+        .chain(std::iter::once(None))
+        .chain((len - tail..len).map(Some))
+        .collect()
+}
+
+/// Exponent of the common scale factor, if short or long output needs one: MATLAB prints
+/// `1.0e+03 *` above a matrix whose largest element is at least 1000 or below 0.001.
+pub fn scale_exponent(matrix: &MatrixDouble, format: NumberFormat) -> Option<i32> {
+    if !matches!(format, NumberFormat::Short | NumberFormat::Long) {
+        return None;
+    }
+    let finite: Vec<f64> = matrix
+        .data()
+        .iter()
+        .copied()
+        .filter(|x| x.is_finite())
+        .collect();
+    if finite.iter().all(|&x| is_plain_integer(x)) {
+        return None;
+    }
+    let max = finite.iter().fold(0.0f64, |max, x| max.max(x.abs()));
+    if max >= 1e3 || (max > 0.0 && max < 1e-3) {
+        Some(max.log10().floor() as i32)
+    } else {
+        None
+    }
+}
+
+/// Formats one element of a matrix, after dividing by the matrix's scale factor.
+pub fn format_element(x: f64, format: NumberFormat, scale: Option<i32>, integers: bool) -> String {
+    if let Some(text) = non_finite(x) {
+        return text;
+    }
+    match format {
+        NumberFormat::Short | NumberFormat::Long if integers => format!("{}", x),
+        NumberFormat::Short | NumberFormat::Long => {
+            let x = match scale {
+                Some(exponent) => x / 10f64.powi(exponent),
+                None => x,
+            };
+            format!("{:.*}", format.decimals(), x)
+        }
+        _ => format_number(x, format),
+    }
+}
+
+/// Formats a matrix as right-aligned columns under an optional scale factor line, eliding
+/// the middle rows and columns of matrices larger than the display limits.
+pub fn format_matrix(matrix: &MatrixDouble, options: &DisplayOptions) -> String {
+    if matrix.nrows() == 0 || matrix.ncols() == 0 {
+        return format!("   [] ({}x{})\n", matrix.nrows(), matrix.ncols());
+    }
+
+    let format = options.format;
+    let scale = scale_exponent(matrix, format);
+    let integers = matrix
+        .data()
+        .iter()
+        .all(|&x| !x.is_finite() || is_plain_integer(x));
+
+    let rows = visible(matrix.nrows(), options.max_rows);
+    let cols = visible(matrix.ncols(), options.max_cols);
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            cols.iter()
+                .map(|col| match (row, col) {
+                    (Some(i), Some(j)) => format_element(matrix[*i][*j], format, scale, integers),
+                    _ => "...".to_string(),
+                })
+                .collect()
+        })
+        .collect();
+
+    let width = cells
+        .iter()
+        .flatten()
+        .map(|cell| cell.len())
+        .max()
+        .unwrap_or(0);
+    let mut text = String::new();
+    if let Some(exponent) = scale {
+        text.push_str(&format!("   {} *\n\n", with_exponent(1.0, exponent, 1)));
+    }
+    for row in &cells {
+        for cell in row {
+            text.push_str(&format!("   {:>width$}", cell, width = width));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Bracket style of a LaTeX matrix.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LatexBrackets {
+    /// `bmatrix`, with square brackets.
+    #[default]
+    Square,
+    /// `pmatrix`, with parentheses.
+    Round,
+}
+
+impl LatexBrackets {
+    fn environment(self) -> &'static str {
+        match self {
+            LatexBrackets::Square => "bmatrix",
+            LatexBrackets::Round => "pmatrix",
+        }
+    }
+}
+
+// Writes `1/3` as `\frac{1}{3}` and `1.5e+03` as `1.5 \times 10^{3}`.
+fn latex_number(x: f64, format: NumberFormat) -> String {
+    if x.is_nan() {
+        return "\\mathrm{NaN}".to_string();
+    } else if x.is_infinite() {
+        return if x > 0.0 { "\\infty" } else { "-\\infty" }.to_string();
+    }
+
+    let text = format_number(x, format);
+    if format == NumberFormat::Hex {
+        return format!("\\texttt{{{}}}", text);
+    }
+    if let Some((numerator, denominator)) = text.split_once('/') {
+        let (sign, numerator) = match numerator.strip_prefix('-') {
+            Some(numerator) => ("-", numerator),
+            None => ("", numerator),
+        };
+        return format!("{}\\frac{{{}}}{{{}}}", sign, numerator, denominator);
+    }
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => format!(
+            "{} \\times 10^{{{}}}",
+            mantissa,
+            exponent.parse::<i32>().unwrap_or(0)
+        ),
+        None => text,
+    }
+}
+
+/// Renders a matrix as a LaTeX `bmatrix` or `pmatrix`, one row per line.
+pub fn matrix_to_latex(
+    matrix: &MatrixDouble,
+    format: NumberFormat,
+    brackets: LatexBrackets,
+) -> String {
+    let rows: Vec<String> = (0..matrix.nrows())
+        .map(|i| {
+            matrix[i]
+                .iter()
+                .map(|&x| latex_number(x, format))
+                .collect::<Vec<String>>()
+                .join(" & ")
+        })
+        .collect();
+    format!(
+        "\\begin{{{env}}}\n{}\n\\end{{{env}}}",
+        rows.join(" \\\\\n"),
+        env = brackets.environment()
+    )
+}
+
+/// Renders a matrix as a Markdown table with right-aligned columns headed by their numbers.
+pub fn matrix_to_markdown(matrix: &MatrixDouble, format: NumberFormat) -> String {
+    let header: Vec<String> = (1..=matrix.ncols()).map(|j| j.to_string()).collect();
+    let mut text = format!("| {} |\n", header.join(" | "));
+    text.push_str(&format!("|{}\n", "---:|".repeat(matrix.ncols())));
+    for i in 0..matrix.nrows() {
+        let cells: Vec<String> = matrix[i]
+            .iter()
+            .map(|&x| format_number(x, format))
+            .collect();
+        text.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    text
+}
+
+/// Renders a matrix as an HTML table with right-aligned cells.
+pub fn matrix_to_html(matrix: &MatrixDouble, format: NumberFormat) -> String {
+    let mut html = String::from("<table>\n");
+    for i in 0..matrix.nrows() {
+        html.push_str("<tr>");
+        for &x in matrix[i].iter() {
+            html.push_str(&format!(
+                "<td style=\"text-align: right\">{}</td>",
+                format_number(x, format)
+            ));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn display(format: NumberFormat) -> DisplayOptions {
+        DisplayOptions {
+            format,
+            ..DisplayOptions::default()
+        }
+    }
+
+    #[test]
+    fn rounding_carries_into_the_exponent() {
+        assert_eq!(format_number(9.99995, NumberFormat::Sci), "1.0000e+01");
+        assert_eq!(format_number(-9.99995, NumberFormat::Sci), "-1.0000e+01");
+        assert_eq!(format_number(999.99999, NumberFormat::Eng), "1.0000e+03");
+        assert_eq!(format_number(9.99995, NumberFormat::Short), "10.0000");
+        assert_eq!(
+            format_number(99999.99999, NumberFormat::Short),
+            "1.0000e+05"
+        );
+        assert_eq!(format_number(99999.5, NumberFormat::Short), "99999.5000");
+        assert_eq!(
+            format_number(0.000099999999, NumberFormat::Short),
+            "1.0000e-04"
+        );
+    }
+
+    #[test]
+    fn scale_factor_line() {
+        let large = MatrixDouble::from_slice(2, 2, &[1500.5, 2.0, 3.0, 4.0]);
+        assert_eq!(
+            format_matrix(&large, &DisplayOptions::default()),
+            "   1.0e+03 *\n\n   1.5005   0.0020\n   0.0030   0.0040\n"
+        );
+        let small = MatrixDouble::from_slice(1, 2, &[0.0001, 0.0002]);
+        assert_eq!(
+            format_matrix(&small, &DisplayOptions::default()),
+            "   1.0e-04 *\n\n   1.0000   2.0000\n"
+        );
+        // Integers and the other formats never get a scale factor.
+        let integers = MatrixDouble::from_slice(1, 2, &[1500.0, 2.0]);
+        assert_eq!(scale_exponent(&integers, NumberFormat::Short), None);
+        assert_eq!(scale_exponent(&large, NumberFormat::Sci), None);
+    }
+
+    #[test]
+    fn rat_finds_small_fractions() {
+        assert_eq!(format_number(1.0 / 3.0, NumberFormat::Rat), "1/3");
+        assert_eq!(format_number(PI, NumberFormat::Rat), "355/113");
+        assert_eq!(format_number(-0.75, NumberFormat::Rat), "-3/4");
+        assert_eq!(format_number(0.1, NumberFormat::Rat), "1/10");
+        let row = MatrixDouble::from_slice(1, 3, &[1.0 / 3.0, -0.5, PI]);
+        assert_eq!(
+            format_matrix(&row, &display(NumberFormat::Rat)),
+            "       1/3      -1/2   355/113\n"
+        );
+    }
+
+    #[test]
+    fn large_matrices_are_elided() {
+        let matrix = MatrixDouble::from_fn(5, 6, |i, j| (10 * i + j) as f64);
+        let options = DisplayOptions {
+            max_rows: 4,
+            max_cols: 3,
+            ..DisplayOptions::default()
+        };
+        assert_eq!(
+            format_matrix(&matrix, &options),
+            concat!(
+                "     0   ...     4     5\n",
+                "    10   ...    14    15\n",
+                "   ...   ...   ...   ...\n",
+                "    30   ...    34    35\n",
+                "    40   ...    44    45\n",
+            )
+        );
+        // At the limits nothing is elided.
+        let options = DisplayOptions {
+            max_rows: 5,
+            max_cols: 6,
+            ..DisplayOptions::default()
+        };
+        assert!(!format_matrix(&matrix, &options).contains("..."));
+    }
+
+    #[test]
+    fn markup_output() {
+        let matrix = MatrixDouble::from_slice(2, 2, &[0.5, -2.0, f64::INFINITY, 1500.0]);
+        assert_eq!(
+            matrix_to_latex(&matrix, NumberFormat::Rat, LatexBrackets::Round),
+            "\\begin{pmatrix}\n\\frac{1}{2} & -2 \\\\\n\\infty & 1500\n\\end{pmatrix}"
+        );
+        assert_eq!(
+            matrix_to_latex(&matrix, NumberFormat::Short, LatexBrackets::Square),
+            "\\begin{bmatrix}\n0.5000 & -2 \\\\\n\\infty & 1500\n\\end{bmatrix}"
+        );
+        let row = MatrixDouble::from_slice(1, 2, &[-0.75, 1.5e-7]);
+        assert_eq!(
+            matrix_to_latex(&row, NumberFormat::Rat, LatexBrackets::Square),
+            "\\begin{bmatrix}\n-\\frac{3}{4} & 0\n\\end{bmatrix}"
+        );
+        assert_eq!(
+            matrix_to_latex(&row, NumberFormat::Short, LatexBrackets::Square),
+            "\\begin{bmatrix}\n-0.7500 & 1.5000 \\times 10^{-7}\n\\end{bmatrix}"
+        );
+        assert_eq!(
+            matrix_to_markdown(&matrix, NumberFormat::Rat),
+            "| 1 | 2 |\n|---:|---:|\n| 1/2 | -2 |\n| Inf | 1500 |\n"
+        );
+        assert_eq!(
+            matrix_to_html(&matrix, NumberFormat::Short),
+            concat!(
+                "<table>\n",
+                "<tr><td style=\"text-align: right\">0.5000</td>",
+                "<td style=\"text-align: right\">-2</td></tr>\n",
+                "<tr><td style=\"text-align: right\">Inf</td>",
+                "<td style=\"text-align: right\">1500</td></tr>\n",
+                "</table>",
+            )
+        );
+    }
+}
@@ -0,0 +1,494 @@
+use crate::fixed_matrix::{Matrix3d, Matrix4d, Vector3d};
+use std::ops::Mul;
+
+/// Rotation by `angle` radians about `axis` (right-handed), by Rodrigues' formula.
+pub fn rotation_from_axis_angle(axis: &Vector3d, angle: f64) -> Result<Matrix3d, &'static str> {
+    let norm = axis.norm();
+    if norm == 0.0 {
+        return Err("Rotation axis must be nonzero");
+    }
+    let k = *axis * (1.0 / norm);
+    let cross = Matrix3d::from_rows([[0.0, -k[2], k[1]], [k[2], 0.0, -k[0]], [-k[1], k[0], 0.0]]);
+    Ok(Matrix3d::identity() + cross * angle.sin() + cross * cross * (1.0 - angle.cos()))
+}
+
+pub fn rotation_x(angle: f64) -> Matrix3d {
+    let (s, c) = angle.sin_cos();
+    Matrix3d::from_rows([[1.0, 0.0, 0.0], [0.0, c, -s], [0.0, s, c]])
+}
+
+pub fn rotation_y(angle: f64) -> Matrix3d {
+    let (s, c) = angle.sin_cos();
+    Matrix3d::from_rows([[c, 0.0, s], [0.0, 1.0, 0.0], [-s, 0.0, c]])
+}
+
+pub fn rotation_z(angle: f64) -> Matrix3d {
+    let (s, c) = angle.sin_cos();
+    Matrix3d::from_rows([[c, -s, 0.0], [s, c, 0.0], [0.0, 0.0, 1.0]])
+}
+
+/// Rotation from roll, pitch and yaw in the Z-Y-X convention: `Rz(yaw) * Ry(pitch) * Rx(roll)`.
+pub fn rotation_from_euler(roll: f64, pitch: f64, yaw: f64) -> Matrix3d {
+    rotation_z(yaw) * rotation_y(pitch) * rotation_x(roll)
+}
+
+/// The (roll, pitch, yaw) that `rotation_from_euler` turns into `r`. At pitch ±π/2 only
+/// roll - yaw (or roll + yaw) is determined, and yaw is reported as 0.
+pub fn euler_from_rotation(r: &Matrix3d) -> (f64, f64, f64) {
+    let pitch = (-r[2][0]).clamp(-1.0, 1.0).asin();
+    if r[2][0].abs() < 1.0 - 1e-12 {
+        (r[2][1].atan2(r[2][2]), pitch, r[1][0].atan2(r[0][0]))
+    } else {
+        ((-r[2][0] * r[0][1]).atan2(r[1][1]), pitch, 0.0)
+    }
+}
+
+/// The orthogonal factor Q of the polar decomposition M = Q H, i.e. the orthogonal matrix
+/// nearest to `m`. Use it to remove the drift from a rotation that has been updated many
+/// times. A matrix with negative determinant gives a reflection, not a rotation.
+pub fn orthonormalize(m: &Matrix3d) -> Result<Matrix3d, &'static str> {
+    let mut q = *m;
+    // Scaled Newton iteration Q <- (g Q + Q^-T / g) / 2, which converges quadratically.
+    for _ in 0..100 {
+        let inverse = q.inverse().ok_or("Singular matrix in orthonormalization")?;
+        let gamma = (inverse.frobenius_norm() / q.frobenius_norm()).sqrt();
+        let next = (q * gamma + inverse.transpose() * (1.0 / gamma)) * 0.5;
+        let change = (next - q).frobenius_norm();
+        q = next;
+        if change <= 4.0 * f64::EPSILON * q.frobenius_norm() {
+            return Ok(q);
+        }
+    }
+    Err("Orthonormalization did not converge")
+}
+
+/// A quaternion w + xi + yj + zk. Unit quaternions represent rotations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub const fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub const fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn from_axis_angle(axis: &Vector3d, angle: f64) -> Result<Quaternion, &'static str> {
+        let norm = axis.norm();
+        if norm == 0.0 {
+            return Err("Rotation axis must be nonzero");
+        }
+        let (s, c) = (angle / 2.0).sin_cos();
+        let k = *axis * (s / norm);
+        Ok(Quaternion::new(c, k[0], k[1], k[2]))
+    }
+
+    /// The unit quaternion of a rotation matrix, with w >= 0 (Shepperd's method).
+    pub fn from_rotation_matrix(r: &Matrix3d) -> Quaternion {
+        let trace = r.trace();
+        // Divide by the largest of 4w², 4x², 4y², 4z², whichever keeps the square root far from 0.
+        let q = if trace > r[0][0].max(r[1][1]).max(r[2][2]) {
+            let s = 2.0 * (1.0 + trace).sqrt();
+            Quaternion::new(
+                s / 4.0,
+                (r[2][1] - r[1][2]) / s,
+                (r[0][2] - r[2][0]) / s,
+                (r[1][0] - r[0][1]) / s,
+            )
+        } else if r[0][0] >= r[1][1] && r[0][0] >= r[2][2] {
+            let s = 2.0 * (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt();
+            Quaternion::new(
+                (r[2][1] - r[1][2]) / s,
+                s / 4.0,
+                (r[0][1] + r[1][0]) / s,
+                (r[0][2] + r[2][0]) / s,
+            )
+        } else if r[1][1] >= r[2][2] {
+            let s = 2.0 * (1.0 - r[0][0] + r[1][1] - r[2][2]).sqrt();
+            Quaternion::new(
+                (r[0][2] - r[2][0]) / s,
+                (r[0][1] + r[1][0]) / s,
+                s / 4.0,
+                (r[1][2] + r[2][1]) / s,
+            )
+        } else {
+            let s = 2.0 * (1.0 - r[0][0] - r[1][1] + r[2][2]).sqrt();
+            Quaternion::new(
+                (r[1][0] - r[0][1]) / s,
+                (r[0][2] + r[2][0]) / s,
+                (r[1][2] + r[2][1]) / s,
+                s / 4.0,
+            )
+        };
+        let q = q.normalize();
+        if q.w < 0.0 {
+            q.scale(-1.0)
+        } else {
+            q
+        }
+    }
+
+    /// The rotation matrix of a unit quaternion.
+    pub fn to_rotation_matrix(&self) -> Matrix3d {
+        let Quaternion { w, x, y, z } = *self;
+        Matrix3d::from_rows([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn norm(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        self.scale(1.0 / self.norm())
+    }
+
+    fn scale(&self, s: f64) -> Quaternion {
+        Quaternion::new(self.w * s, self.x * s, self.y * s, self.z * s)
+    }
+
+    /// Rotates `v` by this unit quaternion.
+    pub fn rotate(&self, v: &Vector3d) -> Vector3d {
+        let u = Vector3d::new([self.x, self.y, self.z]);
+        let t = u.cross(v) * 2.0;
+        *v + t * self.w + u.cross(&t)
+    }
+
+    /// Spherical linear interpolation between unit quaternions, from `self` at t = 0 to
+    /// `other` at t = 1, at constant angular speed along the shorter arc.
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        let mut cos_theta = self.dot(other);
+        let mut other = *other;
+        // q and -q are the same rotation; flipping one takes the shorter way round.
+        if cos_theta < 0.0 {
+            other = other.scale(-1.0);
+            cos_theta = -cos_theta;
+        }
+        let (a, b) = if cos_theta > 1.0 - 1e-10 {
+            // Nearly parallel: sin(theta) vanishes, and linear interpolation is as accurate.
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        Quaternion::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalize()
+    }
+}
+
+/// The Hamilton product: `p * q` rotates by q first, then by p.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, q: Quaternion) -> Quaternion {
+        let p = self;
+        Quaternion::new(
+            p.w * q.w - p.x * q.x - p.y * q.y - p.z * q.z,
+            p.w * q.x + p.x * q.w + p.y * q.z - p.z * q.y,
+            p.w * q.y - p.x * q.z + p.y * q.w + p.z * q.x,
+            p.w * q.z + p.x * q.y - p.y * q.x + p.z * q.w,
+        )
+    }
+}
+
+/// A 4x4 homogeneous transform [A t; 0 1] mapping the point p to A p + t.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4d,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4d::identity(),
+        }
+    }
+
+    /// The transform applying `linear` (usually a rotation) and then translating by `translation`.
+    pub fn from_parts(linear: &Matrix3d, translation: &Vector3d) -> Transform {
+        Transform {
+            matrix: Matrix4d::from_fn(|i, j| match (i, j) {
+                (3, 3) => 1.0,
+                (3, _) => 0.0,
+                (_, 3) => translation[i],
+                _ => linear[i][j],
+            }),
+        }
+    }
+
+    pub fn from_rotation(rotation: &Matrix3d) -> Transform {
+        Transform::from_parts(rotation, &Vector3d::zeros())
+    }
+
+    pub fn from_translation(translation: &Vector3d) -> Transform {
+        Transform::from_parts(&Matrix3d::identity(), translation)
+    }
+
+    pub fn from_matrix(matrix: &Matrix4d) -> Result<Transform, &'static str> {
+        if matrix[3] != [0.0, 0.0, 0.0, 1.0] {
+            return Err("The last row of a homogeneous transform must be [0 0 0 1]");
+        }
+        Ok(Transform { matrix: *matrix })
+    }
+
+    pub fn matrix(&self) -> &Matrix4d {
+        &self.matrix
+    }
+
+    pub fn linear(&self) -> Matrix3d {
+        Matrix3d::from_fn(|i, j| self.matrix[i][j])
+    }
+
+    pub fn translation(&self) -> Vector3d {
+        Vector3d::from_fn(|i| self.matrix[i][3])
+    }
+
+    /// The transform applying `other` first and then `self`.
+    pub fn compose(&self, other: &Transform) -> Transform {
+        Transform {
+            matrix: self.matrix * other.matrix,
+        }
+    }
+
+    /// [A⁻¹ -A⁻¹t; 0 1], or None if A is singular. A rotation is inverted by its transpose,
+    /// with no rounding beyond the products.
+    pub fn inverse(&self) -> Option<Transform> {
+        let linear = self.linear();
+        let inverse = if is_rotation(&linear) {
+            linear.transpose()
+        } else {
+            linear.inverse()?
+        };
+        let translation = -(inverse * self.translation());
+        Some(Transform::from_parts(&inverse, &translation))
+    }
+
+    pub fn apply_to_point(&self, point: &Vector3d) -> Vector3d {
+        self.linear() * *point + self.translation()
+    }
+
+    /// Applies the linear part only, as for directions and displacements.
+    pub fn apply_to_vector(&self, vector: &Vector3d) -> Vector3d {
+        self.linear() * *vector
+    }
+
+    pub fn apply_to_points(&self, points: &[Vector3d]) -> Vec<Vector3d> {
+        let (linear, translation) = (self.linear(), self.translation());
+        points.iter().map(|&p| linear * p + translation).collect()
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Transform {
+        self.compose(&other)
+    }
+}
+
+fn is_rotation(m: &Matrix3d) -> bool {
+    (m.transpose() * *m - Matrix3d::identity()).frobenius_norm() < 1e-12 && m.det() > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    fn assert_close(a: Matrix3d, b: Matrix3d) {
+        assert!((a - b).frobenius_norm() < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_close_vectors(a: Vector3d, b: Vector3d) {
+        assert!((a - b).norm() < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    fn axes() -> Vec<Vector3d> {
+        vec![
+            Vector3d::new([1.0, 0.0, 0.0]),
+            Vector3d::new([0.0, 1.0, 0.0]),
+            Vector3d::new([0.0, 0.0, 1.0]),
+            Vector3d::new([1.0, 1.0, 0.0]),
+            Vector3d::new([1.0, -2.0, 3.0]),
+        ]
+    }
+
+    #[test]
+    fn axis_angle_matrix_matches_quaternion() {
+        let v = Vector3d::new([0.5, -1.0, 2.0]);
+        for axis in axes() {
+            for angle in [0.0, 0.3, -1.7, PI, 2.5] {
+                let r = rotation_from_axis_angle(&axis, angle).unwrap();
+                let q = Quaternion::from_axis_angle(&axis, angle).unwrap();
+                assert_close(r, q.to_rotation_matrix());
+                assert_close_vectors(r * v, q.rotate(&v));
+            }
+        }
+        assert_close(
+            rotation_from_axis_angle(&Vector3d::new([0.0, 0.0, 2.0]), 0.4).unwrap(),
+            rotation_z(0.4),
+        );
+        let p = Quaternion::from_axis_angle(&axes()[3], 0.8).unwrap();
+        let q = Quaternion::from_axis_angle(&axes()[4], -1.1).unwrap();
+        assert_close(
+            (p * q).to_rotation_matrix(),
+            p.to_rotation_matrix() * q.to_rotation_matrix(),
+        );
+        assert!(rotation_from_axis_angle(&Vector3d::zeros(), 1.0).is_err());
+        assert!(Quaternion::from_axis_angle(&Vector3d::zeros(), 1.0).is_err());
+    }
+
+    #[test]
+    fn euler_angles_round_trip() {
+        for (roll, pitch, yaw) in [(0.1, 0.2, 0.3), (-2.0, 1.2, 3.0), (3.0, -1.5, -0.5)] {
+            let (r, p, y) = euler_from_rotation(&rotation_from_euler(roll, pitch, yaw));
+            assert!(
+                (r - roll).abs() < 1e-12 && (p - pitch).abs() < 1e-12 && (y - yaw).abs() < 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn euler_angles_in_gimbal_lock_give_the_same_rotation() {
+        for pitch in [FRAC_PI_2, -FRAC_PI_2] {
+            let rotation = rotation_from_euler(0.4, pitch, 0.9);
+            let (r, p, y) = euler_from_rotation(&rotation);
+            assert_eq!((p, y), (pitch, 0.0));
+            assert_close(rotation_from_euler(r, p, y), rotation);
+        }
+    }
+
+    #[test]
+    fn quaternion_from_half_turns() {
+        for axis in axes() {
+            let r = rotation_from_axis_angle(&axis, PI).unwrap();
+            let q = Quaternion::from_rotation_matrix(&r);
+            assert!(q.w.abs() < 1e-12);
+            assert!((q.norm() - 1.0).abs() < 1e-12);
+            // The vector part is ±axis; both signs describe the same half turn.
+            let u = axis.normalize();
+            assert!((q.x * u[0] + q.y * u[1] + q.z * u[2]).abs() > 1.0 - 1e-12);
+            assert_close(q.to_rotation_matrix(), r);
+        }
+    }
+
+    #[test]
+    fn quaternion_from_rotation_matrix_round_trips() {
+        for axis in axes() {
+            for angle in [0.0, 0.3, -1.7, 2.5] {
+                let q = Quaternion::from_axis_angle(&axis, angle).unwrap();
+                let p = Quaternion::from_rotation_matrix(&q.to_rotation_matrix());
+                assert!(p.w >= 0.0);
+                assert!((p.dot(&q).abs() - 1.0).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn slerp_midpoint_is_half_the_rotation() {
+        let axis = axes()[4];
+        let start = Quaternion::from_axis_angle(&axis, 0.2).unwrap();
+        let end = Quaternion::from_axis_angle(&axis, 1.4).unwrap();
+        let middle = Quaternion::from_axis_angle(&axis, 0.8).unwrap();
+        assert!((start.slerp(&end, 0.5).dot(&middle) - 1.0).abs() < 1e-12);
+        assert!((start.slerp(&end, 0.0).dot(&start) - 1.0).abs() < 1e-12);
+        assert!((start.slerp(&end, 1.0).dot(&end) - 1.0).abs() < 1e-12);
+        // -end is the same rotation, and slerp still takes the short way.
+        let flipped = Quaternion::new(-end.w, -end.x, -end.y, -end.z);
+        assert!((start.slerp(&flipped, 0.5).dot(&middle).abs() - 1.0).abs() < 1e-12);
+        let same = start.slerp(&start, 0.5);
+        assert!((same.dot(&start) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn transforms_compose_and_invert() {
+        let a = Transform::from_parts(
+            &rotation_from_euler(0.1, -0.4, 1.3),
+            &Vector3d::new([1.0, 2.0, 3.0]),
+        );
+        let b = Transform::from_parts(&rotation_x(0.7), &Vector3d::new([-4.0, 0.5, 0.0]));
+        let p = Vector3d::new([0.3, -0.2, 5.0]);
+        assert_close_vectors(
+            (a * b).apply_to_point(&p),
+            a.apply_to_point(&b.apply_to_point(&p)),
+        );
+        assert_close_vectors(
+            a.compose(&b).apply_to_vector(&p),
+            a.linear() * (b.linear() * p),
+        );
+        assert_eq!(a.apply_to_points(&[p])[0], a.apply_to_point(&p));
+
+        let sheared = Transform::from_parts(
+            &Matrix3d::from_rows([[2.0, 1.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 0.5]]),
+            &Vector3d::new([1.0, 0.0, -1.0]),
+        );
+        for t in [a, sheared, a * sheared] {
+            let inverse = t.inverse().unwrap();
+            assert_close_vectors(inverse.apply_to_point(&t.apply_to_point(&p)), p);
+            let error = ((t * inverse).matrix - Matrix4d::identity()).frobenius_norm();
+            assert!(error < 1e-12);
+        }
+
+        let flat = Transform::from_parts(&Matrix3d::from_diag([1.0, 1.0, 0.0]), &Vector3d::zeros());
+        assert_eq!(flat.inverse(), None);
+        let mut projective = Matrix4d::identity();
+        projective[3][0] = 1.0;
+        assert!(Transform::from_matrix(&projective).is_err());
+    }
+
+    #[test]
+    fn orthonormalize_recovers_the_rotation() {
+        let rotation = rotation_from_euler(0.3, -0.8, 2.1);
+        assert_close(orthonormalize(&(rotation * 3.0)).unwrap(), rotation);
+
+        let mut drifted = rotation;
+        drifted[0][1] += 1e-6;
+        drifted[2][0] -= 2e-6;
+        drifted[1][1] += 3e-6;
+        let q = orthonormalize(&drifted).unwrap();
+        assert_close(q.transpose() * q, Matrix3d::identity());
+        assert!((q.det() - 1.0).abs() < 1e-12);
+        assert!((q - rotation).frobenius_norm() < 1e-5);
+
+        assert!(orthonormalize(&Matrix3d::zeros()).is_err());
+    }
+}
@@ -1,55 +1,213 @@
-use eframe::egui::{self, FontId, ScrollArea, TextEdit, Ui};
+use eframe::egui::plot::{Bar, BarChart, Legend, Line, PlotImage, PlotPoint, PlotPoints, Points};
+use eframe::egui::{
+    self, Color32, ColorImage, FontId, Id, Key, Modifiers, RichText, ScrollArea, TextEdit,
+    TextureHandle, TextureOptions, Ui,
+};
 use eframe::App;
-use meval::eval_str;
 use std::collections::HashMap;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::lu_decomposition::LU;
-use crate::math_utilities::{MatrixDouble, VectorDouble};
-use crate::matrix_operations::{add_matrices, multiply_matrices, subtract_matrices};
-use crate::repl::{handle_matrix_operations, parse_matrix};
+use crate::cancel::{self, CancelToken};
+use crate::format::{
+    format_number, matrix_to_html, matrix_to_latex, matrix_to_markdown, LatexBrackets, NumberFormat,
+};
+use crate::inspector::Inspector;
+use crate::io::session::{load_session, save_session, LAST_SESSION_FILE};
+use crate::math_utilities::MatrixDouble;
+use crate::notebook::{
+    export_html, export_markdown, load_notebook, markdown_blocks, save_notebook, Block, Cell,
+    CellKind, LAST_NOTEBOOK_FILE,
+};
+use crate::output::Value;
+use crate::plot::{axis_label, axis_value, colormap, finite_range, nonzeros, Plot, PlotKind};
+use crate::interpreter::{display_options, evaluate_command};
+use crate::script::split_statements;
 
+const DEFAULT_NOTEBOOK: &str = "notebook.num";
+const MIN_FONT_SIZE: f32 = 8.0;
+const MAX_FONT_SIZE: f32 = 48.0;
+
+// What one statement of a code cell printed or produced.
 #[derive(Clone)]
-struct CommandEntry {
-    input: String,
-    output: String,
+struct CellOutput {
+    text: String,
+    error: bool,
+    // The matrix the statement produced, offered for copying as LaTeX, Markdown or HTML.
+    matrix: Option<MatrixDouble>,
+    plot: Option<Plot>,
+    // Image plots are uploaded to the GPU once, the first time they are shown.
+    texture: Option<TextureHandle>,
+}
+
+impl CellOutput {
+    fn error(text: String) -> CellOutput {
+        CellOutput {
+            text,
+            error: true,
+            matrix: None,
+            plot: None,
+            texture: None,
+        }
+    }
+}
+
+struct NotebookCell {
+    cell: Cell,
+    outputs: Vec<CellOutput>,
+    // Set once the cell has been run, to show its execution number.
+    count: Option<usize>,
+    // Markdown cells show their rendering unless they are being edited.
+    editing: bool,
+}
+
+impl NotebookCell {
+    fn new(cell: Cell) -> NotebookCell {
+        let editing = cell.kind == CellKind::Markdown && cell.source.is_empty();
+        NotebookCell {
+            cell,
+            outputs: Vec::new(),
+            count: None,
+            editing,
+        }
+    }
+}
+
+// Buttons on a cell, applied once the notebook has been drawn.
+enum CellAction {
+    Run(usize),
+    RunFrom(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    Delete(usize),
+}
+
+// Sent by the worker thread that runs cells.
+enum WorkerMessage {
+    // The outputs of a cell, sent as soon as it finishes. `ok` is false if it stopped at an error.
+    Cell {
+        index: usize,
+        outputs: Vec<CellOutput>,
+        ok: bool,
+    },
+    // The workspace, handed back when the run is over.
+    Done(HashMap<String, MatrixDouble>),
+}
+
+// Cells running on the worker thread, which holds the workspace until it sends `Done`.
+struct Run {
+    receiver: Receiver<WorkerMessage>,
+    token: CancelToken,
+    started: Instant,
+    // Cells still to run, in order. The first one is running.
+    pending: Vec<usize>,
 }
 
 pub struct NumerusApp {
     current_input: String,
-    history: Vec<CommandEntry>,
+    cells: Vec<NotebookCell>,
     matrices: HashMap<String, MatrixDouble>,
     font_size: f32,
+    restore: bool,
+    inspector: Inspector,
+    notebook_path: String,
+    execution_count: usize,
+    status: String,
+    run: Option<Run>,
 }
 
 impl Default for NumerusApp {
     fn default() -> Self {
         Self {
             current_input: String::new(),
-            history: Vec::new(),
+            cells: Vec::new(),
             matrices: HashMap::new(),
             font_size: 16.0,
+            restore: false,
+            inspector: Inspector::new(),
+            notebook_path: DEFAULT_NOTEBOOK.to_string(),
+            execution_count: 0,
+            status: String::new(),
+            run: None,
         }
     }
 }
 
 impl App for NumerusApp {
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
-        let mut changed = false;
+        self.show(ctx);
+    }
 
-        if ctx.input().modifiers.command && ctx.input().key_pressed(egui::Key::PlusEquals) {
-            self.font_size += 1.0;
-            changed = true;
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Stop a running computation and wait for the workspace to come back before saving it.
+        if let Some(run) = &self.run {
+            run.token.cancel();
+        }
+        self.wait_for_run();
+        if self.restore {
+            let _ = save_session(LAST_SESSION_FILE, &self.matrices);
+            let _ = save_notebook(LAST_NOTEBOOK_FILE, &self.notebook());
         }
+    }
+}
 
-        if ctx.input().modifiers.command && ctx.input().key_pressed(egui::Key::Minus) {
-            self.font_size -= 1.0;
-            changed = true;
+impl NumerusApp {
+    pub fn new(restore: bool) -> Self {
+        let mut app = Self {
+            restore,
+            ..Self::default()
+        };
+        if restore && Path::new(LAST_SESSION_FILE).exists() {
+            app.status = match load_session(LAST_SESSION_FILE) {
+                Ok(session) => {
+                    let status = format!(
+                        "Restored {} matrices from the previous session.",
+                        session.len()
+                    );
+                    app.matrices = session;
+                    status
+                }
+                Err(err) => format!("Could not restore the previous session: {}", err),
+            };
+        }
+        if restore && Path::new(LAST_NOTEBOOK_FILE).exists() {
+            if let Ok(cells) = load_notebook(LAST_NOTEBOOK_FILE) {
+                app.cells = cells.into_iter().map(NotebookCell::new).collect();
+            }
         }
+        app
+    }
 
-        if changed {
+    /// Draws a frame and handles its input. Needs no window, so tests can drive the app
+    /// through a bare `egui::Context`.
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if ctx.input().modifiers.command && ctx.input().key_pressed(Key::PlusEquals) {
+            self.zoom(1.0);
+            ctx.request_repaint();
+        }
+        if ctx.input().modifiers.command && ctx.input().key_pressed(Key::Minus) {
+            self.zoom(-1.0);
             ctx.request_repaint();
         }
 
+        self.poll_run();
+        if self.run.is_some() {
+            // Keep polling the worker and counting the elapsed time.
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| self.show_toolbar(ui));
+
+        if self.run.is_some() {
+            self.inspector.show_busy(ctx);
+        } else {
+            self.inspector.show(ctx, &mut self.matrices);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading(
                 egui::RichText::new("Numerus Interactive Notebook")
@@ -57,17 +215,27 @@ impl App for NumerusApp {
             );
 
             ScrollArea::vertical().show(ui, |ui| {
-                for entry in &self.history {
-                    display_entry(ui, entry, self.font_size);
+                let mut action = None;
+                for (index, cell) in self.cells.iter_mut().enumerate() {
+                    if let Some(clicked) = show_cell(ui, cell, index, self.font_size) {
+                        action = Some(clicked);
+                    }
+                }
+                if let Some(action) = action {
+                    self.apply(action);
                 }
 
                 ui.separator();
 
                 ui.horizontal(|ui| {
                     ui.label(egui::RichText::new("In:").font(FontId::proportional(self.font_size)));
-                    let input = ui.add_sized(
+                    let input_id = Id::new("notebook_input");
+                    let submitted = ui.memory().has_focus(input_id)
+                        && ui.input_mut().consume_key(Modifiers::SHIFT, Key::Enter);
+                    ui.add_sized(
                         [ui.available_width() - 50.0, 30.0],
                         TextEdit::multiline(&mut self.current_input)
+                            .id(input_id)
                             .font(FontId::proportional(self.font_size))
                             .desired_width(f32::INFINITY)
                             .desired_rows(1),
@@ -78,176 +246,761 @@ impl App for NumerusApp {
                             egui::RichText::new("Run").font(FontId::proportional(self.font_size)),
                         )
                         .clicked()
+                        || submitted
                     {
-                        self.execute_command();
+                        self.submit_input();
                     }
+                });
+            });
+        });
+    }
+
+    fn zoom(&mut self, step: f32) {
+        self.font_size = (self.font_size + step).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+    }
 
-                    if ui.input().key_pressed(egui::Key::Enter) && ui.input().modifiers.shift {
-                        self.execute_command();
+    fn notebook(&self) -> Vec<Cell> {
+        self.cells.iter().map(|cell| cell.cell.clone()).collect()
+    }
+
+    fn show_toolbar(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Notebook:");
+            ui.add(TextEdit::singleline(&mut self.notebook_path).desired_width(200.0));
+            if ui.button("Open").clicked() {
+                self.open();
+            }
+            if ui.button("Save").clicked() {
+                self.status = match save_notebook(&self.notebook_path, &self.notebook()) {
+                    Ok(()) => format!("Saved {}.", self.notebook_path),
+                    Err(err) => format!("Could not save {}: {}", self.notebook_path, err),
+                };
+            }
+            ui.separator();
+            if ui.button("Run all").clicked() {
+                self.start_run((0..self.cells.len()).collect());
+            }
+            if ui.button("Add Markdown").clicked() {
+                self.cells.push(NotebookCell::new(Cell::markdown("")));
+            }
+            ui.separator();
+            if ui.button("Export HTML").clicked() {
+                self.export("html");
+            }
+            if ui.button("Export Markdown").clicked() {
+                self.export("md");
+            }
+        });
+        if let Some(run) = &self.run {
+            ui.horizontal(|ui| {
+                ui.add(egui::Spinner::new());
+                let elapsed = run.started.elapsed().as_secs_f64();
+                if run.token.is_cancelled() {
+                    ui.label(format!("Cancelling… {:.1} s", elapsed));
+                } else {
+                    let cell = run.pending.first().map_or(0, |index| index + 1);
+                    ui.label(format!("Running cell {}… {:.1} s", cell, elapsed));
+                    if ui.button("Cancel").clicked() {
+                        run.token.cancel();
                     }
-                });
+                }
+            });
+        } else if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+
+    fn open(&mut self) {
+        if self.run.is_some() {
+            return;
+        }
+        self.status = match load_notebook(&self.notebook_path) {
+            Ok(cells) => {
+                self.cells = cells.into_iter().map(NotebookCell::new).collect();
+                format!(
+                    "Opened {} ({} cells). Use Run all to compute the outputs.",
+                    self.notebook_path,
+                    self.cells.len()
+                )
+            }
+            Err(err) => format!("Could not open {}: {}", self.notebook_path, err),
+        };
+    }
+
+    // Writes the notebook and its current outputs next to the notebook file.
+    fn export(&mut self, extension: &str) {
+        let path = Path::new(&self.notebook_path).with_extension(extension);
+        let cells: Vec<(&Cell, Vec<&str>)> = self
+            .cells
+            .iter()
+            .map(|cell| {
+                let outputs = cell.outputs.iter().map(|o| o.text.as_str()).collect();
+                (&cell.cell, outputs)
+            })
+            .collect();
+        let document = if extension == "html" {
+            let title = path
+                .file_stem()
+                .map_or("notebook".into(), |stem| stem.to_string_lossy());
+            export_html(&title, &cells)
+        } else {
+            export_markdown(&cells)
+        };
+        self.status = match fs::write(&path, document) {
+            Ok(()) => format!("Exported {}.", path.display()),
+            Err(err) => format!("Could not export {}: {}", path.display(), err),
+        };
+    }
+
+    fn apply(&mut self, action: CellAction) {
+        // Cells are addressed by index until the worker is done with them.
+        if self.run.is_some() {
+            self.status = "Wait for the running cells to finish, or cancel them.".to_string();
+            return;
+        }
+        match action {
+            CellAction::Run(index) => self.run_cell(index),
+            CellAction::RunFrom(index) => self.start_run((index..self.cells.len()).collect()),
+            CellAction::MoveUp(index) if index > 0 => self.cells.swap(index - 1, index),
+            CellAction::MoveDown(index) if index + 1 < self.cells.len() => {
+                self.cells.swap(index, index + 1)
+            }
+            CellAction::Delete(index) => {
+                self.cells.remove(index);
+            }
+            _ => {}
+        }
+    }
+
+    fn run_cell(&mut self, index: usize) {
+        self.start_run(vec![index]);
+    }
+
+    // Runs cells in order on a worker thread, stopping at the first error. The workspace
+    // moves to the worker for the duration of the run, so the interface stays responsive.
+    fn start_run(&mut self, indices: Vec<usize>) {
+        if self.run.is_some() {
+            return;
+        }
+        let mut jobs = Vec::new();
+        for index in indices {
+            let cell = &mut self.cells[index];
+            match cell.cell.kind {
+                CellKind::Markdown => cell.editing = false,
+                CellKind::Code => jobs.push((index, cell.cell.source.clone())),
+            }
+        }
+        if jobs.is_empty() {
+            return;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let token = CancelToken::new();
+        let pending = jobs.iter().map(|(index, _)| *index).collect();
+        let mut matrices = std::mem::take(&mut self.matrices);
+        let worker_token = token.clone();
+        thread::spawn(move || {
+            cancel::with_token(&worker_token, || {
+                for (index, source) in jobs {
+                    let (outputs, ok) = run_source(&source, &mut matrices);
+                    let _ = sender.send(WorkerMessage::Cell { index, outputs, ok });
+                    if !ok {
+                        break;
+                    }
+                }
             });
+            let _ = sender.send(WorkerMessage::Done(matrices));
+        });
+
+        self.status.clear();
+        self.run = Some(Run {
+            receiver,
+            token,
+            started: Instant::now(),
+            pending,
         });
     }
+
+    // Takes in whatever the worker has finished since the last frame.
+    fn poll_run(&mut self) {
+        let Some(run) = self.run.as_mut() else {
+            return;
+        };
+        loop {
+            match run.receiver.try_recv() {
+                Ok(WorkerMessage::Cell { index, outputs, ok }) => {
+                    run.pending.retain(|&pending| pending != index);
+                    self.execution_count += 1;
+                    let cell = &mut self.cells[index];
+                    cell.count = Some(self.execution_count);
+                    cell.outputs = outputs;
+                    if !ok {
+                        self.status = if run.token.is_cancelled() {
+                            format!("Cancelled cell {}.", index + 1)
+                        } else {
+                            format!("Stopped at an error in cell {}.", index + 1)
+                        };
+                    }
+                }
+                Ok(WorkerMessage::Done(matrices)) => {
+                    self.matrices = matrices;
+                    self.run = None;
+                    return;
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.status =
+                        "The worker thread stopped and the workspace was lost.".to_string();
+                    self.run = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    // Blocks until the cells running in the background are done and the workspace is back.
+    fn wait_for_run(&mut self) {
+        while self.run.is_some() {
+            self.poll_run();
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // Appends the input box as a new code cell and runs it.
+    fn submit_input(&mut self) {
+        let source = self.current_input.trim().to_string();
+        if source.is_empty() || self.run.is_some() {
+            return;
+        }
+        self.cells.push(NotebookCell::new(Cell::code(source)));
+        self.run_cell(self.cells.len() - 1);
+        self.current_input.clear();
+    }
 }
 
-impl NumerusApp {
-    fn execute_command(&mut self) {
-        let command = self.current_input.clone();
-        let response = handle_command(&command, &mut self.matrices);
-        self.history.push(CommandEntry {
-            input: command,
-            output: response,
+// Runs the statements of a cell until one fails. Returns the outputs and false if one did.
+fn run_source(
+    source: &str,
+    matrices: &mut HashMap<String, MatrixDouble>,
+) -> (Vec<CellOutput>, bool) {
+    let mut outputs = Vec::new();
+    for statement in split_statements(source) {
+        // Some kernels panic, e.g. LU of a matrix with a zero row. That must not take the
+        // worker thread and the workspace it holds down with it.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            evaluate_command(&statement.text, matrices)
+        }))
+        .unwrap_or_else(|_| Err("Internal error while evaluating the statement.".to_string()));
+        match result {
+            Ok(_) if statement.silent => {}
+            Ok(output) => {
+                let (matrix, plot) = match output.value {
+                    Value::Matrix(m) | Value::Lu { lu: m, .. } => (Some(m), None),
+                    Value::Vector(v) => {
+                        (Some(MatrixDouble::from_slice(v.size(), 1, v.data())), None)
+                    }
+                    Value::Plot(plot) => (None, Some(plot)),
+                    Value::Scalar(_) | Value::None => (None, None),
+                };
+                outputs.push(CellOutput {
+                    text: output.text,
+                    error: false,
+                    matrix,
+                    plot,
+                    texture: None,
+                });
+            }
+            Err(err) => {
+                outputs.push(CellOutput::error(err));
+                return (outputs, false);
+            }
+        }
+    }
+    (outputs, true)
+}
+
+fn image_texture(ctx: &egui::Context, matrix: &MatrixDouble) -> TextureHandle {
+    let (min, max) = finite_range(matrix).unwrap_or((0.0, 1.0));
+    let pixels: Vec<u8> = matrix
+        .data()
+        .iter()
+        .flat_map(|&x| {
+            if !x.is_finite() {
+                [255, 255, 255]
+            } else if max == min {
+                colormap(0.5)
+            } else {
+                colormap((x - min) / (max - min))
+            }
+        })
+        .collect();
+    let image = ColorImage::from_rgb([matrix.ncols(), matrix.nrows()], &pixels);
+    ctx.load_texture("imagesc", image, TextureOptions::NEAREST)
+}
+
+fn plot_points(plot: &Plot, x: &[f64], y: &[f64]) -> PlotPoints {
+    PlotPoints::new(
+        x.iter()
+            .zip(y)
+            .filter_map(|(&x, &y)| Some([axis_value(x, plot.log_x)?, axis_value(y, plot.log_y)?]))
+            .collect(),
+    )
+}
+
+// Image and sparsity plots put row i at y = -i so that the first row is at the top.
+fn show_plot(ui: &mut Ui, plot: &Plot, texture: &mut Option<TextureHandle>, id: (usize, usize)) {
+    if !plot.title.is_empty() {
+        ui.label(egui::RichText::new(&plot.title).strong());
+    }
+    if !plot.y_label.is_empty() {
+        ui.label(&plot.y_label);
+    }
+
+    let matrix_plot = matches!(plot.kind, PlotKind::Image | PlotKind::Spy);
+    let (log_x, log_y) = (plot.log_x, plot.log_y);
+    let mut widget = egui::plot::Plot::new(("plot", id))
+        .height(300.0)
+        .legend(Legend::default())
+        .x_axis_formatter(move |x, _| axis_label(x, log_x))
+        .y_axis_formatter(move |y, _| axis_label(y, log_y));
+    if matrix_plot {
+        widget = widget
+            .data_aspect(1.0)
+            .y_axis_formatter(|y, _| format_number(-y, NumberFormat::Short));
+    }
+
+    widget.show(ui, |plot_ui| match (plot.kind, &plot.matrix) {
+        (PlotKind::Line, _) => {
+            for series in &plot.series {
+                plot_ui.line(Line::new(plot_points(plot, &series.x, &series.y)).name(&series.name));
+            }
+        }
+        (PlotKind::Scatter, _) => {
+            for series in &plot.series {
+                plot_ui.points(
+                    Points::new(plot_points(plot, &series.x, &series.y))
+                        .radius(3.0)
+                        .name(&series.name),
+                );
+            }
+        }
+        (PlotKind::Histogram { bin_width }, _) => {
+            for series in &plot.series {
+                let bars = series
+                    .x
+                    .iter()
+                    .zip(&series.y)
+                    .map(|(&x, &count)| Bar::new(x, count).width(bin_width))
+                    .collect();
+                plot_ui.bar_chart(BarChart::new(bars).name(&series.name));
+            }
+        }
+        (PlotKind::Image, Some(matrix)) if matrix.nrows() > 0 && matrix.ncols() > 0 => {
+            let texture = texture.get_or_insert_with(|| image_texture(plot_ui.ctx(), matrix));
+            let (rows, cols) = (matrix.nrows() as f64, matrix.ncols() as f64);
+            plot_ui.image(PlotImage::new(
+                texture.id(),
+                PlotPoint::new((cols + 1.0) / 2.0, -(rows + 1.0) / 2.0),
+                [cols as f32, rows as f32],
+            ));
+        }
+        (PlotKind::Spy, Some(matrix)) => {
+            let points = nonzeros(matrix)
+                .into_iter()
+                .map(|(i, j)| [(j + 1) as f64, -((i + 1) as f64)])
+                .collect();
+            plot_ui.points(
+                Points::new(PlotPoints::new(points))
+                    .radius(2.0)
+                    .name(format!("nz = {}", nonzeros(matrix).len())),
+            );
+        }
+        _ => {}
+    });
+
+    if !plot.x_label.is_empty() {
+        ui.label(&plot.x_label);
+    }
+}
+
+fn show_markdown(ui: &mut Ui, source: &str, font_size: f32) {
+    for block in markdown_blocks(source) {
+        match block {
+            Block::Heading(level, text) => {
+                let size = font_size + 2.0 * (7 - level.min(6)) as f32;
+                ui.label(
+                    RichText::new(text)
+                        .font(FontId::proportional(size))
+                        .strong(),
+                );
+            }
+            Block::Item(text) => {
+                ui.label(
+                    RichText::new(format!("  •  {}", text)).font(FontId::proportional(font_size)),
+                );
+            }
+            Block::Paragraph(text) => {
+                ui.add(
+                    egui::Label::new(RichText::new(text).font(FontId::proportional(font_size)))
+                        .wrap(true),
+                );
+            }
+            Block::Code(text) => {
+                ui.label(RichText::new(text).font(FontId::monospace(font_size)));
+            }
+        }
+    }
+}
+
+fn show_output(ui: &mut Ui, output: &mut CellOutput, id: (usize, usize), font_size: f32) {
+    if let Some(plot) = &output.plot {
+        show_plot(ui, plot, &mut output.texture, id);
+        return;
+    }
+    // Monospaced so that matrix columns line up.
+    let mut text = RichText::new(&output.text).font(FontId::monospace(font_size));
+    if output.error {
+        text = text.color(Color32::RED);
+    }
+    ui.add(egui::Label::new(text).wrap(true));
+    if let Some(matrix) = &output.matrix {
+        ui.menu_button("Copy as", |ui| {
+            let format = display_options().format;
+            let copied = if ui.button("Text").clicked() {
+                Some(output.text.clone())
+            } else if ui.button("LaTeX").clicked() {
+                Some(matrix_to_latex(matrix, format, LatexBrackets::Square))
+            } else if ui.button("Markdown").clicked() {
+                Some(matrix_to_markdown(matrix, format))
+            } else if ui.button("HTML").clicked() {
+                Some(matrix_to_html(matrix, format))
+            } else {
+                None
+            };
+            if let Some(text) = copied {
+                ui.output().copied_text = text;
+                ui.close_menu();
+            }
         });
-        self.current_input.clear();
     }
 }
 
-fn display_entry(ui: &mut Ui, entry: &CommandEntry, font_size: f32) {
+// Draws one cell and returns the action of any of its buttons that was clicked.
+// Shift+Enter in a cell runs it.
+fn show_cell(
+    ui: &mut Ui,
+    cell: &mut NotebookCell,
+    index: usize,
+    font_size: f32,
+) -> Option<CellAction> {
+    let mut action = None;
     ui.horizontal(|ui| {
-        ui.label(egui::RichText::new("In:").font(FontId::proportional(font_size)));
-        ui.add(
-            egui::Label::new(
-                egui::RichText::new(&entry.input).font(FontId::proportional(font_size)),
-            )
-            .wrap(true),
-        );
+        let label = match (cell.cell.kind, cell.count) {
+            (CellKind::Markdown, _) => "Markdown".to_string(),
+            (CellKind::Code, Some(count)) => format!("In [{}]:", count),
+            (CellKind::Code, None) => "In [ ]:".to_string(),
+        };
+        ui.label(RichText::new(label).font(FontId::proportional(font_size)));
+        match cell.cell.kind {
+            CellKind::Code => {
+                if ui.button("Run").clicked() {
+                    action = Some(CellAction::Run(index));
+                }
+            }
+            CellKind::Markdown => {
+                let label = if cell.editing { "Done" } else { "Edit" };
+                if ui.button(label).clicked() {
+                    cell.editing = !cell.editing;
+                }
+            }
+        }
+        if ui.button("Run from here").clicked() {
+            action = Some(CellAction::RunFrom(index));
+        }
+        if ui.button("Up").clicked() {
+            action = Some(CellAction::MoveUp(index));
+        }
+        if ui.button("Down").clicked() {
+            action = Some(CellAction::MoveDown(index));
+        }
+        if ui.button("Delete").clicked() {
+            action = Some(CellAction::Delete(index));
+        }
     });
-    ui.horizontal(|ui| {
-        ui.label(egui::RichText::new("Out:").font(FontId::proportional(font_size)));
+
+    if cell.cell.kind == CellKind::Markdown && !cell.editing {
+        show_markdown(ui, &cell.cell.source, font_size);
+    } else {
+        let id = Id::new(("notebook_cell", index));
+        if ui.memory().has_focus(id) && ui.input_mut().consume_key(Modifiers::SHIFT, Key::Enter) {
+            action = Some(CellAction::Run(index));
+        }
+        let font = match cell.cell.kind {
+            CellKind::Code => FontId::monospace(font_size),
+            CellKind::Markdown => FontId::proportional(font_size),
+        };
         ui.add(
-            egui::Label::new(
-                egui::RichText::new(&entry.output).font(FontId::proportional(font_size)),
-            )
-            .wrap(true),
+            TextEdit::multiline(&mut cell.cell.source)
+                .id(id)
+                .font(font)
+                .desired_width(f32::INFINITY)
+                .desired_rows(1),
         );
-    });
+    }
+
+    for (number, output) in cell.outputs.iter_mut().enumerate() {
+        show_output(ui, output, (index, number), font_size);
+    }
     ui.separator();
+    action
 }
 
-fn handle_command(command: &str, matrices: &mut HashMap<String, MatrixDouble>) -> String {
-    let trimmed_command = command.trim();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::accesskit::{Action, ActionRequest, NodeId, Role};
+    use eframe::egui::{Event, FullOutput, Pos2, RawInput, Rect, Shape, Vec2};
+
+    // A widget from the accessibility tree of the last frame.
+    struct Widget {
+        id: NodeId,
+        role: Role,
+        name: String,
+        value: String,
+    }
 
-    // Try to evaluate the expression first
-    match eval_str(trimmed_command) {
-        Ok(result) => return format!("Result: {}", result),
-        Err(_) => {}
+    // Runs the app frame by frame on a bare context, with no window or GPU. Widgets are
+    // found through the accessibility tree that egui builds each frame.
+    struct Harness {
+        app: NumerusApp,
+        ctx: egui::Context,
+        modifiers: Modifiers,
+        events: Vec<Event>,
+        output: FullOutput,
     }
 
-    if let Some((var, matrix_str)) = trimmed_command.split_once('=') {
-        let var = var.trim();
-        let matrix_str = matrix_str.trim();
-        if matrix_str.starts_with('[') && matrix_str.ends_with(']') {
-            match parse_matrix(&matrix_str[1..matrix_str.len() - 1]) {
-                Ok(matrix) => {
-                    matrices.insert(var.to_string(), matrix);
-                    return format!("Matrix {} defined.", var);
+    impl Harness {
+        fn new() -> Harness {
+            let ctx = egui::Context::default();
+            ctx.enable_accesskit();
+            let mut harness = Harness {
+                app: NumerusApp::default(),
+                ctx,
+                modifiers: Modifiers::NONE,
+                events: Vec::new(),
+                output: FullOutput::default(),
+            };
+            harness.frame();
+            harness
+        }
+
+        fn frame(&mut self) {
+            let input = RawInput {
+                screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(1280.0, 1024.0))),
+                modifiers: self.modifiers,
+                events: std::mem::take(&mut self.events),
+                ..RawInput::default()
+            };
+            let app = &mut self.app;
+            self.output = self.ctx.run(input, |ctx| app.show(ctx));
+        }
+
+        // Lets the running cells finish and draws their outputs.
+        fn finish(&mut self) {
+            self.app.wait_for_run();
+            self.frame();
+        }
+
+        fn widgets(&self) -> Vec<Widget> {
+            let update = self.output.platform_output.accesskit_update.as_ref();
+            update
+                .map_or(&[][..], |update| &update.nodes)
+                .iter()
+                .map(|(id, node)| Widget {
+                    id: *id,
+                    role: node.role,
+                    name: node.name.as_deref().unwrap_or_default().to_string(),
+                    value: node.value.as_deref().unwrap_or_default().to_string(),
+                })
+                .collect()
+        }
+
+        fn find(&self, role: Role, matches: impl Fn(&Widget) -> bool) -> NodeId {
+            self.widgets()
+                .into_iter()
+                .rfind(|widget| widget.role == role && matches(widget))
+                .map(|widget| widget.id)
+                .expect("widget not found")
+        }
+
+        fn request(&mut self, action: Action, target: NodeId) {
+            self.events
+                .push(Event::AccessKitActionRequest(ActionRequest {
+                    action,
+                    target,
+                    data: None,
+                }));
+            self.frame();
+        }
+
+        // Clicks the last button with this label, e.g. the Run button of the input box.
+        fn click(&mut self, label: &str) {
+            let button = self.find(Role::Button, |widget| widget.name == label);
+            self.request(Action::Default, button);
+        }
+
+        fn press(&mut self, key: Key, modifiers: Modifiers) {
+            self.modifiers = modifiers;
+            self.events.push(Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+            });
+            self.frame();
+            self.modifiers = Modifiers::NONE;
+        }
+
+        fn type_text(&mut self, text: &str) {
+            self.events.push(Event::Text(text.to_string()));
+            self.frame();
+        }
+
+        // Every piece of text drawn in the last frame.
+        fn text(&self) -> Vec<String> {
+            fn collect(shape: &Shape, texts: &mut Vec<String>) {
+                match shape {
+                    Shape::Text(text) => texts.push(text.galley.text().to_string()),
+                    Shape::Vec(shapes) => shapes.iter().for_each(|shape| collect(shape, texts)),
+                    _ => {}
                 }
-                Err(err) => return format!("Error: {}", err),
-            }
-        } else {
-            return "Invalid matrix format.".to_string();
-        }
-    } else if let Some((a_var, b_var)) = trimmed_command.split_once('+') {
-        return handle_matrix_operations('+', a_var.trim(), b_var.trim(), matrices);
-    } else if let Some((a_var, b_var)) = trimmed_command.split_once('-') {
-        return handle_matrix_operations('-', a_var.trim(), b_var.trim(), matrices);
-    } else if let Some((a_var, b_var)) = trimmed_command.split_once('*') {
-        return handle_matrix_operations('*', a_var.trim(), b_var.trim(), matrices);
-    } else if trimmed_command.starts_with("inv(") && trimmed_command.ends_with(")") {
-        let var = &trimmed_command[4..trimmed_command.len() - 1];
-        if let Some(matrix) = matrices.get(var) {
-            if matrix.nrows() == matrix.ncols() {
-                let lu = LU::new(matrix);
-                let mut inv_matrix = MatrixDouble::new(matrix.nrows(), matrix.ncols());
-                lu.inverse(&mut inv_matrix);
-                return format!(
-                    "Inverse of matrix {}:\n{}",
-                    var,
-                    matrix_to_string(&inv_matrix)
-                );
-            } else {
-                return format!("Matrix {} is not square and cannot be inverted.", var);
             }
-        } else {
-            return format!("Matrix {} is not defined.", var);
-        }
-    } else if trimmed_command.starts_with("det(") && trimmed_command.ends_with(")") {
-        let var = &trimmed_command[4..trimmed_command.len() - 1];
-        if let Some(matrix) = matrices.get(var) {
-            let lu = LU::new(matrix);
-            let det = lu.det();
-            return format!("Determinant of matrix {}: {:.6}", var, det);
-        } else {
-            return format!("Matrix {} is not defined.", var);
-        }
-    } else if trimmed_command.starts_with("solve(") && trimmed_command.ends_with(")") {
-        if let Some((a_var, b_var)) = trimmed_command[6..trimmed_command.len() - 1].split_once(',')
-        {
-            let a_var = a_var.trim();
-            let b_var = b_var.trim();
-            if let Some(matrix) = matrices.get(a_var) {
-                if let Some(vector) = matrices.get(b_var) {
-                    if vector.ncols() == 1 && vector.nrows() == matrix.nrows() {
-                        let lu = LU::new(matrix);
-                        let mut x = VectorDouble::new(vector.nrows());
-                        let b = VectorDouble::from_slice(vector.data());
-                        lu.solve(&b, &mut x);
-                        return format!(
-                            "Solution vector x:\n{}",
-                            (0..x.size())
-                                .map(|i| format!("{:.6}", x[i]))
-                                .collect::<Vec<String>>()
-                                .join("\n")
-                        );
-                    } else {
-                        return format!(
-                            "Vector {} is not a valid vector or does not match matrix {} dimensions.",
-                            b_var, a_var
-                        );
-                    }
-                } else {
-                    return format!("Vector {} is not defined.", b_var);
-                }
-            } else {
-                return format!("Matrix {} is not defined.", a_var);
+            let mut texts = Vec::new();
+            for clipped in &self.output.shapes {
+                collect(&clipped.1, &mut texts);
             }
-        } else {
-            return "Invalid solve command format.".to_string();
-        }
-    } else if trimmed_command.starts_with("lu_decomposition(") && trimmed_command.ends_with(")") {
-        let var = &trimmed_command[17..trimmed_command.len() - 1];
-        if let Some(matrix) = matrices.get(var) {
-            let lu = LU::new(matrix);
-            let mut lu_matrix = MatrixDouble::new(matrix.nrows(), matrix.ncols());
-            lu.lu_decomposition(matrix, &mut lu_matrix);
-            return format!(
-                "LU decomposition of matrix {}:\n{}",
-                var,
-                matrix_to_string(&lu_matrix)
-            );
-        } else {
-            return format!("Matrix {} is not defined.", var);
+            texts
         }
-    } else if matrices.contains_key(trimmed_command) {
-        if let Some(matrix) = matrices.get(trimmed_command) {
-            return format!("Matrix {}:\n{}", trimmed_command, matrix_to_string(matrix));
+
+        fn shows(&self, text: &str) -> bool {
+            self.text().iter().any(|drawn| drawn == text)
         }
-    } else {
-        return format!("Unknown command: {}", trimmed_command);
     }
 
-    "Unknown command".to_string()
-}
+    #[test]
+    fn submitting_input_runs_a_cell_and_fills_the_inspector() {
+        let mut harness = Harness::new();
+        assert!(harness.shows("The workspace is empty."));
 
-fn matrix_to_string(matrix: &MatrixDouble) -> String {
-    let mut result = String::new();
-    for i in 0..matrix.nrows() {
-        for j in 0..matrix.ncols() {
-            result.push_str(&format!("{:.6} ", matrix[i][j]));
+        harness
+            .ctx
+            .memory()
+            .request_focus(Id::new("notebook_input"));
+        harness.frame();
+        harness.type_text("A = [1, 2; 3, 4]");
+        harness.press(Key::Enter, Modifiers::SHIFT);
+        harness.finish();
+
+        assert!(harness.app.current_input.is_empty());
+        assert_eq!(harness.app.cells.len(), 1);
+        assert!(harness.shows("In [1]:"));
+        assert!(harness.shows("Matrix A defined."));
+        for text in ["A", "2x2", "matrix", "32 B"] {
+            assert!(harness.shows(text), "inspector is missing {}", text);
         }
-        result.push('\n');
     }
-    result
-}
 
+    #[test]
+    fn run_button_shows_matrix_output() {
+        let mut harness = Harness::new();
+        harness.app.current_input = "eye(2)".to_string();
+        harness.click("Run");
+        harness.finish();
+
+        let output = &harness.app.cells[0].outputs[0];
+        assert!(!output.error);
+        assert!(output.matrix.is_some());
+        assert_eq!(output.text, "eye(2):\n   1   0\n   0   1\n");
+        assert!(harness.shows(&output.text));
+        assert!(harness
+            .widgets()
+            .iter()
+            .any(|widget| widget.name == "Copy as"));
+    }
+
+    #[test]
+    fn run_all_stops_at_the_first_error() {
+        let mut harness = Harness::new();
+        harness.app.cells = ["A = eye(2)", "B = undefined_matrix", "C = eye(3)"]
+            .into_iter()
+            .map(|source| NotebookCell::new(Cell::code(source)))
+            .collect();
+        harness.frame();
+        harness.click("Run all");
+        harness.finish();
+
+        assert!(harness.shows("Stopped at an error in cell 2."));
+        assert!(harness.app.cells[1].outputs[0].error);
+        assert_eq!(harness.app.cells[2].count, None);
+        assert!(harness.app.matrices.contains_key("A"));
+        assert!(!harness.app.matrices.contains_key("C"));
+    }
+
+    #[test]
+    fn grid_editor_writes_cells_back_to_the_workspace() {
+        let mut harness = Harness::new();
+        harness.app.current_input = "A = [1, 2; 3, 4]".to_string();
+        harness.app.submit_input();
+        harness.finish();
+
+        let variable = harness.find(Role::ToggleButton, |widget| widget.name == "A");
+        harness.request(Action::Default, variable);
+        assert!(harness.app.inspector.is_editing("A"));
+        harness.frame();
+        assert!(harness.shows("Edit A"));
+
+        let cell = harness.find(Role::TextField, |widget| widget.value == "2");
+        harness.request(Action::Focus, cell);
+        harness.type_text("0");
+        harness.press(Key::Enter, Modifiers::NONE);
+
+        assert_eq!(harness.app.matrices["A"][0][1], 20.0);
+        assert!(harness.shows("20"));
+    }
+
+    #[test]
+    fn zoom_shortcuts_change_the_font_size_within_limits() {
+        let mut harness = Harness::new();
+        harness.press(Key::PlusEquals, Modifiers::COMMAND);
+        assert_eq!(harness.app.font_size, 17.0);
+        harness.press(Key::Minus, Modifiers::COMMAND);
+        harness.press(Key::Minus, Modifiers::COMMAND);
+        assert_eq!(harness.app.font_size, 15.0);
+
+        for _ in 0..20 {
+            harness.press(Key::Minus, Modifiers::COMMAND);
+        }
+        assert_eq!(harness.app.font_size, MIN_FONT_SIZE);
+    }
+
+    #[test]
+    fn cancel_stops_a_long_computation_and_keeps_the_workspace() {
+        let mut harness = Harness::new();
+        harness
+            .app
+            .matrices
+            .insert("A".to_string(), MatrixDouble::identity(2));
+        harness.app.current_input = "X = rand(600, 600)\nY = X * X".to_string();
+        harness.app.submit_input();
+        harness.frame();
+        assert!(harness.shows("The workspace is in use by the running cells."));
+
+        harness.click("Cancel");
+        harness.finish();
+
+        assert!(harness.shows("Cancelled cell 1."));
+        let outputs = &harness.app.cells[0].outputs;
+        assert_eq!(outputs.last().unwrap().text, crate::cancel::CANCELLED);
+        assert!(harness.app.matrices.contains_key("A"));
+        assert!(!harness.app.matrices.contains_key("Y"));
+    }
+}
@@ -0,0 +1,399 @@
+use crate::format::{format_number, NumberFormat};
+use crate::io::csv::{parse_csv, CsvOptions};
+use crate::math_utilities::MatrixDouble;
+use crate::interpreter::{display_options, is_identifier};
+use eframe::egui::{self, Color32, Key, RichText, ScrollArea, TextEdit, Ui};
+use meval::eval_str;
+use std::collections::HashMap;
+
+const CELL_WIDTH: f32 = 70.0;
+
+/// Kind of value a matrix represents, judged from its shape.
+pub fn kind(matrix: &MatrixDouble) -> &'static str {
+    match (matrix.nrows(), matrix.ncols()) {
+        (0, _) | (_, 0) => "empty",
+        (1, 1) => "scalar",
+        (1, _) => "row vector",
+        (_, 1) => "column vector",
+        _ => "matrix",
+    }
+}
+
+/// Memory taken by the elements of a matrix, e.g. `72 B` or `7.8 KB`.
+pub fn memory_size(matrix: &MatrixDouble) -> String {
+    let bytes = std::mem::size_of_val(matrix.data());
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+fn insert_row(matrix: &MatrixDouble, at: usize) -> MatrixDouble {
+    MatrixDouble::from_fn(matrix.nrows() + 1, matrix.ncols(), |i, j| {
+        match i.cmp(&at) {
+            std::cmp::Ordering::Less => matrix[i][j],
+            std::cmp::Ordering::Equal => 0.0,
+            std::cmp::Ordering::Greater => matrix[i - 1][j],
+        }
+    })
+}
+
+fn remove_row(matrix: &MatrixDouble, at: usize) -> MatrixDouble {
+    MatrixDouble::from_fn(matrix.nrows() - 1, matrix.ncols(), |i, j| {
+        matrix[if i < at { i } else { i + 1 }][j]
+    })
+}
+
+fn insert_col(matrix: &MatrixDouble, at: usize) -> MatrixDouble {
+    MatrixDouble::from_fn(matrix.nrows(), matrix.ncols() + 1, |i, j| {
+        match j.cmp(&at) {
+            std::cmp::Ordering::Less => matrix[i][j],
+            std::cmp::Ordering::Equal => 0.0,
+            std::cmp::Ordering::Greater => matrix[i][j - 1],
+        }
+    })
+}
+
+fn remove_col(matrix: &MatrixDouble, at: usize) -> MatrixDouble {
+    MatrixDouble::from_fn(matrix.nrows(), matrix.ncols() - 1, |i, j| {
+        matrix[i][if j < at { j } else { j + 1 }]
+    })
+}
+
+// Writes `block` into `matrix` with its top left corner at (row, col), growing the matrix
+// with zeros where the block extends past it.
+fn paste_block(
+    matrix: &MatrixDouble,
+    block: &MatrixDouble,
+    row: usize,
+    col: usize,
+) -> MatrixDouble {
+    let rows = matrix.nrows().max(row + block.nrows());
+    let cols = matrix.ncols().max(col + block.ncols());
+    MatrixDouble::from_fn(rows, cols, |i, j| {
+        if i >= row && j >= col && i - row < block.nrows() && j - col < block.ncols() {
+            block[i - row][j - col]
+        } else if i < matrix.nrows() && j < matrix.ncols() {
+            matrix[i][j]
+        } else {
+            0.0
+        }
+    })
+}
+
+// Spreadsheet-like editor for one variable. Cells show the matrix as it is in the
+// workspace, except for the cell being typed in, which is written back when it loses focus.
+struct GridEditor {
+    name: String,
+    selected: (usize, usize),
+    editing: Option<((usize, usize), String)>,
+    error: Option<String>,
+}
+
+impl GridEditor {
+    fn new(name: &str) -> GridEditor {
+        GridEditor {
+            name: name.to_string(),
+            selected: (0, 0),
+            editing: None,
+            error: None,
+        }
+    }
+
+    // Pastes tab-separated values at the selected cell.
+    fn paste(&mut self, text: &str, matrix: &mut MatrixDouble) {
+        let options = CsvOptions {
+            delimiter: Some('\t'),
+            ..CsvOptions::default()
+        };
+        match parse_csv(text, &options) {
+            Ok(block) => {
+                let (row, col) = self.selected;
+                *matrix = paste_block(matrix, &block, row, col);
+                self.editing = None;
+                self.error = None;
+            }
+            Err(err) => self.error = Some(format!("Cannot paste: {}", err)),
+        }
+    }
+
+    fn show(&mut self, ui: &mut Ui, matrix: &mut MatrixDouble) {
+        // Pasting tab-separated or multi-line text into a cell fills a block of cells instead.
+        if self.editing.is_some() {
+            let mut pasted = None;
+            ui.ctx().input_mut().events.retain(|event| match event {
+                egui::Event::Paste(text) if text.contains(['\t', '\n']) => {
+                    pasted = Some(text.clone());
+                    false
+                }
+                _ => true,
+            });
+            if let Some(text) = pasted {
+                self.paste(&text, matrix);
+            }
+        }
+
+        let (row, col) = self.selected;
+        ui.horizontal(|ui| {
+            if ui.button("Row above").clicked() {
+                *matrix = insert_row(matrix, row.min(matrix.nrows()));
+            }
+            if ui.button("Row below").clicked() {
+                *matrix = insert_row(matrix, (row + 1).min(matrix.nrows()));
+            }
+            if ui.button("Delete row").clicked() && row < matrix.nrows() {
+                *matrix = remove_row(matrix, row);
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Column left").clicked() {
+                *matrix = insert_col(matrix, col.min(matrix.ncols()));
+            }
+            if ui.button("Column right").clicked() {
+                *matrix = insert_col(matrix, (col + 1).min(matrix.ncols()));
+            }
+            if ui.button("Delete column").clicked() && col < matrix.ncols() {
+                *matrix = remove_col(matrix, col);
+            }
+        });
+        ui.label("Select a cell and press Ctrl+V to paste tab-separated values.");
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+        ui.separator();
+
+        let format = display_options().format;
+        ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("grid_editor_cells")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    for j in 0..matrix.ncols() {
+                        ui.label(RichText::new((j + 1).to_string()).strong());
+                    }
+                    ui.end_row();
+
+                    for i in 0..matrix.nrows() {
+                        ui.label(RichText::new((i + 1).to_string()).strong());
+                        for j in 0..matrix.ncols() {
+                            self.show_cell(ui, matrix, (i, j), format);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn show_cell(
+        &mut self,
+        ui: &mut Ui,
+        matrix: &mut MatrixDouble,
+        (i, j): (usize, usize),
+        format: NumberFormat,
+    ) {
+        let editing = matches!(&self.editing, Some((cell, _)) if *cell == (i, j));
+        let mut text = match &self.editing {
+            Some((_, text)) if editing => text.clone(),
+            _ => format_number(matrix[i][j], format),
+        };
+        let response = ui.add(
+            TextEdit::singleline(&mut text)
+                .id_source(("grid_editor_cell", i, j))
+                .desired_width(CELL_WIDTH),
+        );
+
+        if response.gained_focus() {
+            // The previous cell may not have reported losing focus yet.
+            self.commit(matrix);
+            self.selected = (i, j);
+            self.editing = Some(((i, j), matrix[i][j].to_string()));
+        } else if response.changed() {
+            self.editing = Some(((i, j), text));
+        }
+
+        if response.lost_focus() && editing {
+            self.commit(matrix);
+        }
+    }
+
+    // Writes the cell being edited back into the matrix. Cells accept scalar expressions.
+    fn commit(&mut self, matrix: &mut MatrixDouble) {
+        let Some(((i, j), text)) = self.editing.take() else {
+            return;
+        };
+        if i >= matrix.nrows() || j >= matrix.ncols() {
+            return;
+        }
+        match eval_str(text.trim()) {
+            Ok(value) => {
+                matrix[i][j] = value;
+                self.error = None;
+            }
+            Err(_) => {
+                self.error = Some(format!(
+                    "Invalid value '{}' in row {}, column {}.",
+                    text.trim(),
+                    i + 1,
+                    j + 1
+                ))
+            }
+        }
+    }
+}
+
+/// Side panel listing the workspace variables, with renaming, deletion and a grid editor.
+#[derive(Default)]
+pub struct Inspector {
+    editor: Option<GridEditor>,
+    // Variable being renamed and the new name typed so far.
+    renaming: Option<(String, String)>,
+    error: Option<String>,
+}
+
+impl Inspector {
+    pub fn new() -> Inspector {
+        Inspector::default()
+    }
+
+    pub fn is_editing(&self, name: &str) -> bool {
+        self.editor
+            .as_ref()
+            .is_some_and(|editor| editor.name == name)
+    }
+
+    fn rename(&mut self, old: &str, new: &str, matrices: &mut HashMap<String, MatrixDouble>) {
+        let new = new.trim();
+        if new == old {
+            return;
+        }
+        if !is_identifier(new) {
+            self.error = Some(format!("Invalid variable name: {}", new));
+        } else if matrices.contains_key(new) {
+            self.error = Some(format!("Matrix {} already exists.", new));
+        } else if let Some(matrix) = matrices.remove(old) {
+            matrices.insert(new.to_string(), matrix);
+            if let Some(editor) = self.editor.as_mut().filter(|editor| editor.name == old) {
+                editor.name = new.to_string();
+            }
+            self.error = None;
+        }
+    }
+
+    fn delete(&mut self, name: &str, matrices: &mut HashMap<String, MatrixDouble>) {
+        matrices.remove(name);
+        if self.is_editing(name) {
+            self.editor = None;
+        }
+    }
+
+    fn show_variables(&mut self, ui: &mut Ui, matrices: &mut HashMap<String, MatrixDouble>) {
+        let mut names: Vec<String> = matrices.keys().cloned().collect();
+        names.sort();
+
+        let mut renamed = None;
+        let mut deleted = None;
+        egui::Grid::new("variables").striped(true).show(ui, |ui| {
+            ui.label(RichText::new("Name").strong());
+            ui.label(RichText::new("Size").strong());
+            ui.label(RichText::new("Type").strong());
+            ui.label(RichText::new("Memory").strong());
+            ui.end_row();
+
+            for name in &names {
+                let matrix = &matrices[name];
+                if let Some((_, new)) = self.renaming.as_mut().filter(|(old, _)| old == name) {
+                    let response = ui.add(TextEdit::singleline(new).desired_width(80.0));
+                    if response.lost_focus() {
+                        if ui.input().key_pressed(Key::Enter) {
+                            renamed = Some((name.clone(), new.clone()));
+                        }
+                        self.renaming = None;
+                    } else {
+                        response.request_focus();
+                    }
+                } else {
+                    let open = self.is_editing(name);
+                    let mut rename = false;
+                    let response = ui
+                        .selectable_label(open, name)
+                        .on_hover_text("Click to edit, right-click to rename or delete")
+                        .context_menu(|ui| {
+                            if ui.button("Rename").clicked() {
+                                rename = true;
+                                ui.close_menu();
+                            }
+                            if ui.button("Delete").clicked() {
+                                deleted = Some(name.clone());
+                                ui.close_menu();
+                            }
+                        });
+                    if response.clicked() {
+                        self.editor = Some(GridEditor::new(name));
+                    }
+                    if rename {
+                        self.renaming = Some((name.clone(), name.clone()));
+                    }
+                }
+                ui.label(format!("{}x{}", matrix.nrows(), matrix.ncols()));
+                ui.label(kind(matrix));
+                ui.label(memory_size(matrix));
+                ui.end_row();
+            }
+        });
+
+        if let Some((old, new)) = renamed {
+            self.rename(&old, &new, matrices);
+        }
+        if let Some(name) = deleted {
+            self.delete(&name, matrices);
+        }
+    }
+
+    /// Draws the panel while the workspace is lent to cells running in the background.
+    pub fn show_busy(&self, ctx: &egui::Context) {
+        egui::SidePanel::right("variables_panel")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.heading("Variables");
+                ui.label("The workspace is in use by the running cells.");
+            });
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, matrices: &mut HashMap<String, MatrixDouble>) {
+        egui::SidePanel::right("variables_panel")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.heading("Variables");
+                if matrices.is_empty() {
+                    ui.label("The workspace is empty.");
+                }
+                ScrollArea::vertical().show(ui, |ui| {
+                    self.show_variables(ui, matrices);
+                });
+                if let Some(error) = &self.error {
+                    ui.colored_label(Color32::RED, error);
+                }
+            });
+
+        // Close the editor if its variable disappeared, e.g. after `load session.num`.
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+        let Some(matrix) = matrices.get_mut(&editor.name) else {
+            self.editor = None;
+            return;
+        };
+        let mut open = true;
+        egui::Window::new(format!("Edit {}", editor.name))
+            .id(egui::Id::new("grid_editor"))
+            .open(&mut open)
+            .default_size([400.0, 300.0])
+            .show(ctx, |ui| editor.show(ui, matrix));
+        if !open {
+            self.editor = None;
+        }
+    }
+}
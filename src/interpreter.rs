@@ -0,0 +1,1479 @@
+use crate::cancel::{cancelled, CANCELLED};
+use crate::format::{
+    format_matrix, format_number, matrix_to_html, matrix_to_latex, matrix_to_markdown,
+    DisplayOptions, LatexBrackets, NumberFormat,
+};
+use crate::io::csv::{read_csv, write_csv, CsvOptions};
+use crate::io::mat::{read_mat, write_mat, MatFile, MatVariable};
+use crate::io::matrix_market;
+use crate::io::npy::{read_npy, read_npz, write_npy, write_npz, NpyArray};
+use crate::io::session::{load_session, save_session};
+use crate::lu_decomposition::LU;
+use crate::math_utilities::{MatrixDouble, VectorDouble, MAX_ELEMENTS};
+use crate::matrix_functions::{expm, logm, powm, sqrtm};
+use crate::matrix_operations::{add_matrices, multiply_matrices, subtract_matrices};
+use crate::output::{Output, Value};
+use crate::plot::{render_text, Plot, Series, TEXT_HEIGHT, TEXT_WIDTH};
+use crate::random::{rand, randn, Rng};
+use crate::special_matrices::{eye, hilb, linspace, magic, vander};
+use meval::eval_str;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+// Like MATLAB, every session starts from the same seed until `rng(seed)` is called.
+const DEFAULT_SEED: u64 = 0;
+
+/// The state that `format`, `disp` and `rng` change and that `rand` and the output draw on.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub display: DisplayOptions,
+    pub rng: Rng,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            display: DisplayOptions::default(),
+            rng: Rng::new(DEFAULT_SEED),
+        }
+    }
+}
+
+// Shared by the front ends that run a single workspace: the REPL, the GUI and the kernel.
+static GLOBAL: LazyLock<Mutex<Settings>> = LazyLock::new(|| Mutex::new(Settings::default()));
+
+thread_local! {
+    static CURRENT: RefCell<Option<Settings>> = const { RefCell::new(None) };
+}
+
+// Hands the settings back to their owner when dropped, also when `f` panics.
+struct Restore<'a> {
+    owner: &'a mut Settings,
+    previous: Option<Settings>,
+}
+
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        let current = CURRENT.with(|current| current.replace(self.previous.take()));
+        if let Some(current) = current {
+            *self.owner = current;
+        }
+    }
+}
+
+/// Runs `f` with `settings` in place of the shared ones on this thread, so that servers can
+/// give every connection its own format and random generator.
+pub fn with_settings<R>(settings: &mut Settings, f: impl FnOnce() -> R) -> R {
+    let previous = CURRENT.with(|current| current.replace(Some(settings.clone())));
+    let _restore = Restore {
+        owner: settings,
+        previous,
+    };
+    f()
+}
+
+fn settings<R>(f: impl FnOnce(&mut Settings) -> R) -> R {
+    CURRENT.with(|current| match current.borrow_mut().as_mut() {
+        Some(settings) => f(settings),
+        None => f(&mut GLOBAL.lock().unwrap()),
+    })
+}
+
+pub fn display_options() -> DisplayOptions {
+    settings(|settings| settings.display)
+}
+
+// Returns the inside of `[...]` when the brackets enclose the whole of `text`.
+fn bracketed(text: &str) -> Option<&str> {
+    enclosed(text, '[', ']')
+}
+
+// The inside of `(...)` when the outer parentheses match each other.
+fn parenthesized(text: &str) -> Option<&str> {
+    enclosed(text, '(', ')')
+}
+
+fn enclosed(text: &str, open: char, close: char) -> Option<&str> {
+    let inner = text.strip_prefix(open)?.strip_suffix(close)?;
+    let mut depth = 0;
+    for c in inner.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            _ => {}
+        }
+        if depth < 0 {
+            return None;
+        }
+    }
+    Some(inner)
+}
+
+// Splits a literal into rows at `;` and newlines outside nested brackets, dropping empty rows.
+fn split_rows(input: &str) -> Vec<&str> {
+    let mut rows = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in input.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            ';' | '\n' if depth == 0 => {
+                rows.push(&input[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    rows.push(&input[start..]);
+    rows.into_iter()
+        .map(str::trim)
+        .filter(|row| !row.is_empty())
+        .collect()
+}
+
+// Splits a row into elements at commas and whitespace. As in MATLAB, whitespace next to a
+// binary operator does not separate elements, so `[1 - 2]` has one element and `[1 -2]` two.
+fn split_elements(row: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = row.char_indices().collect();
+    let mut elements = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (index, c) = chars[i];
+        if depth == 0 && c == ',' {
+            if let Some(s) = start.take() {
+                elements.push(row[s..index].trim_end());
+            }
+        } else if depth == 0 && c.is_whitespace() {
+            let mut next = i;
+            while next < chars.len() && chars[next].1.is_whitespace() {
+                next += 1;
+            }
+            if let Some(s) = start {
+                let previous = row[s..index].chars().last();
+                let binary_operator = match chars.get(next).map(|&(_, c)| c) {
+                    Some('*' | '/' | '^') => true,
+                    Some('+' | '-') => chars.get(next + 1).is_none_or(|&(_, c)| c.is_whitespace()),
+                    _ => false,
+                };
+                let joins =
+                    binary_operator || matches!(previous, Some('+' | '-' | '*' | '/' | '^'));
+                if !joins {
+                    elements.push(&row[s..index]);
+                    start = None;
+                }
+            }
+            i = next;
+            continue;
+        } else {
+            start.get_or_insert(index);
+            match c {
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth -= 1,
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    if let Some(s) = start {
+        elements.push(row[s..].trim_end());
+    }
+    elements
+}
+
+fn value_to_matrix(value: Value) -> Option<MatrixDouble> {
+    match value {
+        Value::Scalar(x) => Some(MatrixDouble::from_slice(1, 1, &[x])),
+        Value::Vector(v) => Some(MatrixDouble::from_slice(v.size(), 1, v.data())),
+        Value::Matrix(m) => Some(m),
+        Value::Lu { .. } | Value::None | Value::Plot(_) => None,
+    }
+}
+
+fn evaluate_element(
+    element: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<MatrixDouble, String> {
+    if let Ok(x) = element.parse::<f64>() {
+        return Ok(MatrixDouble::from_slice(1, 1, &[x]));
+    }
+    match evaluate_expression(element, matrices) {
+        Ok(output) => value_to_matrix(output.value)
+            .ok_or_else(|| format!("'{}' is not a number or matrix", element)),
+        Err(err) if err.starts_with("Unknown command") => {
+            Err(format!("invalid element '{}'", element))
+        }
+        Err(err) => Err(err.trim_end().to_string()),
+    }
+}
+
+/// Parses the inside of a matrix literal. Rows are separated by `;` or newlines and elements
+/// by whitespace or commas. Elements may be expressions, and matrix-valued elements are
+/// concatenated as blocks, so `[A B; C D]` and `[A; [0 0]]` work. Errors give the row and
+/// column of the result at which the offending element starts.
+pub fn parse_matrix(
+    input: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<MatrixDouble, String> {
+    let mut data = Vec::new();
+    let mut nrows = 0;
+    let mut ncols = None;
+
+    for row in split_rows(input) {
+        let mut blocks: Vec<MatrixDouble> = Vec::new();
+        let mut width = 0;
+        for element in split_elements(row) {
+            let block = evaluate_element(element, matrices)
+                .map_err(|err| format!("row {}, column {}: {}", nrows + 1, width + 1, err))?;
+            if block.nrows() == 0 || block.ncols() == 0 {
+                continue;
+            }
+            if let Some(first) = blocks.first() {
+                if first.nrows() != block.nrows() {
+                    return Err(format!(
+                        "row {}, column {}: expected {} rows, found {}",
+                        nrows + 1,
+                        width + 1,
+                        first.nrows(),
+                        block.nrows()
+                    ));
+                }
+            }
+            width += block.ncols();
+            blocks.push(block);
+        }
+
+        let height = match blocks.first() {
+            Some(first) => first.nrows(),
+            None => continue,
+        };
+        if let Some(n) = ncols.filter(|&n| n != width) {
+            return Err(format!(
+                "row {}: expected {} columns, found {}",
+                nrows + 1,
+                n,
+                width
+            ));
+        }
+        ncols = Some(width);
+        for i in 0..height {
+            for block in &blocks {
+                data.extend_from_slice(&block[i]);
+            }
+        }
+        nrows += height;
+    }
+
+    Ok(MatrixDouble::from_slice(nrows, ncols.unwrap_or(0), &data))
+}
+
+pub fn parse_vector(input: &str) -> Result<VectorDouble, &'static str> {
+    let cols: Vec<&str> = input.split_whitespace().collect();
+    let mut data = Vec::new();
+
+    for col in cols {
+        match col.parse::<f64>() {
+            Ok(num) => data.push(num),
+            Err(_) => return Err("Invalid number in vector"),
+        }
+    }
+
+    Ok(VectorDouble::from_slice(&data))
+}
+
+fn resolve_matrix<'a>(
+    arg: &str,
+    matrices: &'a HashMap<String, MatrixDouble>,
+) -> Result<Cow<'a, MatrixDouble>, String> {
+    let arg = arg.trim();
+    if let Some(matrix) = matrices.get(arg) {
+        return Ok(Cow::Borrowed(matrix));
+    }
+    match evaluate_expression(arg, matrices) {
+        Ok(output) => value_to_matrix(output.value)
+            .map(Cow::Owned)
+            .ok_or_else(|| format!("{} is not a matrix.", arg)),
+        Err(err) if err.starts_with("Unknown command") => {
+            Err(format!("Matrix {} is not defined.", arg))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Splits an expression at its top-level binary operator of lowest precedence: the last `+` or
+// `-`, else the last `*`, else the first `^`. So `+`, `-` and `*` associate to the left and `^`
+// to the right. Unary signs and the sign in an exponent such as `1e-3` are not operators.
+fn split_operator(expression: &str) -> Option<(&str, char, &str)> {
+    let mut additive = None;
+    let mut multiplicative = None;
+    let mut power = None;
+    let mut depth = 0;
+    for (index, c) in expression.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            '+' | '-' if depth == 0 && ends_with_operand(&expression[..index]) => {
+                additive = Some((index, c))
+            }
+            '*' if depth == 0 => multiplicative = Some((index, c)),
+            '^' if depth == 0 && power.is_none() => power = Some((index, c)),
+            _ => {}
+        }
+    }
+    let (index, operator) = additive.or(multiplicative).or(power)?;
+    Some((&expression[..index], operator, &expression[index + 1..]))
+}
+
+fn ends_with_operand(text: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let text = text.trim_end();
+    match text.chars().last() {
+        Some(')' | ']') => true,
+        Some(last) if is_word(last) => {
+            let word = &text[text.trim_end_matches(is_word).len()..];
+            let mantissa = &word[..word.len() - 1];
+            let is_exponent = matches!(last, 'e' | 'E')
+                && mantissa.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+                && mantissa.chars().all(|c| c.is_ascii_digit() || c == '.');
+            !is_exponent
+        }
+        _ => false,
+    }
+}
+
+// Splits `name(arg, ...)` into the name and its top-level arguments.
+fn parse_call(command: &str) -> Option<(&str, Vec<&str>)> {
+    let open = command.find('(')?;
+    let name = command[..open].trim();
+    if !is_identifier(name) {
+        return None;
+    }
+    let inner = command[open + 1..].strip_suffix(')')?;
+
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(inner[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+        if depth < 0 {
+            return None;
+        }
+    }
+    if !inner.trim().is_empty() {
+        args.push(inner[start..].trim());
+    }
+    Some((name, args))
+}
+
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn evaluate_scalar(arg: &str, matrices: &HashMap<String, MatrixDouble>) -> Result<f64, String> {
+    let matrix = resolve_matrix(arg, matrices)?;
+    if matrix.nrows() == 1 && matrix.ncols() == 1 {
+        Ok(matrix[0][0])
+    } else {
+        Err(format!("{} is not a scalar.", arg))
+    }
+}
+
+fn evaluate_size(arg: &str, matrices: &HashMap<String, MatrixDouble>) -> Result<usize, String> {
+    let size = evaluate_scalar(arg, matrices)?;
+    if size >= 0.0 && size.fract() == 0.0 {
+        Ok(size as usize)
+    } else {
+        Err(format!("Invalid size: {}", arg))
+    }
+}
+
+fn check_shape(rows: usize, cols: usize) -> Result<(usize, usize), String> {
+    match rows.checked_mul(cols) {
+        Some(count) if count <= MAX_ELEMENTS => Ok((rows, cols)),
+        _ => Err(format!(
+            "A {} x {} matrix is too large; the limit is {} elements.",
+            rows, cols, MAX_ELEMENTS
+        )),
+    }
+}
+
+// Reads the order n of an n x n matrix.
+fn evaluate_order(arg: &str, matrices: &HashMap<String, MatrixDouble>) -> Result<usize, String> {
+    let n = evaluate_size(arg, matrices)?;
+    check_shape(n, n).map(|_| n)
+}
+
+// Reads `(n)` as n x n and `(m, n)` as m x n.
+fn evaluate_shape(
+    name: &str,
+    args: &[&str],
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<(usize, usize), String> {
+    match args {
+        [n] => {
+            let n = evaluate_order(n, matrices)?;
+            Ok((n, n))
+        }
+        [m, n] => check_shape(evaluate_size(m, matrices)?, evaluate_size(n, matrices)?),
+        _ => Err(format!("{} expects one or two sizes.", name)),
+    }
+}
+
+fn evaluate_vector(
+    arg: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Vec<f64>, String> {
+    let matrix = resolve_matrix(arg, matrices)?;
+    if matrix.nrows() == 1 || matrix.ncols() == 1 {
+        Ok(matrix.data().to_vec())
+    } else {
+        Err(format!("{} is not a vector.", arg))
+    }
+}
+
+fn evaluate_linspace(
+    args: &[&str],
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<MatrixDouble, String> {
+    let (a, b, n) = match args {
+        [a, b] => (a, b, 100),
+        [a, b, n] => (a, b, check_shape(1, evaluate_size(n, matrices)?)?.1),
+        _ => return Err("linspace expects two or three arguments.".to_string()),
+    };
+    Ok(linspace(
+        evaluate_scalar(a, matrices)?,
+        evaluate_scalar(b, matrices)?,
+        n,
+    ))
+}
+
+// Series of `plot(x, y)`. The columns of a matrix `y` are separate series, or its rows when
+// only they match the length of `x`.
+fn plot_series(
+    x_arg: Option<&str>,
+    y_arg: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Vec<Series>, String> {
+    let y = resolve_matrix(y_arg, matrices)?;
+    let x = x_arg.map(|x| evaluate_vector(x, matrices)).transpose()?;
+    let by_rows = y.nrows() > 1
+        && y.ncols() > 1
+        && x.as_ref()
+            .is_some_and(|x| x.len() == y.ncols() && x.len() != y.nrows());
+
+    let lines: Vec<(String, Vec<f64>)> = if y.nrows() == 1 || y.ncols() == 1 {
+        vec![(y_arg.to_string(), y.data().to_vec())]
+    } else if by_rows {
+        (0..y.nrows())
+            .map(|i| (format!("{}({},:)", y_arg, i + 1), y[i].to_vec()))
+            .collect()
+    } else {
+        (0..y.ncols())
+            .map(|j| {
+                let column = (0..y.nrows()).map(|i| y[i][j]).collect();
+                (format!("{}(:,{})", y_arg, j + 1), column)
+            })
+            .collect()
+    };
+
+    lines
+        .into_iter()
+        .map(|(name, values)| {
+            let x = match &x {
+                Some(x) if x.len() != values.len() => {
+                    return Err(format!(
+                        "{} and {} have different lengths.",
+                        x_arg.unwrap_or_default(),
+                        y_arg
+                    ))
+                }
+                Some(x) => x.clone(),
+                None => (1..=values.len()).map(|k| k as f64).collect(),
+            };
+            Ok(Series { name, x, y: values })
+        })
+        .collect()
+}
+
+// Plots take data arguments followed by options: `title=...`, `xlabel=...`, `ylabel=...`,
+// `legend=a|b`, `logx`, `logy` and `loglog`.
+fn evaluate_plot(
+    name: &str,
+    args: &[&str],
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Output, String> {
+    const DEFAULT_BINS: usize = 10;
+
+    let mut data = Vec::new();
+    let (mut title, mut x_label, mut y_label) = (String::new(), String::new(), String::new());
+    let (mut log_x, mut log_y) = (false, false);
+    let mut legend = None;
+    for &arg in args {
+        match arg.split_once('=').map(|(key, value)| (key.trim(), value.trim())) {
+            Some(("title", value)) => title = value.to_string(),
+            Some(("xlabel", value)) => x_label = value.to_string(),
+            Some(("ylabel", value)) => y_label = value.to_string(),
+            Some(("legend", value)) => {
+                legend = Some(value.split('|').map(|s| s.trim().to_string()).collect::<Vec<_>>())
+            }
+            Some((key, _)) => return Err(format!("Unknown {} option: {}", name, key)),
+            None if arg == "logx" => log_x = true,
+            None if arg == "logy" => log_y = true,
+            None if arg == "loglog" => (log_x, log_y) = (true, true),
+            None => data.push(arg),
+        }
+    }
+
+    let mut plot = match (name, data.as_slice()) {
+        ("imagesc", [a]) => Plot::image(resolve_matrix(a, matrices)?.into_owned()),
+        ("spy", [a]) => Plot::spy(resolve_matrix(a, matrices)?.into_owned()),
+        ("hist", [v]) => Plot::histogram(v, &evaluate_vector(v, matrices)?, DEFAULT_BINS),
+        ("hist", [v, bins]) => match check_shape(1, evaluate_size(bins, matrices)?)?.1 {
+            0 => return Err("hist needs at least one bin.".to_string()),
+            bins => Plot::histogram(v, &evaluate_vector(v, matrices)?, bins),
+        },
+        ("plot", [y]) => Plot::lines(plot_series(None, y, matrices)?),
+        ("plot" | "scatter", pairs) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+            let mut series = Vec::new();
+            for pair in pairs.chunks(2) {
+                series.extend(plot_series(Some(pair[0]), pair[1], matrices)?);
+            }
+            if name == "plot" {
+                Plot::lines(series)
+            } else {
+                Plot::scatter(series)
+            }
+        }
+        _ => {
+            let signature = find_function(name).map_or(name, |(signature, _)| signature);
+            return Err(format!("Invalid arguments. Usage: {}", signature));
+        }
+    };
+
+    if let Some(names) = legend {
+        if names.len() != plot.series.len() {
+            return Err(format!(
+                "legend gives {} names for {} series.",
+                names.len(),
+                plot.series.len()
+            ));
+        }
+        for (series, name) in plot.series.iter_mut().zip(names) {
+            series.name = name;
+        }
+    }
+    plot.title = title;
+    plot.x_label = x_label;
+    plot.y_label = y_label;
+    plot.log_x = log_x;
+    plot.log_y = log_y;
+
+    Ok(Output::new(
+        render_text(&plot, TEXT_WIDTH, TEXT_HEIGHT),
+        Value::Plot(plot),
+    ))
+}
+
+/// Handles the plotting functions, or returns `None` if `name` is not one of them.
+pub fn handle_plot(
+    name: &str,
+    args: &[&str],
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Option<Result<Output, String>> {
+    ["plot", "scatter", "hist", "imagesc", "spy"]
+        .contains(&name)
+        .then(|| evaluate_plot(name, args, matrices))
+}
+
+/// Handles the functions that create matrices, or returns `None` if `name` is not one of them.
+pub fn handle_constructor(
+    name: &str,
+    args: &[&str],
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Option<Result<Output, String>> {
+    let result = match name {
+        "eye" => evaluate_shape(name, args, matrices).map(|(m, n)| eye(m, n)),
+        "zeros" => evaluate_shape(name, args, matrices).map(|(m, n)| MatrixDouble::zeros(m, n)),
+        "ones" => evaluate_shape(name, args, matrices).map(|(m, n)| MatrixDouble::ones(m, n)),
+        "rand" => evaluate_shape(name, args, matrices)
+            .map(|(m, n)| settings(|settings| rand(m, n, &mut settings.rng))),
+        "randn" => evaluate_shape(name, args, matrices)
+            .map(|(m, n)| settings(|settings| randn(m, n, &mut settings.rng))),
+        "magic" => match args {
+            [n] => evaluate_order(n, matrices).map(magic),
+            _ => Err("magic expects one size.".to_string()),
+        },
+        "hilb" => match args {
+            [n] => evaluate_order(n, matrices).map(hilb),
+            _ => Err("hilb expects one size.".to_string()),
+        },
+        "vander" => match args {
+            [v] => evaluate_vector(v, matrices)
+                .and_then(|v| check_shape(v.len(), v.len()).map(|_| vander(&v))),
+            _ => Err("vander expects one vector.".to_string()),
+        },
+        "diag" => match args {
+            [v] => resolve_matrix(v, matrices).map(|a| {
+                if a.nrows() == 1 || a.ncols() == 1 {
+                    MatrixDouble::from_diag(a.data())
+                } else {
+                    let n = a.nrows().min(a.ncols());
+                    MatrixDouble::from_fn(n, 1, |i, _| a[i][i])
+                }
+            }),
+            _ => Err("diag expects one argument.".to_string()),
+        },
+        "linspace" => evaluate_linspace(args, matrices),
+        "rng" => {
+            return Some(match args {
+                [seed] => evaluate_size(seed, matrices).map(|seed| {
+                    settings(|settings| settings.rng = Rng::new(seed as u64));
+                    Output::message(format!("Random seed set to {}.", seed))
+                }),
+                _ => Err("rng expects one seed.".to_string()),
+            })
+        }
+        _ => return None,
+    };
+
+    Some(result.map(|matrix| {
+        Output::new(
+            format!("{}({}):\n{}", name, args.join(", "), matrix_to_string(&matrix)),
+            Value::Matrix(matrix),
+        )
+    }))
+}
+
+pub fn handle_matrix_operations(
+    operation: char,
+    a_var: &str,
+    b_var: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Output, String> {
+    let a = resolve_matrix(a_var, matrices)?;
+    let b = resolve_matrix(b_var, matrices)?;
+    let result = match operation {
+        '+' => add_matrices(&a, &b),
+        '-' => subtract_matrices(&a, &b),
+        '*' => multiply_matrices(&a, &b),
+        _ => Err("Unknown operation"),
+    };
+
+    match result {
+        Ok(result) => {
+            let mut output = format!("Result of {} {} {}:\n", a_var, operation, b_var);
+            output.push_str(&matrix_to_string(&result));
+            Ok(Output::new(output, Value::Matrix(result)))
+        }
+        Err(err) => Err(format!("Error: {}", err)),
+    }
+}
+
+pub fn handle_matrix_function(
+    function: &str,
+    var: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Output, String> {
+    let matrix = resolve_matrix(var, matrices)?;
+    let result = match function {
+        "expm" => expm(&matrix),
+        "logm" => logm(&matrix),
+        "sqrtm" => sqrtm(&matrix),
+        _ => Err("Unknown function"),
+    };
+
+    match result {
+        Ok(result) => Ok(Output::new(
+            format!("{}({}):\n{}", function, var, matrix_to_string(&result)),
+            Value::Matrix(result),
+        )),
+        Err(err) => Err(format!("Error: {}", err)),
+    }
+}
+
+pub fn handle_matrix_power(
+    var: &str,
+    exponent: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Output, String> {
+    let p = eval_str(exponent).map_err(|_| format!("Invalid exponent: {}", exponent))?;
+    let matrix = resolve_matrix(var, matrices)?;
+
+    match powm(&matrix, p) {
+        Ok(result) => {
+            let mut output = format!("Result of {} ^ {}:\n", var, p);
+            output.push_str(&matrix_to_string(&result));
+            Ok(Output::new(output, Value::Matrix(result)))
+        }
+        Err(err) => Err(format!("Error: {}", err)),
+    }
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn parse_io_options(path: &str, flags: &[&str]) -> Result<CsvOptions, String> {
+    let mut options = CsvOptions::for_path(Path::new(path));
+    for flag in flags {
+        if *flag == "header" {
+            options.skip_header = true;
+        } else if let Some(delimiter) = flag.strip_prefix("delimiter=") {
+            options.delimiter = match delimiter {
+                "tab" => Some('\t'),
+                "space" | "whitespace" => None,
+                _ if delimiter.chars().count() == 1 => delimiter.chars().next(),
+                _ => return Err(format!("Invalid delimiter: {}", delimiter)),
+            };
+        } else if let Some(comment) = flag.strip_prefix("comment=") {
+            // Statements cannot contain `#` or `%`, which start comments, so they have names.
+            options.comment = match comment {
+                "none" => None,
+                "hash" => Some('#'),
+                "percent" => Some('%'),
+                _ if comment.chars().count() == 1 => comment.chars().next(),
+                _ => return Err(format!("Invalid comment character: {}", comment)),
+            };
+        } else {
+            return Err(format!("Unknown option: {}", flag));
+        }
+    }
+    Ok(options)
+}
+
+pub fn handle_load(
+    args: &str,
+    matrices: &mut HashMap<String, MatrixDouble>,
+) -> Result<String, String> {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if parts.len() == 1 && has_extension(parts[0], "npz") {
+        return load_workspace(parts[0], matrices);
+    }
+    if parts.len() == 1 && has_extension(parts[0], "mat") {
+        return load_mat_workspace(parts[0], matrices);
+    }
+    if parts.len() == 1 && has_extension(parts[0], "num") {
+        let session =
+            load_session(parts[0]).map_err(|err| format!("Error: {}: {}", parts[0], err))?;
+        let count = session.len();
+        matrices.extend(session);
+        return Ok(format!("Loaded {} matrices from {}.", count, parts[0]));
+    }
+    if parts.len() < 2 {
+        return Err("Usage: load <name> <file> [header] [delimiter=<c>] [comment=<c>]".to_string());
+    }
+    let (var, path) = (parts[0], parts[1]);
+
+    let options =
+        parse_io_options(path, &parts[2..]).map_err(|err| format!("Error: {}", err))?;
+
+    let result = if has_extension(path, "mtx") {
+        matrix_market::load_matrix(path)
+    } else if has_extension(path, "npy") {
+        read_npy(path).and_then(|array| array.to_matrix())
+    } else {
+        read_csv(path, &options)
+    };
+
+    let matrix = result.map_err(|err| format!("Error: {}: {}", path, err))?;
+    let output = format!(
+        "Matrix {} loaded from {} ({}x{}).",
+        var,
+        path,
+        matrix.nrows(),
+        matrix.ncols()
+    );
+    matrices.insert(var.to_string(), matrix);
+    Ok(output)
+}
+
+pub fn handle_save(
+    args: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<String, String> {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if parts.len() == 1 && has_extension(parts[0], "npz") {
+        return save_workspace(parts[0], matrices);
+    }
+    if parts.len() == 1 && has_extension(parts[0], "mat") {
+        return save_mat_workspace(parts[0], matrices);
+    }
+    if parts.len() == 1 && has_extension(parts[0], "num") {
+        save_session(parts[0], matrices).map_err(|err| format!("Error: {}: {}", parts[0], err))?;
+        return Ok(format!("Saved {} matrices to {}.", matrices.len(), parts[0]));
+    }
+    if parts.len() < 2 {
+        return Err("Usage: save <name> <file> [delimiter=<c>]".to_string());
+    }
+    let (var, path) = (parts[0], parts[1]);
+
+    let options =
+        parse_io_options(path, &parts[2..]).map_err(|err| format!("Error: {}", err))?;
+
+    let matrix = matrices
+        .get(var)
+        .ok_or_else(|| format!("Matrix {} is not defined.", var))?;
+    let result = if has_extension(path, "mtx") {
+        matrix_market::save_matrix(path, matrix)
+    } else if has_extension(path, "npy") {
+        write_npy(path, &NpyArray::from_matrix(matrix))
+    } else {
+        write_csv(path, matrix, &options)
+    };
+
+    result.map_err(|err| format!("Error: {}: {}", path, err))?;
+    Ok(format!("Matrix {} saved to {}.", var, path))
+}
+
+fn load_workspace(
+    path: &str,
+    matrices: &mut HashMap<String, MatrixDouble>,
+) -> Result<String, String> {
+    let arrays = read_npz(path).map_err(|err| format!("Error: {}: {}", path, err))?;
+
+    let mut loaded = Vec::new();
+    let mut skipped = Vec::new();
+    for (name, array) in arrays {
+        if !is_identifier(&name) {
+            skipped.push(format!("Warning: skipped '{}': not a valid variable name", name));
+            continue;
+        }
+        match array.to_matrix() {
+            Ok(matrix) => {
+                matrices.insert(name.clone(), matrix);
+                loaded.push(name);
+            }
+            Err(err) => skipped.push(format!("Warning: skipped {}: {}", name, err)),
+        }
+    }
+
+    let mut output = format!("Loaded {} from {}.", loaded.join(", "), path);
+    for warning in skipped {
+        output.push('\n');
+        output.push_str(&warning);
+    }
+    Ok(output)
+}
+
+fn save_workspace(path: &str, matrices: &HashMap<String, MatrixDouble>) -> Result<String, String> {
+    let mut names: Vec<&String> = matrices.keys().collect();
+    names.sort();
+    let arrays: Vec<(String, NpyArray)> = names
+        .iter()
+        .map(|name| ((*name).clone(), NpyArray::from_matrix(&matrices[*name])))
+        .collect();
+
+    write_npz(path, &arrays).map_err(|err| format!("Error: {}: {}", path, err))?;
+    Ok(format!("Saved {} matrices to {}.", arrays.len(), path))
+}
+
+fn load_mat_workspace(
+    path: &str,
+    matrices: &mut HashMap<String, MatrixDouble>,
+) -> Result<String, String> {
+    let file = read_mat(path).map_err(|err| format!("Error: {}: {}", path, err))?;
+
+    let mut loaded = Vec::new();
+    let mut warnings: Vec<String> = file
+        .warnings
+        .iter()
+        .map(|w| format!("Warning: {}", w))
+        .collect();
+    for variable in &file.variables {
+        match variable.to_dense() {
+            Ok(matrix) => {
+                matrices.insert(variable.name.clone(), matrix);
+                loaded.push(variable.name.clone());
+            }
+            Err(err) => warnings.push(format!("Warning: skipped {}: {}", variable.name, err)),
+        }
+    }
+
+    let mut output = format!("Loaded {} from {}.", loaded.join(", "), path);
+    for warning in warnings {
+        output.push('\n');
+        output.push_str(&warning);
+    }
+    Ok(output)
+}
+
+fn save_mat_workspace(
+    path: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<String, String> {
+    let mut names: Vec<&String> = matrices.keys().collect();
+    names.sort();
+    let file = MatFile {
+        variables: names
+            .iter()
+            .map(|name| MatVariable::from_matrix(name, &matrices[*name]))
+            .collect(),
+        warnings: Vec::new(),
+    };
+
+    write_mat(path, &file, true).map_err(|err| format!("Error: {}: {}", path, err))?;
+    Ok(format!("Saved {} matrices to {}.", file.variables.len(), path))
+}
+
+/// Built-in functions as (name, signature, description).
+pub const FUNCTIONS: &[(&str, &str, &str)] = &[
+    ("inv", "inv(A)", "Compute the inverse of matrix A"),
+    ("det", "det(A)", "Compute the determinant of matrix A"),
+    ("solve", "solve(A, b)", "Solve the system Ax = b"),
+    ("lu_decomposition", "lu_decomposition(A)", "Perform LU decomposition of matrix A"),
+    ("expm", "expm(A)", "Compute the matrix exponential of A"),
+    ("logm", "logm(A)", "Compute the principal matrix logarithm of A"),
+    ("sqrtm", "sqrtm(A)", "Compute the principal matrix square root of A"),
+    ("powm", "powm(A, p)", "Raise matrix A to the real power p"),
+    ("eye", "eye(n)", "Create an n x n identity matrix (or eye(m, n))"),
+    ("zeros", "zeros(m, n)", "Create an m x n matrix of zeros"),
+    ("ones", "ones(m, n)", "Create an m x n matrix of ones"),
+    ("rand", "rand(m, n)", "Create an m x n matrix of uniform random numbers"),
+    ("randn", "randn(m, n)", "Create an m x n matrix of normal random numbers"),
+    ("rng", "rng(seed)", "Seed the random number generator"),
+    ("diag", "diag(v)", "Diagonal matrix from v, or the diagonal of a matrix"),
+    ("linspace", "linspace(a, b, n)", "Create n evenly spaced points from a to b"),
+    ("magic", "magic(n)", "Create an n x n magic square"),
+    ("hilb", "hilb(n)", "Create the n x n Hilbert matrix"),
+    ("vander", "vander(v)", "Create the Vandermonde matrix of v"),
+    ("disp", "disp(X)", "Display a value without its name"),
+    ("latex", "latex(A)", "Render A as a LaTeX bmatrix"),
+    ("markdown", "markdown(A)", "Render A as a Markdown table"),
+    ("html", "html(A)", "Render A as an HTML table"),
+    ("plot", "plot(x, y)", "Plot y against x; more x, y pairs add series"),
+    ("scatter", "scatter(x, y)", "Scatter plot of y against x"),
+    ("hist", "hist(v, bins)", "Histogram of v (10 bins by default)"),
+    ("imagesc", "imagesc(A)", "Show A as a colour-mapped image"),
+    ("spy", "spy(A)", "Show the nonzero pattern of A"),
+];
+
+pub fn find_function(name: &str) -> Option<(&'static str, &'static str)> {
+    FUNCTIONS
+        .iter()
+        .find(|(function, _, _)| *function == name)
+        .map(|(_, signature, description)| (*signature, *description))
+}
+
+/// Function and variable names starting with `prefix`, functions first.
+pub fn completion_candidates<'a>(
+    prefix: &str,
+    variables: impl IntoIterator<Item = &'a String>,
+) -> Vec<String> {
+    let mut variables: Vec<String> = variables
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .cloned()
+        .collect();
+    variables.sort();
+
+    FUNCTIONS
+        .iter()
+        .map(|(name, _, _)| name.to_string())
+        .filter(|name| name.starts_with(prefix))
+        .chain(variables)
+        .collect()
+}
+
+pub fn matrix_to_string(matrix: &MatrixDouble) -> String {
+    format_matrix(matrix, &display_options())
+}
+
+pub fn scalar_to_string(x: f64) -> String {
+    format_number(x, display_options().format)
+}
+
+// `format` with no argument restores the default, as in MATLAB.
+fn handle_format(args: &str) -> Result<Output, String> {
+    let name = if args.is_empty() { "short" } else { args };
+    let format = NumberFormat::parse(name).ok_or_else(|| {
+        let names: Vec<&str> = NumberFormat::ALL.iter().map(|f| f.name()).collect();
+        format!("Unknown format '{}'. Use one of: {}.", name, names.join(", "))
+    })?;
+    settings(|settings| settings.display.format = format);
+    Ok(Output::message(format!("Format set to {}.", format.name())))
+}
+
+// `disp` shows the display settings and `disp maxrows=<n> maxcols=<n>` changes them.
+fn handle_disp_options(args: &str) -> Result<Output, String> {
+    let mut options = display_options();
+    for option in args.split_whitespace() {
+        let parsed = option.split_once('=').and_then(|(key, value)| {
+            let value = value.parse::<usize>().ok().filter(|&n| n > 0)?;
+            match key {
+                "maxrows" => options.max_rows = value,
+                "maxcols" => options.max_cols = value,
+                _ => return None,
+            }
+            Some(())
+        });
+        if parsed.is_none() {
+            return Err(format!("Invalid display option: {}", option));
+        }
+    }
+    settings(|settings| settings.display = options);
+    Ok(Output::message(format!(
+        "format {}, maxrows {}, maxcols {}",
+        options.format.name(),
+        options.max_rows,
+        options.max_cols
+    )))
+}
+
+// `latex(A)`, `markdown(A)` and `html(A)` use the active format. Further arguments select
+// rational entries (`rat`) and, for LaTeX, the brackets (`bmatrix` or `pmatrix`).
+fn handle_markup(
+    name: &str,
+    args: &[&str],
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Output, String> {
+    let (arg, options) = args
+        .split_first()
+        .ok_or_else(|| format!("{} expects a matrix.", name))?;
+    let matrix = resolve_matrix(arg, matrices)?;
+    let mut format = display_options().format;
+    let mut brackets = LatexBrackets::default();
+    for option in options {
+        match (name, *option) {
+            (_, "rat") => format = NumberFormat::Rat,
+            ("latex", "bmatrix") => brackets = LatexBrackets::Square,
+            ("latex", "pmatrix") => brackets = LatexBrackets::Round,
+            _ => return Err(format!("Unknown {} option: {}", name, option)),
+        }
+    }
+    let text = match name {
+        "latex" => matrix_to_latex(&matrix, format, brackets),
+        "markdown" => matrix_to_markdown(&matrix, format),
+        _ => matrix_to_html(&matrix, format),
+    };
+    Ok(Output::message(text))
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::None => String::new(),
+        Value::Scalar(x) => scalar_to_string(*x),
+        Value::Vector(v) => matrix_to_string(&MatrixDouble::from_slice(v.size(), 1, v.data())),
+        Value::Matrix(m) => matrix_to_string(m),
+        Value::Lu { lu, .. } => matrix_to_string(lu),
+        Value::Plot(plot) => render_text(plot, TEXT_WIDTH, TEXT_HEIGHT),
+    }
+}
+
+// Splits `name = expression` at the first `=` outside brackets, so that options such as
+// `plot(x, y, title=...)` are not taken for assignments.
+fn split_assignment(command: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (index, c) in command.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            '=' if depth == 0 => return Some((&command[..index], &command[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Runs a single command, returning its output on success and the error message on failure.
+pub fn evaluate_command(
+    command: &str,
+    matrices: &mut HashMap<String, MatrixDouble>,
+) -> Result<Output, String> {
+    let trimmed_command = command.trim();
+
+    if let Some(args) = trimmed_command.strip_prefix("load ") {
+        return handle_load(args, matrices).map(Output::message);
+    } else if let Some(args) = trimmed_command.strip_prefix("save ") {
+        return handle_save(args, matrices).map(Output::message);
+    } else if trimmed_command == "format" || trimmed_command.starts_with("format ") {
+        return handle_format(trimmed_command[6..].trim());
+    } else if trimmed_command == "disp" || trimmed_command.starts_with("disp ") {
+        return handle_disp_options(trimmed_command[4..].trim());
+    }
+
+    if let Some((var, expression)) = split_assignment(trimmed_command) {
+        let var = var.trim();
+        if !is_identifier(var) {
+            return Err(format!("Invalid variable name: {}", var));
+        }
+        let output = evaluate_expression(expression, matrices);
+        // Whatever a cancelled computation produced, it must not reach the workspace.
+        if cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        let output = output?;
+        match value_to_matrix(output.value) {
+            Some(matrix) => {
+                matrices.insert(var.to_string(), matrix.clone());
+                Ok(Output::new(
+                    format!("Matrix {} defined.", var),
+                    Value::Matrix(matrix),
+                ))
+            }
+            None => Err(format!("{} does not produce a matrix.", expression.trim())),
+        }
+    } else {
+        let output = evaluate_expression(trimmed_command, matrices);
+        if cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        output
+    }
+}
+
+/// Evaluates a command that does not change the workspace.
+pub fn evaluate_expression(
+    command: &str,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<Output, String> {
+    let trimmed_command = command.trim();
+
+    // Try to evaluate the expression first
+    if let Ok(result) = eval_str(trimmed_command) {
+        return Ok(Output::new(
+            format!("Result: {}", scalar_to_string(result)),
+            Value::Scalar(result),
+        ));
+    }
+
+    if let Some((a_var, operator, b_var)) = split_operator(trimmed_command) {
+        return match operator {
+            '^' => handle_matrix_power(a_var.trim(), b_var.trim(), matrices),
+            _ => handle_matrix_operations(operator, a_var.trim(), b_var.trim(), matrices),
+        };
+    }
+    if let Some(inner) = parenthesized(trimmed_command) {
+        return evaluate_expression(inner, matrices);
+    }
+
+    if let Some(literal) = bracketed(trimmed_command) {
+        match parse_matrix(literal, matrices) {
+            Ok(matrix) => Ok(Output::new(
+                format!("Result:\n{}", matrix_to_string(&matrix)),
+                Value::Matrix(matrix),
+            )),
+            Err(err) => Err(format!("Error in matrix literal at {}", err)),
+        }
+    } else if let Some(("disp", [arg])) = parse_call(trimmed_command)
+        .as_ref()
+        .map(|(name, args)| (*name, args.as_slice()))
+    {
+        let output = evaluate_expression(arg, matrices)?;
+        Ok(Output::new(value_to_string(&output.value), output.value))
+    } else if let Some(result) = parse_call(trimmed_command)
+        .filter(|(name, _)| ["latex", "markdown", "html"].contains(name))
+        .map(|(name, args)| handle_markup(name, &args, matrices))
+    {
+        result
+    } else if let Some(result) = parse_call(trimmed_command)
+        .and_then(|(name, args)| handle_constructor(name, &args, matrices))
+    {
+        result
+    } else if let Some(result) =
+        parse_call(trimmed_command).and_then(|(name, args)| handle_plot(name, &args, matrices))
+    {
+        result
+    } else if trimmed_command.starts_with("powm(") && trimmed_command.ends_with(')') {
+        if let Some((var, exponent)) = trimmed_command[5..trimmed_command.len() - 1].split_once(',')
+        {
+            handle_matrix_power(var.trim(), exponent.trim(), matrices)
+        } else {
+            Err("Invalid powm command format.".to_string())
+        }
+    } else if let Some((function, var)) = ["expm", "logm", "sqrtm"].iter().find_map(|name| {
+        trimmed_command
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('('))
+            .and_then(|rest| rest.strip_suffix(')'))
+            .map(|var| (*name, var.trim()))
+    }) {
+        handle_matrix_function(function, var, matrices)
+    } else if trimmed_command.starts_with("inv(") && trimmed_command.ends_with(")") {
+        let var = &trimmed_command[4..trimmed_command.len() - 1];
+        let matrix = resolve_matrix(var, matrices)?;
+        if matrix.nrows() == matrix.ncols() {
+            let lu = LU::new(&matrix).map_err(|err| format!("Error: {}", err))?;
+            if lu.is_singular() {
+                return Err("Matrix is singular".to_string());
+            }
+            let mut inv_matrix = MatrixDouble::new(matrix.nrows(), matrix.ncols());
+            lu.inverse(&mut inv_matrix)
+                .map_err(|err| format!("Error: {}", err))?;
+            Ok(Output::new(
+                format!(
+                    "Inverse of matrix {}:\n{}",
+                    var,
+                    matrix_to_string(&inv_matrix)
+                ),
+                Value::Matrix(inv_matrix),
+            ))
+        } else {
+            Err(format!("Matrix {} is not square and cannot be inverted.", var))
+        }
+    } else if trimmed_command.starts_with("det(") && trimmed_command.ends_with(")") {
+        let var = &trimmed_command[4..trimmed_command.len() - 1];
+        let matrix = resolve_matrix(var, matrices)?;
+        if matrix.nrows() != matrix.ncols() {
+            return Err(format!("Matrix {} is not square and has no determinant.", var));
+        }
+        let lu = LU::new(&matrix).map_err(|err| format!("Error: {}", err))?;
+        let det = lu.det();
+        Ok(Output::new(
+            format!("Determinant of matrix {}: {}", var, scalar_to_string(det)),
+            Value::Scalar(det),
+        ))
+    } else if trimmed_command.starts_with("solve(") && trimmed_command.ends_with(")") {
+        if let Some((a_var, b_var)) = trimmed_command[6..trimmed_command.len() - 1].split_once(',')
+        {
+            let a_var = a_var.trim();
+            let b_var = b_var.trim();
+            let matrix = resolve_matrix(a_var, matrices)?;
+            let vector = resolve_matrix(b_var, matrices)
+                .map_err(|_| format!("Vector {} is not defined.", b_var))?;
+            if matrix.nrows() != matrix.ncols() {
+                return Err(format!(
+                    "Matrix {} is not square, so the system has no unique solution.",
+                    a_var
+                ));
+            }
+            if vector.ncols() == 1 && vector.nrows() == matrix.nrows() {
+                let lu = LU::new(&matrix).map_err(|err| format!("Error: {}", err))?;
+                if lu.is_singular() {
+                    return Err("Matrix is singular".to_string());
+                }
+                let mut x = VectorDouble::new(vector.nrows());
+                let b = VectorDouble::from_slice(vector.data());
+                lu.solve(&b, &mut x);
+                Ok(Output::new(
+                    format!(
+                        "Solution vector x:\n{}",
+                        matrix_to_string(&MatrixDouble::from_slice(x.size(), 1, x.data()))
+                    ),
+                    Value::Vector(x),
+                ))
+            } else {
+                Err(format!(
+                    "Vector {} is not a valid vector or does not match matrix {} dimensions.",
+                    b_var, a_var
+                ))
+            }
+        } else {
+            Err("Invalid solve command format.".to_string())
+        }
+    } else if trimmed_command.starts_with("lu_decomposition(") && trimmed_command.ends_with(")") {
+        let var = &trimmed_command[17..trimmed_command.len() - 1];
+        let matrix = resolve_matrix(var, matrices)?;
+        if matrix.nrows() != matrix.ncols() {
+            return Err(format!("Matrix {} is not square and cannot be factored.", var));
+        }
+        let lu = LU::new(&matrix).map_err(|err| format!("Error: {}", err))?;
+        let mut lu_matrix = MatrixDouble::new(matrix.nrows(), matrix.ncols());
+        lu.lu_decomposition(&matrix, &mut lu_matrix);
+        Ok(Output::new(
+            format!(
+                "LU decomposition of matrix {}:\n{}",
+                var,
+                matrix_to_string(&lu_matrix)
+            ),
+            Value::Lu {
+                lu: lu_matrix,
+                pivots: lu.pivots(),
+            },
+        ))
+    } else if let Some(matrix) = matrices.get(trimmed_command) {
+        Ok(Output::new(
+            format!("Matrix {}:\n{}", trimmed_command, matrix_to_string(matrix)),
+            Value::Matrix(matrix.clone()),
+        ))
+    } else {
+        Err(format!("Unknown command: {}", trimmed_command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot::PlotKind;
+
+    fn workspace() -> HashMap<String, MatrixDouble> {
+        let diag = |x: f64| MatrixDouble::from_diag(&[x, x]);
+        HashMap::from([
+            ("A".to_string(), diag(1.0)),
+            ("B".to_string(), diag(2.0)),
+            ("C".to_string(), diag(3.0)),
+        ])
+    }
+
+    fn evaluate(expression: &str) -> MatrixDouble {
+        let output = evaluate_expression(expression, &workspace()).unwrap();
+        value_to_matrix(output.value).unwrap()
+    }
+
+    fn diag(x: f64) -> MatrixDouble {
+        MatrixDouble::from_diag(&[x, x])
+    }
+
+    #[test]
+    fn chains_associate_to_the_left() {
+        assert_eq!(evaluate("A - B - C"), diag(-4.0));
+        assert_eq!(evaluate("A - B + C"), diag(2.0));
+        assert_eq!(evaluate("C - B - A - A"), diag(-1.0));
+        assert_eq!(evaluate("A * B * C"), diag(6.0));
+    }
+
+    #[test]
+    fn powers_bind_tighter_than_products_and_sums() {
+        assert_eq!(evaluate("A + B^2"), diag(5.0));
+        assert_eq!(evaluate("A*B^2"), diag(4.0));
+        assert_eq!(evaluate("B^2*C"), diag(12.0));
+        assert_eq!(evaluate("A + B*C"), diag(7.0));
+        assert_eq!(evaluate("A*B + C"), diag(5.0));
+        assert_eq!(evaluate("(A + B)^2"), diag(9.0));
+        assert_eq!(evaluate("B^-1"), diag(0.5));
+        assert_eq!(evaluate("inv(B) + A"), diag(1.5));
+    }
+
+    #[test]
+    fn signs_inside_brackets_and_numbers_are_not_operators() {
+        let expected = MatrixDouble::from_slice(1, 2, &[-2.0, -6.0]);
+        assert_eq!(evaluate("[1 -2] - [3 4]"), expected);
+        assert_eq!(evaluate("[1 -2]-[3 4]"), expected);
+        assert_eq!(
+            evaluate("[1e-1 2] + [1 1]"),
+            MatrixDouble::from_slice(1, 2, &[1.1, 3.0])
+        );
+        assert_eq!(
+            evaluate("[-1; 2]"),
+            MatrixDouble::from_slice(2, 1, &[-1.0, 2.0])
+        );
+    }
+
+    #[test]
+    fn singular_systems_are_errors() {
+        let mut matrices = workspace();
+        for command in [
+            "inv([1 2; 2 4])",
+            "inv([0.1 0.3; 0.2 0.6])",
+            "solve([1 2; 2 4], [1; 2])",
+            "inv([1 2; 2 4]) + A",
+        ] {
+            assert_eq!(
+                evaluate_command(command, &mut matrices).unwrap_err(),
+                "Matrix is singular"
+            );
+        }
+        assert_eq!(
+            evaluate("inv([2 0; 0 4])"),
+            MatrixDouble::from_diag(&[0.5, 0.25])
+        );
+    }
+
+    #[test]
+    fn oversized_constructors_are_errors() {
+        let mut matrices = workspace();
+        for command in [
+            "zeros(1e10)",
+            "ones(4294967296, 4294967296)",
+            "eye(1e300)",
+            "rand(1e5, 1e5)",
+            "randn(1e9)",
+            "magic(1e6)",
+            "hilb(1e20)",
+            "linspace(0, 1, 1e12)",
+        ] {
+            let error = evaluate_command(command, &mut matrices).unwrap_err();
+            assert!(error.contains("too large"), "{}: {}", command, error);
+        }
+        assert_eq!(evaluate("zeros(2, 3)"), MatrixDouble::zeros(2, 3));
+    }
+
+    #[test]
+    fn assignment_stores_the_whole_expression() {
+        let mut matrices = workspace();
+        evaluate_command("D = A - B - C", &mut matrices).unwrap();
+        assert_eq!(matrices["D"], diag(-4.0));
+    }
+
+    #[test]
+    fn load_options() {
+        let flags = ["header", "delimiter=tab", "comment=percent"];
+        let options = parse_io_options("data.csv", &flags).unwrap();
+        assert!(options.skip_header);
+        assert_eq!(options.delimiter, Some('\t'));
+        assert_eq!(options.comment, Some('%'));
+        let options = parse_io_options("data.txt", &["comment=none"]).unwrap();
+        assert_eq!((options.delimiter, options.comment), (None, None));
+        assert!(parse_io_options("data.csv", &["comment=ab"]).is_err());
+    }
+
+    #[test]
+    fn plot_arguments() {
+        let mut matrices = workspace();
+        evaluate_command("x = [1 2 3]", &mut matrices).unwrap();
+        evaluate_command("Y = [1 10; 2 20; 3 30]", &mut matrices).unwrap();
+        let output = evaluate_command(
+            "plot(x, Y, title=Growth, ylabel=size, legend=small|large, logy)",
+            &mut matrices,
+        )
+        .unwrap();
+        let Value::Plot(plot) = output.value else {
+            panic!("plot gave {:?}", output.value);
+        };
+        assert_eq!(plot.kind, PlotKind::Line);
+        assert_eq!(plot.title, "Growth");
+        assert_eq!(plot.y_label, "size");
+        assert_eq!((plot.log_x, plot.log_y), (false, true));
+        let names: Vec<&str> = plot.series.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["small", "large"]);
+        assert_eq!(plot.series[1].y, [10.0, 20.0, 30.0]);
+
+        let output = evaluate_command("hist(x, 3, loglog)", &mut matrices).unwrap();
+        let Value::Plot(plot) = output.value else {
+            panic!("hist gave {:?}", output.value);
+        };
+        assert_eq!(plot.series[0].y, [1.0, 1.0, 1.0]);
+        assert_eq!((plot.log_x, plot.log_y), (true, true));
+    }
+
+    #[test]
+    fn plot_argument_errors() {
+        let mut matrices = workspace();
+        evaluate_command("x = [1 2 3]", &mut matrices).unwrap();
+        for (command, error) in [
+            ("plot(x, color=red)", "Unknown plot option: color"),
+            ("plot(x, legend=a|b)", "legend gives 2 names for 1 series."),
+            ("plot(x, [1 2])", "x and [1 2] have different lengths."),
+            ("scatter(x)", "Invalid arguments. Usage: scatter(x, y)"),
+            ("spy(A, B)", "Invalid arguments. Usage: spy(A)"),
+            ("hist(x, 0)", "hist needs at least one bin."),
+            ("plot(nothing)", "Matrix nothing is not defined."),
+        ] {
+            assert_eq!(
+                evaluate_command(command, &mut matrices).unwrap_err(),
+                error,
+                "{}",
+                command
+            );
+        }
+    }
+}
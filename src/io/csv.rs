@@ -0,0 +1,271 @@
+use super::Error;
+use crate::math_utilities::MatrixDouble;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    // `None` splits fields on any run of whitespace.
+    pub delimiter: Option<char>,
+    pub skip_header: bool,
+    pub comment: Option<char>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: Some(','),
+            skip_header: false,
+            comment: Some('#'),
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn whitespace() -> CsvOptions {
+        CsvOptions {
+            delimiter: None,
+            ..CsvOptions::default()
+        }
+    }
+
+    /// Picks the delimiter from the file extension: `.csv` uses commas, `.tsv` tabs, anything else whitespace.
+    pub fn for_path(path: &Path) -> CsvOptions {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("csv") => CsvOptions::default(),
+            Some("tsv") => CsvOptions {
+                delimiter: Some('\t'),
+                ..CsvOptions::default()
+            },
+            _ => CsvOptions::whitespace(),
+        }
+    }
+}
+
+// Returns each field together with its 1-based starting column.
+fn split_fields(line: &str, delimiter: Option<char>) -> Vec<(usize, &str)> {
+    let mut fields = Vec::new();
+    match delimiter {
+        Some(delimiter) => {
+            let mut start = 0;
+            for (i, c) in line.char_indices() {
+                if c == delimiter {
+                    fields.push((start, &line[start..i]));
+                    start = i + c.len_utf8();
+                }
+            }
+            fields.push((start, &line[start..]));
+        }
+        None => {
+            let mut start = None;
+            for (i, c) in line.char_indices() {
+                match (c.is_whitespace(), start) {
+                    (true, Some(s)) => {
+                        fields.push((s, &line[s..i]));
+                        start = None;
+                    }
+                    (false, None) => start = Some(i),
+                    _ => {}
+                }
+            }
+            if let Some(s) = start {
+                fields.push((s, &line[s..]));
+            }
+        }
+    }
+
+    fields
+        .into_iter()
+        .map(|(offset, field)| {
+            let leading = field.len() - field.trim_start().len();
+            (line[..offset + leading].chars().count() + 1, field.trim())
+        })
+        .collect()
+}
+
+fn parse_value(field: &str) -> Option<f64> {
+    let field = field
+        .strip_prefix('"')
+        .and_then(|f| f.strip_suffix('"'))
+        .unwrap_or(field)
+        .trim();
+    if field.eq_ignore_ascii_case("na") {
+        return Some(f64::NAN);
+    }
+    field.parse::<f64>().ok()
+}
+
+pub fn parse_csv(text: &str, options: &CsvOptions) -> Result<MatrixDouble, Error> {
+    let mut data = Vec::new();
+    let mut nrows = 0;
+    let mut ncols = None;
+    let mut header_skipped = !options.skip_header;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(comment) = options.comment {
+            if trimmed.starts_with(comment) {
+                continue;
+            }
+        }
+        if !header_skipped {
+            header_skipped = true;
+            continue;
+        }
+
+        let fields = split_fields(line, options.delimiter);
+        match ncols {
+            None => ncols = Some(fields.len()),
+            Some(expected) if expected != fields.len() => {
+                return Err(Error::parse(
+                    line_number,
+                    1,
+                    format!("expected {} columns but found {}", expected, fields.len()),
+                ));
+            }
+            _ => {}
+        }
+
+        for (column, field) in fields {
+            if field.is_empty() {
+                return Err(Error::parse(line_number, column, "empty field"));
+            }
+            match parse_value(field) {
+                Some(value) => data.push(value),
+                None => {
+                    return Err(Error::parse(
+                        line_number,
+                        column,
+                        format!("invalid number '{}'", field),
+                    ))
+                }
+            }
+        }
+        nrows += 1;
+    }
+
+    match ncols {
+        Some(ncols) => Ok(MatrixDouble::from_slice(nrows, ncols, &data)),
+        None => Err(Error::parse(1, 1, "no data rows found")),
+    }
+}
+
+pub fn read_csv<P: AsRef<Path>>(path: P, options: &CsvOptions) -> Result<MatrixDouble, Error> {
+    let text = fs::read_to_string(path)?;
+    parse_csv(&text, options)
+}
+
+pub fn format_csv(matrix: &MatrixDouble, options: &CsvOptions) -> String {
+    let delimiter = options.delimiter.unwrap_or(' ').to_string();
+    let mut result = String::new();
+    for i in 0..matrix.nrows() {
+        let row: Vec<String> = matrix[i].iter().map(|x| format!("{}", x)).collect();
+        result.push_str(&row.join(&delimiter));
+        result.push('\n');
+    }
+    result
+}
+
+pub fn write_csv<P: AsRef<Path>>(
+    path: P,
+    matrix: &MatrixDouble,
+    options: &CsvOptions,
+) -> Result<(), Error> {
+    fs::write(path, format_csv(matrix, options))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str, options: &CsvOptions) -> (usize, usize, String) {
+        match parse_csv(text, options) {
+            Err(Error::Parse {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_field() {
+        let options = CsvOptions::default();
+        assert_eq!(
+            parse_error("1,2\n\n3, x\n", &options),
+            (3, 4, "invalid number 'x'".to_string())
+        );
+        assert_eq!(
+            parse_error("1,2\n3,,4\n", &options),
+            (2, 1, "expected 2 columns but found 3".to_string())
+        );
+        assert_eq!(
+            parse_error("1,,2\n", &options),
+            (1, 3, "empty field".to_string())
+        );
+        assert_eq!(
+            parse_error("# only a comment\n", &options),
+            (1, 1, "no data rows found".to_string())
+        );
+        // Columns count characters, not bytes: the no-break space takes two bytes.
+        assert_eq!(
+            parse_error("1 2\n3\u{a0}x\n", &CsvOptions::whitespace()),
+            (2, 3, "invalid number 'x'".to_string())
+        );
+    }
+
+    #[test]
+    fn delimiters_headers_and_comments() {
+        let expected = MatrixDouble::from_slice(2, 2, &[1.0, 2.0, 3.0, 4.0]);
+        let tsv = CsvOptions::for_path(Path::new("data.TSV"));
+        assert_eq!(parse_csv("1\t2\n3\t4\n", &tsv).unwrap(), expected);
+        let whitespace = CsvOptions::for_path(Path::new("data.txt"));
+        assert_eq!(
+            parse_csv("  1   2\n3\t 4  \n", &whitespace).unwrap(),
+            expected
+        );
+
+        let header = CsvOptions {
+            skip_header: true,
+            ..CsvOptions::default()
+        };
+        let text = "# exported\n\na,b\n1,\"2\"\n3,4\n";
+        assert_eq!(parse_csv(text, &header).unwrap(), expected);
+        assert!(parse_csv(text, &CsvOptions::default()).is_err());
+
+        let no_comments = CsvOptions {
+            comment: None,
+            ..CsvOptions::default()
+        };
+        assert!(parse_csv("# 1,2\n3,4\n", &no_comments).is_err());
+        let semicolons = CsvOptions {
+            delimiter: Some(';'),
+            comment: Some('%'),
+            ..CsvOptions::default()
+        };
+        assert_eq!(
+            parse_csv("% a comment\n1;2\n3;4\n", &semicolons).unwrap(),
+            expected
+        );
+        assert!(parse_csv("NA,1\n", &CsvOptions::default()).unwrap()[0][0].is_nan());
+    }
+
+    #[test]
+    fn formatted_matrices_parse_back() {
+        let matrix = MatrixDouble::from_slice(2, 2, &[0.1, -2.5e-10, 1e300, 3.0]);
+        for options in [CsvOptions::default(), CsvOptions::whitespace()] {
+            let text = format_csv(&matrix, &options);
+            assert_eq!(parse_csv(&text, &options).unwrap(), matrix);
+        }
+    }
+}
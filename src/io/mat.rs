@@ -0,0 +1,731 @@
+use super::Error;
+use crate::interpreter::is_identifier;
+use crate::math_utilities::{MatrixDouble, MAX_ELEMENTS};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const HEADER_LEN: usize = 128;
+
+const MI_INT8: u32 = 1;
+const MI_UINT8: u32 = 2;
+const MI_INT16: u32 = 3;
+const MI_UINT16: u32 = 4;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_SINGLE: u32 = 7;
+const MI_DOUBLE: u32 = 9;
+const MI_INT64: u32 = 12;
+const MI_UINT64: u32 = 13;
+const MI_MATRIX: u32 = 14;
+const MI_COMPRESSED: u32 = 15;
+
+const FLAG_COMPLEX: u32 = 0x0800;
+const FLAG_LOGICAL: u32 = 0x0200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatClass {
+    Double,
+    Single,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+}
+
+impl MatClass {
+    fn from_code(code: u32) -> Option<MatClass> {
+        match code {
+            6 => Some(MatClass::Double),
+            7 => Some(MatClass::Single),
+            8 => Some(MatClass::Int8),
+            9 => Some(MatClass::UInt8),
+            10 => Some(MatClass::Int16),
+            11 => Some(MatClass::UInt16),
+            12 => Some(MatClass::Int32),
+            13 => Some(MatClass::UInt32),
+            14 => Some(MatClass::Int64),
+            15 => Some(MatClass::UInt64),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> u32 {
+        match self {
+            MatClass::Double => 6,
+            MatClass::Single => 7,
+            MatClass::Int8 => 8,
+            MatClass::UInt8 => 9,
+            MatClass::Int16 => 10,
+            MatClass::UInt16 => 11,
+            MatClass::Int32 => 12,
+            MatClass::UInt32 => 13,
+            MatClass::Int64 => 14,
+            MatClass::UInt64 => 15,
+        }
+    }
+
+    // The element type used to store data of this class.
+    fn data_type(self) -> u32 {
+        match self {
+            MatClass::Double => MI_DOUBLE,
+            MatClass::Single => MI_SINGLE,
+            MatClass::Int8 => MI_INT8,
+            MatClass::UInt8 => MI_UINT8,
+            MatClass::Int16 => MI_INT16,
+            MatClass::UInt16 => MI_UINT16,
+            MatClass::Int32 => MI_INT32,
+            MatClass::UInt32 => MI_UINT32,
+            MatClass::Int64 => MI_INT64,
+            MatClass::UInt64 => MI_UINT64,
+        }
+    }
+}
+
+fn class_name(code: u32) -> &'static str {
+    match code {
+        1 => "cell",
+        2 => "struct",
+        3 => "object",
+        4 => "char",
+        16 => "function handle",
+        17 => "opaque",
+        _ => "unknown",
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatData {
+    Dense {
+        re: MatrixDouble,
+        im: Option<MatrixDouble>,
+    },
+    // Compressed sparse column storage, exactly as MATLAB keeps it.
+    Sparse {
+        rows: usize,
+        cols: usize,
+        row_indices: Vec<usize>,
+        col_ptrs: Vec<usize>,
+        re: Vec<f64>,
+        im: Option<Vec<f64>>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatVariable {
+    pub name: String,
+    pub class: MatClass,
+    pub logical: bool,
+    pub data: MatData,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MatFile {
+    pub variables: Vec<MatVariable>,
+    pub warnings: Vec<String>,
+}
+
+impl MatVariable {
+    pub fn from_matrix(name: &str, matrix: &MatrixDouble) -> MatVariable {
+        MatVariable {
+            name: name.to_string(),
+            class: MatClass::Double,
+            logical: false,
+            data: MatData::Dense {
+                re: matrix.clone(),
+                im: None,
+            },
+        }
+    }
+
+    pub fn is_complex(&self) -> bool {
+        match &self.data {
+            MatData::Dense { im, .. } => im.is_some(),
+            MatData::Sparse { im, .. } => im.is_some(),
+        }
+    }
+
+    /// Returns the real part as a dense matrix, expanding sparse storage. Fails when the
+    /// matrix has more than `MAX_ELEMENTS` elements.
+    pub fn real_part(&self) -> Result<MatrixDouble, Error> {
+        match &self.data {
+            MatData::Dense { re, .. } => Ok(re.clone()),
+            MatData::Sparse {
+                rows,
+                cols,
+                row_indices,
+                col_ptrs,
+                re,
+                ..
+            } => {
+                if rows
+                    .checked_mul(*cols)
+                    .is_none_or(|count| count > MAX_ELEMENTS)
+                {
+                    return Err(Error::Format(format!(
+                        "{}: a {}x{} matrix is too large to load densely",
+                        self.name, rows, cols
+                    )));
+                }
+                let mut result = MatrixDouble::new(*rows, *cols);
+                for j in 0..*cols {
+                    for k in col_ptrs[j]..col_ptrs[j + 1] {
+                        result[row_indices[k]][j] += re[k];
+                    }
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    pub fn to_dense(&self) -> Result<MatrixDouble, Error> {
+        if self.is_complex() {
+            return Err(Error::Format(
+                "complex matrix cannot be loaded as a real matrix".to_string(),
+            ));
+        }
+        self.real_part()
+    }
+}
+
+struct Reader {
+    big_endian: bool,
+}
+
+// Compressed sparse column storage is only safe to expand when the column pointers start at
+// zero, never decrease and stay within the row indices, and every row index is in range.
+fn check_sparse(
+    rows: usize,
+    cols: usize,
+    row_indices: &[usize],
+    col_ptrs: &[usize],
+) -> Result<(), &'static str> {
+    if cols.checked_add(1) != Some(col_ptrs.len()) {
+        return Err("expected one column pointer per column plus one");
+    }
+    if col_ptrs[0] != 0 || col_ptrs.windows(2).any(|pair| pair[0] > pair[1]) {
+        return Err("column pointers must start at 0 and never decrease");
+    }
+    let nnz = col_ptrs[cols];
+    if nnz > row_indices.len() {
+        return Err("more nonzeros than row indices");
+    }
+    if row_indices[..nnz].iter().any(|&i| i >= rows) {
+        return Err("row index out of range");
+    }
+    Ok(())
+}
+
+impl Reader {
+    fn u32_at(&self, bytes: &[u8], pos: usize) -> Result<u32, Error> {
+        let chunk: [u8; 4] = bytes
+            .get(pos..pos + 4)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| Error::Format("MAT-file is truncated".to_string()))?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(chunk)
+        } else {
+            u32::from_le_bytes(chunk)
+        })
+    }
+
+    // Reads one data element, returning its type, its payload and the offset of the next element.
+    fn element<'a>(&self, bytes: &'a [u8], pos: usize) -> Result<(u32, &'a [u8], usize), Error> {
+        let first = self.u32_at(bytes, pos)?;
+        let (data_type, start, len, next) = if first >> 16 != 0 {
+            // Small data element: type and size share the first word, data fits in the second.
+            (first & 0xffff, pos + 4, (first >> 16) as usize, pos + 8)
+        } else {
+            let len = self.u32_at(bytes, pos + 4)? as usize;
+            let padded = if first == MI_COMPRESSED {
+                len
+            } else {
+                len.div_ceil(8) * 8
+            };
+            (first, pos + 8, len, pos + 8 + padded)
+        };
+        let data = bytes
+            .get(start..start + len)
+            .ok_or_else(|| Error::Format("MAT-file is truncated".to_string()))?;
+        Ok((data_type, data, next.min(bytes.len())))
+    }
+
+    fn numbers(&self, data_type: u32, data: &[u8]) -> Result<Vec<f64>, Error> {
+        macro_rules! convert {
+            ($t:ty, $n:expr) => {
+                data.chunks_exact($n)
+                    .map(|chunk| {
+                        let chunk: [u8; $n] = chunk.try_into().unwrap();
+                        if self.big_endian {
+                            <$t>::from_be_bytes(chunk) as f64
+                        } else {
+                            <$t>::from_le_bytes(chunk) as f64
+                        }
+                    })
+                    .collect()
+            };
+        }
+
+        Ok(match data_type {
+            MI_INT8 => convert!(i8, 1),
+            MI_UINT8 => convert!(u8, 1),
+            MI_INT16 => convert!(i16, 2),
+            MI_UINT16 => convert!(u16, 2),
+            MI_INT32 => convert!(i32, 4),
+            MI_UINT32 => convert!(u32, 4),
+            MI_SINGLE => convert!(f32, 4),
+            MI_DOUBLE => convert!(f64, 8),
+            MI_INT64 => convert!(i64, 8),
+            MI_UINT64 => convert!(u64, 8),
+            other => return Err(Error::Format(format!("unsupported data type {}", other))),
+        })
+    }
+
+    fn matrix(&self, data: &[u8], file: &mut MatFile) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let (_, flags, pos) = self.element(data, 0)?;
+        let flags = self.u32_at(flags, 0)?;
+        let class_code = flags & 0xff;
+
+        let (dims_type, dims, pos) = self.element(data, pos)?;
+        let dims: Vec<usize> = self
+            .numbers(dims_type, dims)?
+            .into_iter()
+            .map(|d| d as usize)
+            .collect();
+
+        let (_, name, pos) = self.element(data, pos)?;
+        let name = String::from_utf8_lossy(name).to_string();
+
+        if !is_identifier(&name) {
+            file.warnings
+                .push(format!("skipped '{}': not a valid variable name", name));
+            return Ok(());
+        }
+        if dims.len() != 2 {
+            file.warnings.push(format!(
+                "skipped {}: {}-dimensional arrays are not supported",
+                name,
+                dims.len()
+            ));
+            return Ok(());
+        }
+        let (rows, cols) = (dims[0], dims[1]);
+        let complex = flags & FLAG_COMPLEX != 0;
+        let logical = flags & FLAG_LOGICAL != 0;
+
+        if class_code == 5 {
+            let (ir_type, ir, pos) = self.element(data, pos)?;
+            let (jc_type, jc, pos) = self.element(data, pos)?;
+            let (re_type, re, pos) = self.element(data, pos)?;
+            let row_indices: Vec<usize> = self
+                .numbers(ir_type, ir)?
+                .into_iter()
+                .map(|i| i as usize)
+                .collect();
+            let col_ptrs: Vec<usize> = self
+                .numbers(jc_type, jc)?
+                .into_iter()
+                .map(|i| i as usize)
+                .collect();
+            let nnz = col_ptrs.last().copied().unwrap_or(0);
+            check_sparse(rows, cols, &row_indices, &col_ptrs).map_err(|err| {
+                Error::Format(format!("{}: malformed sparse matrix: {}", name, err))
+            })?;
+            let mut re = if logical && re.is_empty() {
+                vec![1.0; nnz]
+            } else {
+                self.numbers(re_type, re)?
+            };
+            re.resize(nnz, 0.0);
+            let im = if complex {
+                let (im_type, im, _) = self.element(data, pos)?;
+                let mut im = self.numbers(im_type, im)?;
+                im.resize(nnz, 0.0);
+                Some(im)
+            } else {
+                None
+            };
+            file.variables.push(MatVariable {
+                name,
+                class: MatClass::Double,
+                logical,
+                data: MatData::Sparse {
+                    rows,
+                    cols,
+                    row_indices,
+                    col_ptrs,
+                    re,
+                    im,
+                },
+            });
+            return Ok(());
+        }
+
+        let class = match MatClass::from_code(class_code) {
+            Some(class) => class,
+            None => {
+                file.warnings.push(format!(
+                    "skipped {}: {} arrays are not supported",
+                    name,
+                    class_name(class_code)
+                ));
+                return Ok(());
+            }
+        };
+
+        // MATLAB stores dense data column by column.
+        let to_matrix = |values: Vec<f64>| -> Result<MatrixDouble, Error> {
+            if rows.checked_mul(cols) != Some(values.len()) {
+                return Err(Error::Format(format!(
+                    "{}: data size does not match dimensions",
+                    name
+                )));
+            }
+            let mut matrix = MatrixDouble::new(rows, cols);
+            for j in 0..cols {
+                for i in 0..rows {
+                    matrix[i][j] = values[j * rows + i];
+                }
+            }
+            Ok(matrix)
+        };
+
+        let (re_type, re, pos) = self.element(data, pos)?;
+        let re = to_matrix(self.numbers(re_type, re)?)?;
+        let im = if complex {
+            let (im_type, im, _) = self.element(data, pos)?;
+            Some(to_matrix(self.numbers(im_type, im)?)?)
+        } else {
+            None
+        };
+
+        file.variables.push(MatVariable {
+            name,
+            class,
+            logical,
+            data: MatData::Dense { re, im },
+        });
+        Ok(())
+    }
+
+    fn top_level(&self, data_type: u32, data: &[u8], file: &mut MatFile) -> Result<(), Error> {
+        match data_type {
+            MI_MATRIX => self.matrix(data, file),
+            MI_COMPRESSED => {
+                let mut inflated = Vec::new();
+                ZlibDecoder::new(data)
+                    .read_to_end(&mut inflated)
+                    .map_err(|err| Error::Format(format!("invalid compressed element: {}", err)))?;
+                let mut pos = 0;
+                while pos < inflated.len() {
+                    let (inner_type, inner, next) = self.element(&inflated, pos)?;
+                    self.top_level(inner_type, inner, file)?;
+                    pos = next;
+                }
+                Ok(())
+            }
+            other => {
+                file.warnings
+                    .push(format!("skipped top-level element of type {}", other));
+                Ok(())
+            }
+        }
+    }
+}
+
+impl MatFile {
+    pub fn parse(bytes: &[u8]) -> Result<MatFile, Error> {
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Format("not a MAT-file".to_string()));
+        }
+        let big_endian = match &bytes[126..128] {
+            b"IM" => false,
+            b"MI" => true,
+            _ => return Err(Error::Format("not a MATLAB 5.0 MAT-file".to_string())),
+        };
+
+        let reader = Reader { big_endian };
+        let mut file = MatFile::default();
+        let mut pos = HEADER_LEN;
+        while pos + 8 <= bytes.len() {
+            let (data_type, data, next) = reader.element(bytes, pos)?;
+            reader.top_level(data_type, data, &mut file)?;
+            pos = next;
+        }
+        Ok(file)
+    }
+
+    pub fn to_bytes(&self, compress: bool) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        let mut text = "MATLAB 5.0 MAT-file, Platform: Numerus".to_string();
+        text.push_str(&" ".repeat(116 - text.len()));
+        bytes.extend_from_slice(text.as_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&0x0100u16.to_le_bytes());
+        bytes.extend_from_slice(b"IM");
+
+        for variable in &self.variables {
+            let mut element = Vec::new();
+            write_element(&mut element, MI_MATRIX, &matrix_payload(variable));
+            if compress {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&element)?;
+                let compressed = encoder.finish()?;
+                write_element(&mut bytes, MI_COMPRESSED, &compressed);
+            } else {
+                bytes.extend_from_slice(&element);
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+fn write_element(out: &mut Vec<u8>, data_type: u32, data: &[u8]) {
+    out.extend_from_slice(&data_type.to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data_type != MI_COMPRESSED {
+        out.resize(out.len() + (8 - data.len() % 8) % 8, 0);
+    }
+}
+
+fn encode(class: MatClass, values: &[f64]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for &x in values {
+        match class {
+            MatClass::Double => out.extend_from_slice(&x.to_le_bytes()),
+            MatClass::Single => out.extend_from_slice(&(x as f32).to_le_bytes()),
+            MatClass::Int8 => out.extend_from_slice(&(x as i8).to_le_bytes()),
+            MatClass::UInt8 => out.extend_from_slice(&(x as u8).to_le_bytes()),
+            MatClass::Int16 => out.extend_from_slice(&(x as i16).to_le_bytes()),
+            MatClass::UInt16 => out.extend_from_slice(&(x as u16).to_le_bytes()),
+            MatClass::Int32 => out.extend_from_slice(&(x as i32).to_le_bytes()),
+            MatClass::UInt32 => out.extend_from_slice(&(x as u32).to_le_bytes()),
+            MatClass::Int64 => out.extend_from_slice(&(x as i64).to_le_bytes()),
+            MatClass::UInt64 => out.extend_from_slice(&(x as u64).to_le_bytes()),
+        }
+    }
+    out
+}
+
+fn column_major(matrix: &MatrixDouble) -> Vec<f64> {
+    let mut values = Vec::with_capacity(matrix.nrows() * matrix.ncols());
+    for j in 0..matrix.ncols() {
+        for i in 0..matrix.nrows() {
+            values.push(matrix[i][j]);
+        }
+    }
+    values
+}
+
+fn matrix_payload(variable: &MatVariable) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let (rows, cols) = match &variable.data {
+        MatData::Dense { re, .. } => (re.nrows(), re.ncols()),
+        MatData::Sparse { rows, cols, .. } => (*rows, *cols),
+    };
+
+    let mut flags = match variable.data {
+        MatData::Dense { .. } => variable.class.code(),
+        MatData::Sparse { .. } => 5,
+    };
+    if variable.is_complex() {
+        flags |= FLAG_COMPLEX;
+    }
+    if variable.logical {
+        flags |= FLAG_LOGICAL;
+    }
+    let nzmax = match &variable.data {
+        MatData::Sparse { re, .. } => re.len().max(1) as u32,
+        MatData::Dense { .. } => 0,
+    };
+    let mut flag_bytes = flags.to_le_bytes().to_vec();
+    flag_bytes.extend_from_slice(&nzmax.to_le_bytes());
+    write_element(&mut payload, MI_UINT32, &flag_bytes);
+
+    let dims = encode(MatClass::Int32, &[rows as f64, cols as f64]);
+    write_element(&mut payload, MI_INT32, &dims);
+    write_element(&mut payload, MI_INT8, variable.name.as_bytes());
+
+    match &variable.data {
+        MatData::Dense { re, im } => {
+            let class = variable.class;
+            write_element(
+                &mut payload,
+                class.data_type(),
+                &encode(class, &column_major(re)),
+            );
+            if let Some(im) = im {
+                write_element(
+                    &mut payload,
+                    class.data_type(),
+                    &encode(class, &column_major(im)),
+                );
+            }
+        }
+        MatData::Sparse {
+            row_indices,
+            col_ptrs,
+            re,
+            im,
+            ..
+        } => {
+            let to_f64 = |v: &[usize]| v.iter().map(|x| *x as f64).collect::<Vec<f64>>();
+            write_element(
+                &mut payload,
+                MI_INT32,
+                &encode(MatClass::Int32, &to_f64(row_indices)),
+            );
+            write_element(
+                &mut payload,
+                MI_INT32,
+                &encode(MatClass::Int32, &to_f64(col_ptrs)),
+            );
+            write_element(&mut payload, MI_DOUBLE, &encode(MatClass::Double, re));
+            if let Some(im) = im {
+                write_element(&mut payload, MI_DOUBLE, &encode(MatClass::Double, im));
+            }
+        }
+    }
+    payload
+}
+
+pub fn read_mat<P: AsRef<Path>>(path: P) -> Result<MatFile, Error> {
+    MatFile::parse(&fs::read(path)?)
+}
+
+pub fn write_mat<P: AsRef<Path>>(path: P, file: &MatFile, compress: bool) -> Result<(), Error> {
+    fs::write(path, file.to_bytes(compress)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Laid out the way MATLAB saves -v6 and -v7 files; see tests/data/make_mat_fixtures.py.
+    const MATLAB_V6: &[u8] = include_bytes!("../../tests/data/matlab_v6.mat");
+    const MATLAB_V7: &[u8] = include_bytes!("../../tests/data/matlab_v7.mat");
+
+    fn dense(file: &MatFile, name: &str) -> MatrixDouble {
+        let variable = file.variables.iter().find(|v| v.name == name).unwrap();
+        variable.to_dense().unwrap()
+    }
+
+    fn sparse(rows: usize, cols: usize, row_indices: Vec<usize>, col_ptrs: Vec<usize>) -> MatFile {
+        let nnz = row_indices.len();
+        MatFile {
+            variables: vec![MatVariable {
+                name: "S".to_string(),
+                class: MatClass::Double,
+                logical: false,
+                data: MatData::Sparse {
+                    rows,
+                    cols,
+                    row_indices,
+                    col_ptrs,
+                    re: vec![1.0; nnz],
+                    im: None,
+                },
+            }],
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reads_files_in_matlab_layout() {
+        for bytes in [MATLAB_V6, MATLAB_V7] {
+            let file = MatFile::parse(bytes).unwrap();
+            let names: Vec<&str> = file.variables.iter().map(|v| v.name.as_str()).collect();
+            assert_eq!(names, ["A", "S", "L", "z"]);
+            assert_eq!(
+                dense(&file, "A"),
+                MatrixDouble::from_slice(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            );
+            assert_eq!(
+                dense(&file, "S"),
+                MatrixDouble::from_slice(3, 2, &[5.0, 0.0, 0.0, 0.0, 0.0, -1.0])
+            );
+            assert_eq!(
+                dense(&file, "L"),
+                MatrixDouble::from_slice(1, 3, &[1.0, 0.0, 1.0])
+            );
+            assert!(file.variables[2].logical);
+            assert_eq!(file.variables[3].class, MatClass::Int16);
+            assert_eq!(
+                dense(&file, "z"),
+                MatrixDouble::from_slice(1, 2, &[-3.0, 7.0])
+            );
+            assert_eq!(file.warnings, ["skipped c: char arrays are not supported"]);
+        }
+    }
+
+    #[test]
+    fn written_files_read_back() {
+        let a = MatrixDouble::from_slice(2, 3, &[1.5, -2.0, f64::INFINITY, 0.0, 1e-300, 7.0]);
+        let mut complex = MatVariable::from_matrix("C", &MatrixDouble::identity(2));
+        complex.data = MatData::Dense {
+            re: MatrixDouble::identity(2),
+            im: Some(MatrixDouble::from_slice(2, 2, &[0.0, 1.0, -1.0, 0.0])),
+        };
+        let mut bytes =
+            MatVariable::from_matrix("b", &MatrixDouble::from_slice(1, 2, &[3.0, 250.0]));
+        bytes.class = MatClass::UInt8;
+        let file = MatFile {
+            variables: vec![
+                MatVariable::from_matrix("A", &a),
+                complex,
+                bytes,
+                sparse(3, 2, vec![0, 2], vec![0, 1, 2]).variables.remove(0),
+            ],
+            warnings: Vec::new(),
+        };
+        for compress in [false, true] {
+            let read = MatFile::parse(&file.to_bytes(compress).unwrap()).unwrap();
+            assert_eq!(read.variables, file.variables);
+            assert!(read.warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn malformed_sparse_matrices_are_errors() {
+        for file in [
+            sparse(3, 2, vec![0, 3], vec![0, 1, 2]),
+            sparse(3, 2, vec![0, 2], vec![0, 2, 1]),
+            sparse(3, 2, vec![0, 2], vec![1, 1, 2]),
+            sparse(3, 2, vec![0, 2], vec![0, 1, 14]),
+            sparse(3, 3, vec![0, 2], vec![0, 1, 2]),
+        ] {
+            let bytes = file.to_bytes(false).unwrap();
+            assert!(matches!(MatFile::parse(&bytes), Err(Error::Format(_))));
+        }
+    }
+
+    #[test]
+    fn invalid_names_are_skipped() {
+        let file = MatFile {
+            variables: vec![MatVariable::from_matrix(
+                "1 bad",
+                &MatrixDouble::identity(1),
+            )],
+            warnings: Vec::new(),
+        };
+        let read = MatFile::parse(&file.to_bytes(true).unwrap()).unwrap();
+        assert!(read.variables.is_empty());
+        assert_eq!(
+            read.warnings,
+            ["skipped '1 bad': not a valid variable name"]
+        );
+    }
+}
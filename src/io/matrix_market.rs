@@ -0,0 +1,569 @@
+use super::Error;
+use crate::math_utilities::{MatrixDouble, MAX_ELEMENTS};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Coordinate,
+    Array,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Real,
+    Integer,
+    Pattern,
+    Complex,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    General,
+    Symmetric,
+    SkewSymmetric,
+    Hermitian,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub row: usize,
+    pub col: usize,
+    pub re: f64,
+    pub im: f64,
+}
+
+/// Contents of a Matrix Market file. Entries are 0-based and hold only what the file stores,
+/// so symmetric matrices keep just their lower triangle until they are expanded by `to_dense`.
+/// Numerus has no sparse matrix type, so coordinate files stay sparse only here: `to_dense`
+/// and `load_matrix` (and so `load A file.mtx`) expand them, up to `MAX_ELEMENTS` elements.
+#[derive(Clone, Debug)]
+pub struct MatrixMarket {
+    pub format: Format,
+    pub field: Field,
+    pub symmetry: Symmetry,
+    pub rows: usize,
+    pub cols: usize,
+    pub entries: Vec<Entry>,
+}
+
+fn parse_header(line: &str) -> Result<(Format, Field, Symmetry), Error> {
+    let tokens: Vec<String> = line
+        .split_whitespace()
+        .map(|t| t.to_ascii_lowercase())
+        .collect();
+    if tokens.len() != 5 || tokens[0] != "%%matrixmarket" {
+        return Err(Error::parse(1, 1, "missing %%MatrixMarket header"));
+    }
+    if tokens[1] != "matrix" {
+        return Err(Error::parse(1, 1, format!("unsupported object '{}'", tokens[1])));
+    }
+
+    let format = match tokens[2].as_str() {
+        "coordinate" => Format::Coordinate,
+        "array" => Format::Array,
+        other => return Err(Error::parse(1, 1, format!("unknown format '{}'", other))),
+    };
+    let field = match tokens[3].as_str() {
+        "real" | "double" => Field::Real,
+        "integer" => Field::Integer,
+        "pattern" => Field::Pattern,
+        "complex" => Field::Complex,
+        other => return Err(Error::parse(1, 1, format!("unknown field '{}'", other))),
+    };
+    let symmetry = match tokens[4].as_str() {
+        "general" => Symmetry::General,
+        "symmetric" => Symmetry::Symmetric,
+        "skew-symmetric" => Symmetry::SkewSymmetric,
+        "hermitian" => Symmetry::Hermitian,
+        other => return Err(Error::parse(1, 1, format!("unknown symmetry '{}'", other))),
+    };
+
+    if format == Format::Array && field == Field::Pattern {
+        return Err(Error::parse(1, 1, "pattern field requires coordinate format"));
+    }
+    if symmetry == Symmetry::Hermitian && field != Field::Complex {
+        return Err(Error::parse(1, 1, "hermitian symmetry requires complex field"));
+    }
+
+    Ok((format, field, symmetry))
+}
+
+fn parse_number<T: std::str::FromStr>(token: &str, line: usize, column: usize) -> Result<T, Error> {
+    token
+        .parse::<T>()
+        .map_err(|_| Error::parse(line, column, format!("invalid number '{}'", token)))
+}
+
+// Splits a line into tokens paired with their 1-based columns.
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                tokens.push((s + 1, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s + 1, &line[s..]));
+    }
+    tokens
+}
+
+impl MatrixMarket {
+    pub fn parse(text: &str) -> Result<MatrixMarket, Error> {
+        let mut lines = text.lines().enumerate();
+        let (format, field, symmetry) = match lines.next() {
+            Some((_, line)) => parse_header(line)?,
+            None => return Err(Error::parse(1, 1, "empty file")),
+        };
+
+        let mut data_lines = lines
+            .map(|(i, line)| (i + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('%'));
+
+        let (size_line, size) = match data_lines.next() {
+            Some(line) => line,
+            None => return Err(Error::parse(1, 1, "missing size line")),
+        };
+        let size_tokens = tokenize(size);
+        let expected_sizes = if format == Format::Coordinate { 3 } else { 2 };
+        if size_tokens.len() != expected_sizes {
+            return Err(Error::parse(
+                size_line,
+                1,
+                format!("expected {} size values", expected_sizes),
+            ));
+        }
+        let rows: usize = parse_number(size_tokens[0].1, size_line, size_tokens[0].0)?;
+        let cols: usize = parse_number(size_tokens[1].1, size_line, size_tokens[1].0)?;
+        if symmetry != Symmetry::General && rows != cols {
+            return Err(Error::parse(size_line, 1, "symmetric matrices must be square"));
+        }
+        // Array files list every element, so they can never be larger than what loads densely.
+        let limit = match format {
+            Format::Coordinate => usize::MAX,
+            Format::Array => MAX_ELEMENTS,
+        };
+        if rows.checked_mul(cols).is_none_or(|count| count > limit) {
+            return Err(Error::parse(
+                size_line,
+                1,
+                format!("a {}x{} matrix is too large", rows, cols),
+            ));
+        }
+
+        let values_per_entry = match field {
+            Field::Pattern => 0,
+            Field::Complex => 2,
+            _ => 1,
+        };
+
+        let mut entries = Vec::new();
+        match format {
+            Format::Coordinate => {
+                let nnz: usize = parse_number(size_tokens[2].1, size_line, size_tokens[2].0)?;
+                for (line_number, line) in data_lines {
+                    let tokens = tokenize(line);
+                    if tokens.len() != 2 + values_per_entry {
+                        return Err(Error::parse(
+                            line_number,
+                            1,
+                            format!("expected {} values per entry", 2 + values_per_entry),
+                        ));
+                    }
+                    let row: usize = parse_number(tokens[0].1, line_number, tokens[0].0)?;
+                    let col: usize = parse_number(tokens[1].1, line_number, tokens[1].0)?;
+                    if row == 0 || row > rows || col == 0 || col > cols {
+                        return Err(Error::parse(line_number, tokens[0].0, "index out of range"));
+                    }
+                    let (re, im) = Self::parse_value(field, &tokens[2..], line_number)?;
+                    entries.push(Entry {
+                        row: row - 1,
+                        col: col - 1,
+                        re,
+                        im,
+                    });
+                }
+                if entries.len() != nnz {
+                    return Err(Error::parse(
+                        size_line,
+                        1,
+                        format!("expected {} entries but found {}", nnz, entries.len()),
+                    ));
+                }
+            }
+            Format::Array => {
+                // Array data is stored column by column, with only the lower triangle for symmetric matrices.
+                let first_row = |j: usize| match symmetry {
+                    Symmetry::General => 0,
+                    Symmetry::Symmetric | Symmetry::Hermitian => j,
+                    Symmetry::SkewSymmetric => j + 1,
+                };
+                // Symmetric matrices are square, and rows * cols is bounded by the check above.
+                let expected = match symmetry {
+                    Symmetry::General => rows * cols,
+                    Symmetry::Symmetric | Symmetry::Hermitian => rows * (rows + 1) / 2,
+                    Symmetry::SkewSymmetric => rows * rows.saturating_sub(1) / 2,
+                };
+                // Positions are stepped through as the values are read, never listed up front.
+                let (mut row, mut col) = (first_row(0), 0);
+                for (line_number, line) in data_lines {
+                    let tokens = tokenize(line);
+                    if tokens.len() != values_per_entry {
+                        return Err(Error::parse(
+                            line_number,
+                            1,
+                            format!("expected {} values per entry", values_per_entry),
+                        ));
+                    }
+                    if entries.len() == expected {
+                        return Err(Error::parse(line_number, 1, "too many entries"));
+                    }
+                    while row >= rows {
+                        col += 1;
+                        row = first_row(col);
+                    }
+                    let (re, im) = Self::parse_value(field, &tokens, line_number)?;
+                    entries.push(Entry { row, col, re, im });
+                    row += 1;
+                }
+                if entries.len() < expected {
+                    return Err(Error::parse(size_line, 1, "too few entries"));
+                }
+            }
+        }
+
+        Ok(MatrixMarket {
+            format,
+            field,
+            symmetry,
+            rows,
+            cols,
+            entries,
+        })
+    }
+
+    fn parse_value(field: Field, tokens: &[(usize, &str)], line: usize) -> Result<(f64, f64), Error> {
+        match field {
+            Field::Pattern => Ok((1.0, 0.0)),
+            Field::Integer => {
+                let value: i64 = parse_number(tokens[0].1, line, tokens[0].0)?;
+                Ok((value as f64, 0.0))
+            }
+            Field::Real => Ok((parse_number(tokens[0].1, line, tokens[0].0)?, 0.0)),
+            Field::Complex => Ok((
+                parse_number(tokens[0].1, line, tokens[0].0)?,
+                parse_number(tokens[1].1, line, tokens[1].0)?,
+            )),
+        }
+    }
+
+    /// Builds a real general file from a dense matrix. Coordinate format stores only the nonzeros.
+    pub fn from_dense(matrix: &MatrixDouble, format: Format) -> MatrixMarket {
+        let mut entries = Vec::new();
+        for j in 0..matrix.ncols() {
+            for i in 0..matrix.nrows() {
+                let value = matrix[i][j];
+                if format == Format::Array || value != 0.0 {
+                    entries.push(Entry {
+                        row: i,
+                        col: j,
+                        re: value,
+                        im: 0.0,
+                    });
+                }
+            }
+        }
+
+        MatrixMarket {
+            format,
+            field: Field::Real,
+            symmetry: Symmetry::General,
+            rows: matrix.nrows(),
+            cols: matrix.ncols(),
+            entries,
+        }
+    }
+
+    /// Expands the stored entries into dense real and imaginary parts. Fails when the matrix
+    /// has more than `MAX_ELEMENTS` elements.
+    pub fn to_dense_complex(&self) -> Result<(MatrixDouble, MatrixDouble), Error> {
+        if self
+            .rows
+            .checked_mul(self.cols)
+            .is_none_or(|count| count > MAX_ELEMENTS)
+        {
+            return Err(Error::Format(format!(
+                "a {}x{} matrix is too large to load densely",
+                self.rows, self.cols
+            )));
+        }
+        let mut re = MatrixDouble::new(self.rows, self.cols);
+        let mut im = MatrixDouble::new(self.rows, self.cols);
+        for entry in &self.entries {
+            re[entry.row][entry.col] += entry.re;
+            im[entry.row][entry.col] += entry.im;
+            if entry.row != entry.col {
+                let (mirror_re, mirror_im) = match self.symmetry {
+                    Symmetry::General => continue,
+                    Symmetry::Symmetric => (entry.re, entry.im),
+                    Symmetry::SkewSymmetric => (-entry.re, -entry.im),
+                    Symmetry::Hermitian => (entry.re, -entry.im),
+                };
+                re[entry.col][entry.row] += mirror_re;
+                im[entry.col][entry.row] += mirror_im;
+            }
+        }
+        Ok((re, im))
+    }
+
+    pub fn to_dense(&self) -> Result<MatrixDouble, Error> {
+        if self.field == Field::Complex && self.entries.iter().any(|e| e.im != 0.0) {
+            return Err(Error::Format(
+                "complex matrix cannot be loaded as a real matrix".to_string(),
+            ));
+        }
+        Ok(self.to_dense_complex()?.0)
+    }
+
+    pub fn to_text(&self) -> String {
+        let format = match self.format {
+            Format::Coordinate => "coordinate",
+            Format::Array => "array",
+        };
+        let field = match self.field {
+            Field::Real => "real",
+            Field::Integer => "integer",
+            Field::Pattern => "pattern",
+            Field::Complex => "complex",
+        };
+        let symmetry = match self.symmetry {
+            Symmetry::General => "general",
+            Symmetry::Symmetric => "symmetric",
+            Symmetry::SkewSymmetric => "skew-symmetric",
+            Symmetry::Hermitian => "hermitian",
+        };
+
+        let mut text = format!("%%MatrixMarket matrix {} {} {}\n", format, field, symmetry);
+        match self.format {
+            Format::Coordinate => {
+                let _ = writeln!(text, "{} {} {}", self.rows, self.cols, self.entries.len());
+            }
+            Format::Array => {
+                let _ = writeln!(text, "{} {}", self.rows, self.cols);
+            }
+        }
+
+        for entry in &self.entries {
+            if self.format == Format::Coordinate {
+                let _ = write!(text, "{} {}", entry.row + 1, entry.col + 1);
+                if self.field != Field::Pattern {
+                    text.push(' ');
+                }
+            }
+            let _ = match self.field {
+                Field::Pattern => Ok(()),
+                Field::Integer => write!(text, "{}", entry.re as i64),
+                Field::Real => write!(text, "{:e}", entry.re),
+                Field::Complex => write!(text, "{:e} {:e}", entry.re, entry.im),
+            };
+            text.push('\n');
+        }
+        text
+    }
+}
+
+pub fn read_matrix_market<P: AsRef<Path>>(path: P) -> Result<MatrixMarket, Error> {
+    let text = fs::read_to_string(path)?;
+    MatrixMarket::parse(&text)
+}
+
+pub fn write_matrix_market<P: AsRef<Path>>(path: P, matrix: &MatrixMarket) -> Result<(), Error> {
+    fs::write(path, matrix.to_text())?;
+    Ok(())
+}
+
+/// Reads a file into a dense matrix; see `MatrixMarket` for the size limit.
+pub fn load_matrix<P: AsRef<Path>>(path: P) -> Result<MatrixDouble, Error> {
+    read_matrix_market(path)?.to_dense()
+}
+
+/// Saves a dense matrix, choosing coordinate format when at most a third of the entries are nonzero.
+pub fn save_matrix<P: AsRef<Path>>(path: P, matrix: &MatrixDouble) -> Result<(), Error> {
+    let nonzeros = matrix.data().iter().filter(|x| **x != 0.0).count();
+    let format = if nonzeros * 3 <= matrix.data().len() {
+        Format::Coordinate
+    } else {
+        Format::Array
+    };
+    write_matrix_market(path, &MatrixMarket::from_dense(matrix, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dense(text: &str) -> MatrixDouble {
+        MatrixMarket::parse(text).unwrap().to_dense().unwrap()
+    }
+
+    fn parse_error(text: &str) -> (usize, usize, String) {
+        match MatrixMarket::parse(text) {
+            Err(Error::Parse {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_real_and_integer_fields() {
+        let coordinate = "%%MatrixMarket matrix coordinate real general\n\
+                          % a comment\n\
+                          2 3 3\n\
+                          1 1 1.5\n\
+                          2 3 -2e1\n\
+                          1 2 0.25\n";
+        assert_eq!(
+            dense(coordinate),
+            MatrixDouble::from_slice(2, 3, &[1.5, 0.25, 0.0, 0.0, 0.0, -20.0])
+        );
+        let array = "%%MatrixMarket matrix array integer general\n2 2\n1\n2\n3\n4\n";
+        assert_eq!(
+            dense(array),
+            MatrixDouble::from_slice(2, 2, &[1.0, 3.0, 2.0, 4.0])
+        );
+    }
+
+    #[test]
+    fn reads_pattern_entries_as_ones() {
+        let text = "%%MatrixMarket matrix coordinate pattern general\n2 2 2\n1 2\n2 1\n";
+        assert_eq!(
+            dense(text),
+            MatrixDouble::from_slice(2, 2, &[0.0, 1.0, 1.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn expands_symmetric_and_skew_symmetric_storage() {
+        let symmetric =
+            "%%MatrixMarket matrix coordinate real symmetric\n3 3 3\n1 1 4\n3 1 2\n3 2 -1\n";
+        assert_eq!(
+            dense(symmetric),
+            MatrixDouble::from_slice(3, 3, &[4.0, 0.0, 2.0, 0.0, 0.0, -1.0, 2.0, -1.0, 0.0])
+        );
+        let symmetric_array = "%%MatrixMarket matrix array real symmetric\n2 2\n1\n2\n3\n";
+        assert_eq!(
+            dense(symmetric_array),
+            MatrixDouble::from_slice(2, 2, &[1.0, 2.0, 2.0, 3.0])
+        );
+        // The diagonal of a skew-symmetric array is implicit.
+        let skew = "%%MatrixMarket matrix array real skew-symmetric\n3 3\n1\n2\n3\n";
+        assert_eq!(
+            dense(skew),
+            MatrixDouble::from_slice(3, 3, &[0.0, -1.0, -2.0, 1.0, 0.0, -3.0, 2.0, 3.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn malformed_headers_are_errors_on_line_one() {
+        for (text, message) in [
+            ("", "empty file"),
+            (
+                "%%MatrixMarket matrix coordinate real\n",
+                "missing %%MatrixMarket header",
+            ),
+            (
+                "%%MatrixMarket vector coordinate real general\n",
+                "unsupported object 'vector'",
+            ),
+            (
+                "%%MatrixMarket matrix dense real general\n",
+                "unknown format 'dense'",
+            ),
+            (
+                "%%MatrixMarket matrix array text general\n",
+                "unknown field 'text'",
+            ),
+            (
+                "%%MatrixMarket matrix array real upper\n",
+                "unknown symmetry 'upper'",
+            ),
+            (
+                "%%MatrixMarket matrix array pattern general\n",
+                "pattern field requires coordinate format",
+            ),
+            (
+                "%%MatrixMarket matrix array real hermitian\n",
+                "hermitian symmetry requires complex field",
+            ),
+        ] {
+            assert_eq!(parse_error(text), (1, 1, message.to_string()));
+        }
+    }
+
+    #[test]
+    fn malformed_data_reports_line_and_column() {
+        let header = "%%MatrixMarket matrix coordinate real general\n";
+        assert_eq!(
+            parse_error(&format!("{}2 2 1\n1 1 x\n", header)),
+            (3, 5, "invalid number 'x'".to_string())
+        );
+        assert_eq!(
+            parse_error(&format!("{}2 2 1\n3 1 1\n", header)).2,
+            "index out of range"
+        );
+        assert_eq!(
+            parse_error(&format!("{}2 2 2\n1 1 1\n", header)),
+            (2, 1, "expected 2 entries but found 1".to_string())
+        );
+        assert_eq!(
+            parse_error(&format!("{}2 2\n", header)),
+            (2, 1, "expected 3 size values".to_string())
+        );
+        let symmetric = "%%MatrixMarket matrix array real symmetric\n2 3\n";
+        assert_eq!(
+            parse_error(symmetric).2,
+            "symmetric matrices must be square"
+        );
+        let short = "%%MatrixMarket matrix array real general\n2 2\n1\n2\n3\n";
+        assert_eq!(parse_error(short), (2, 1, "too few entries".to_string()));
+    }
+
+    #[test]
+    fn oversized_size_lines_fail_before_allocating() {
+        let overflow =
+            "%%MatrixMarket matrix coordinate real general\n4294967296 4294967296 1\n1 1 1\n";
+        assert_eq!(parse_error(overflow).0, 2);
+        let huge_array = "%%MatrixMarket matrix array real general\n1000000 1000000\n1\n";
+        assert_eq!(parse_error(huge_array).0, 2);
+        // Empty, and read without stepping through its columns.
+        let empty = "%%MatrixMarket matrix array real general\n0 18446744073709551615\n";
+        assert!(MatrixMarket::parse(empty).unwrap().entries.is_empty());
+        assert_eq!(parse_error(&format!("{}1\n", empty)).2, "too many entries");
+
+        // A sparse file may be larger than what loads densely.
+        let sparse = "%%MatrixMarket matrix coordinate real general\n1000000 1000000 1\n5 7 1\n";
+        let matrix = MatrixMarket::parse(sparse).unwrap();
+        assert_eq!(matrix.entries.len(), 1);
+        assert!(matches!(matrix.to_dense(), Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn written_files_read_back() {
+        let matrix = MatrixDouble::from_slice(2, 3, &[1.0, 0.0, -0.5, 0.0, 0.0, 1e-3]);
+        for format in [Format::Coordinate, Format::Array] {
+            let text = MatrixMarket::from_dense(&matrix, format).to_text();
+            assert_eq!(dense(&text), matrix);
+        }
+    }
+}
@@ -0,0 +1,543 @@
+use super::Error;
+use crate::math_utilities::{Matrix, Vector};
+use num_complex::Complex64;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Array data in C (row-major) order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NpyData {
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+    C128(Vec<Complex64>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: NpyData,
+}
+
+pub trait Element: Copy + Default {
+    fn extract(data: &NpyData) -> Option<Vec<Self>>;
+    fn wrap(values: Vec<Self>) -> NpyData;
+}
+
+impl Element for f64 {
+    // Every real dtype widens to f64, which is what the interpreter works with.
+    fn extract(data: &NpyData) -> Option<Vec<f64>> {
+        match data {
+            NpyData::F32(v) => Some(v.iter().map(|x| *x as f64).collect()),
+            NpyData::F64(v) => Some(v.clone()),
+            NpyData::I32(v) => Some(v.iter().map(|x| *x as f64).collect()),
+            NpyData::I64(v) => Some(v.iter().map(|x| *x as f64).collect()),
+            NpyData::C128(_) => None,
+        }
+    }
+
+    fn wrap(values: Vec<f64>) -> NpyData {
+        NpyData::F64(values)
+    }
+}
+
+impl Element for f32 {
+    fn extract(data: &NpyData) -> Option<Vec<f32>> {
+        match data {
+            NpyData::F32(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    fn wrap(values: Vec<f32>) -> NpyData {
+        NpyData::F32(values)
+    }
+}
+
+impl Element for i32 {
+    fn extract(data: &NpyData) -> Option<Vec<i32>> {
+        match data {
+            NpyData::I32(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    fn wrap(values: Vec<i32>) -> NpyData {
+        NpyData::I32(values)
+    }
+}
+
+impl Element for i64 {
+    fn extract(data: &NpyData) -> Option<Vec<i64>> {
+        match data {
+            NpyData::I32(v) => Some(v.iter().map(|x| *x as i64).collect()),
+            NpyData::I64(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    fn wrap(values: Vec<i64>) -> NpyData {
+        NpyData::I64(values)
+    }
+}
+
+impl Element for Complex64 {
+    fn extract(data: &NpyData) -> Option<Vec<Complex64>> {
+        match data {
+            NpyData::C128(v) => Some(v.clone()),
+            _ => {
+                f64::extract(data).map(|v| v.into_iter().map(|x| Complex64::new(x, 0.0)).collect())
+            }
+        }
+    }
+
+    fn wrap(values: Vec<Complex64>) -> NpyData {
+        NpyData::C128(values)
+    }
+}
+
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, Error> {
+    let pattern = format!("'{}'", key);
+    let start = header
+        .find(&pattern)
+        .ok_or_else(|| Error::Format(format!("npy header is missing '{}'", key)))?;
+    let rest = header[start + pattern.len()..].trim_start();
+    rest.strip_prefix(':')
+        .map(|value| value.trim_start())
+        .ok_or_else(|| Error::Format("malformed npy header".to_string()))
+}
+
+fn parse_header(header: &str) -> Result<(String, bool, Vec<usize>), Error> {
+    let descr = header_value(header, "descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|d| d.split('\'').next())
+        .ok_or_else(|| Error::Format("malformed npy descr".to_string()))?;
+
+    let fortran_order = header_value(header, "fortran_order")?.starts_with("True");
+
+    let shape = header_value(header, "shape")?;
+    let shape = shape
+        .strip_prefix('(')
+        .and_then(|s| s.split(')').next())
+        .ok_or_else(|| Error::Format("malformed npy shape".to_string()))?;
+    let shape = shape
+        .split(',')
+        .map(|dim| dim.trim())
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| {
+            dim.trim_end_matches('L')
+                .parse::<usize>()
+                .map_err(|_| Error::Format(format!("invalid npy dimension '{}'", dim)))
+        })
+        .collect::<Result<Vec<usize>, Error>>()?;
+
+    Ok((descr.to_string(), fortran_order, shape))
+}
+
+fn decode<const N: usize, T>(
+    bytes: &[u8],
+    count: usize,
+    big_endian: bool,
+    convert: impl Fn([u8; N], bool) -> T,
+) -> Result<Vec<T>, Error> {
+    let size = count
+        .checked_mul(N)
+        .ok_or_else(|| Error::Format("npy shape is too large".to_string()))?;
+    if bytes.len() < size {
+        return Err(Error::Format("npy data is truncated".to_string()));
+    }
+    Ok(bytes[..size]
+        .chunks_exact(N)
+        .map(|chunk| convert(chunk.try_into().unwrap(), big_endian))
+        .collect())
+}
+
+// Reorders column-major data of the given shape into row-major order.
+fn fortran_to_c<T: Copy>(values: Vec<T>, shape: &[usize]) -> Vec<T> {
+    if shape.len() < 2 {
+        return values;
+    }
+    let mut strides = vec![1; shape.len()];
+    for k in 1..shape.len() {
+        strides[k] = strides[k - 1] * shape[k - 1];
+    }
+    let mut result = Vec::with_capacity(values.len());
+    let mut index = vec![0; shape.len()];
+    for _ in 0..values.len() {
+        let offset: usize = index.iter().zip(&strides).map(|(i, s)| i * s).sum();
+        result.push(values[offset]);
+        for k in (0..shape.len()).rev() {
+            index[k] += 1;
+            if index[k] < shape[k] {
+                break;
+            }
+            index[k] = 0;
+        }
+    }
+    result
+}
+
+fn reorder(data: NpyData, shape: &[usize]) -> NpyData {
+    match data {
+        NpyData::F32(v) => NpyData::F32(fortran_to_c(v, shape)),
+        NpyData::F64(v) => NpyData::F64(fortran_to_c(v, shape)),
+        NpyData::I32(v) => NpyData::I32(fortran_to_c(v, shape)),
+        NpyData::I64(v) => NpyData::I64(fortran_to_c(v, shape)),
+        NpyData::C128(v) => NpyData::C128(fortran_to_c(v, shape)),
+    }
+}
+
+impl NpyArray {
+    pub fn parse(bytes: &[u8]) -> Result<NpyArray, Error> {
+        if bytes.len() < 10 || &bytes[..6] != MAGIC {
+            return Err(Error::Format("not an npy file".to_string()));
+        }
+        let major = bytes[6];
+        let (header_len, header_start) = match major {
+            1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+            2 | 3 => {
+                if bytes.len() < 12 {
+                    return Err(Error::Format("npy header is truncated".to_string()));
+                }
+                (
+                    u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+                    12,
+                )
+            }
+            _ => return Err(Error::Format(format!("unsupported npy version {}", major))),
+        };
+        let data_start = header_start + header_len;
+        if bytes.len() < data_start {
+            return Err(Error::Format("npy header is truncated".to_string()));
+        }
+        let header = std::str::from_utf8(&bytes[header_start..data_start])
+            .map_err(|_| Error::Format("npy header is not valid text".to_string()))?;
+        let (descr, fortran_order, shape) = parse_header(header)?;
+
+        let count = shape
+            .iter()
+            .try_fold(1usize, |count, &n| count.checked_mul(n))
+            .ok_or_else(|| Error::Format("npy shape is too large".to_string()))?;
+        let body = &bytes[data_start..];
+        if descr.len() < 2 {
+            return Err(Error::Format(format!("unsupported dtype '{}'", descr)));
+        }
+        let (order, kind) = descr.split_at(1);
+        let big_endian = match order {
+            "<" | "=" | "|" => false,
+            ">" => true,
+            _ => return Err(Error::Format(format!("unsupported dtype '{}'", descr))),
+        };
+
+        let data = match kind {
+            "f4" => NpyData::F32(decode(body, count, big_endian, |b, be| {
+                if be {
+                    f32::from_be_bytes(b)
+                } else {
+                    f32::from_le_bytes(b)
+                }
+            })?),
+            "f8" => NpyData::F64(decode(body, count, big_endian, |b, be| {
+                if be {
+                    f64::from_be_bytes(b)
+                } else {
+                    f64::from_le_bytes(b)
+                }
+            })?),
+            "i4" => NpyData::I32(decode(body, count, big_endian, |b, be| {
+                if be {
+                    i32::from_be_bytes(b)
+                } else {
+                    i32::from_le_bytes(b)
+                }
+            })?),
+            "i8" => NpyData::I64(decode(body, count, big_endian, |b, be| {
+                if be {
+                    i64::from_be_bytes(b)
+                } else {
+                    i64::from_le_bytes(b)
+                }
+            })?),
+            "c16" => NpyData::C128(decode(body, count, big_endian, |b: [u8; 16], be| {
+                let (re, im) = b.split_at(8);
+                let (re, im): ([u8; 8], [u8; 8]) = (re.try_into().unwrap(), im.try_into().unwrap());
+                if be {
+                    Complex64::new(f64::from_be_bytes(re), f64::from_be_bytes(im))
+                } else {
+                    Complex64::new(f64::from_le_bytes(re), f64::from_le_bytes(im))
+                }
+            })?),
+            _ => return Err(Error::Format(format!("unsupported dtype '{}'", descr))),
+        };
+
+        let data = if fortran_order {
+            reorder(data, &shape)
+        } else {
+            data
+        };
+
+        Ok(NpyArray { shape, data })
+    }
+
+    /// Serializes as a little-endian, C-order array, using a version 2 header only when required.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let descr = match self.data {
+            NpyData::F32(_) => "<f4",
+            NpyData::F64(_) => "<f8",
+            NpyData::I32(_) => "<i4",
+            NpyData::I64(_) => "<i8",
+            NpyData::C128(_) => "<c16",
+        };
+        let shape = match self.shape.len() {
+            1 => format!("({},)", self.shape[0]),
+            _ => format!(
+                "({})",
+                self.shape
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            descr, shape
+        );
+
+        let version = if header.len() + 11 > u16::MAX as usize {
+            2
+        } else {
+            1
+        };
+        let prefix_len = if version == 1 { 10 } else { 12 };
+        let padding = (64 - (prefix_len + header.len() + 1) % 64) % 64;
+        header.push_str(&" ".repeat(padding));
+        header.push('\n');
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(version);
+        bytes.push(0);
+        if version == 1 {
+            bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        } else {
+            bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        }
+        bytes.extend_from_slice(header.as_bytes());
+
+        match &self.data {
+            NpyData::F32(v) => v
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            NpyData::F64(v) => v
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            NpyData::I32(v) => v
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            NpyData::I64(v) => v
+                .iter()
+                .for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            NpyData::C128(v) => v.iter().for_each(|x| {
+                bytes.extend_from_slice(&x.re.to_le_bytes());
+                bytes.extend_from_slice(&x.im.to_le_bytes());
+            }),
+        }
+        bytes
+    }
+
+    pub fn from_matrix<T: Element>(matrix: &Matrix<T>) -> NpyArray {
+        NpyArray {
+            shape: vec![matrix.nrows(), matrix.ncols()],
+            data: T::wrap(matrix.data().to_vec()),
+        }
+    }
+
+    pub fn from_vector<T: Element>(vector: &Vector<T>) -> NpyArray {
+        NpyArray {
+            shape: vec![vector.size()],
+            data: T::wrap(vector.data().to_vec()),
+        }
+    }
+
+    /// Converts to a matrix. Scalars become 1x1 and one-dimensional arrays become column vectors.
+    pub fn to_matrix<T: Element>(&self) -> Result<Matrix<T>, Error> {
+        let (rows, cols) = match self.shape.as_slice() {
+            [] => (1, 1),
+            [n] => (*n, 1),
+            [rows, cols] => (*rows, *cols),
+            _ => {
+                return Err(Error::Format(format!(
+                    "cannot convert a {}-dimensional array to a matrix",
+                    self.shape.len()
+                )))
+            }
+        };
+        let values = T::extract(&self.data)
+            .ok_or_else(|| Error::Format("array has an incompatible dtype".to_string()))?;
+        Ok(Matrix::from_slice(rows, cols, &values))
+    }
+
+    pub fn to_vector<T: Element>(&self) -> Result<Vector<T>, Error> {
+        if self.shape.len() > 1 && self.shape.iter().filter(|d| **d != 1).count() > 1 {
+            return Err(Error::Format("array is not a vector".to_string()));
+        }
+        let values = T::extract(&self.data)
+            .ok_or_else(|| Error::Format("array has an incompatible dtype".to_string()))?;
+        Ok(Vector::from_slice(&values))
+    }
+}
+
+pub fn read_npy<P: AsRef<Path>>(path: P) -> Result<NpyArray, Error> {
+    NpyArray::parse(&fs::read(path)?)
+}
+
+pub fn write_npy<P: AsRef<Path>>(path: P, array: &NpyArray) -> Result<(), Error> {
+    fs::write(path, array.to_bytes())?;
+    Ok(())
+}
+
+fn zip_error(err: zip::result::ZipError) -> Error {
+    match err {
+        zip::result::ZipError::Io(err) => Error::Io(err),
+        err => Error::Format(err.to_string()),
+    }
+}
+
+/// Reads every array in an `.npz` archive, in archive order, named without the `.npy` suffix.
+pub fn read_npz<P: AsRef<Path>>(path: P) -> Result<Vec<(String, NpyArray)>, Error> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(zip_error)?;
+    let mut arrays = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(zip_error)?;
+        let name = file.name().trim_end_matches(".npy").to_string();
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let array =
+            NpyArray::parse(&bytes).map_err(|err| Error::Format(format!("{}: {}", name, err)))?;
+        arrays.push((name, array));
+    }
+    Ok(arrays)
+}
+
+/// Writes an uncompressed archive, matching `numpy.savez`.
+pub fn write_npz<P: AsRef<Path>>(path: P, arrays: &[(String, NpyArray)]) -> Result<(), Error> {
+    let mut writer = zip::ZipWriter::new(File::create(path)?);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, array) in arrays {
+        writer
+            .start_file(format!("{}.npy", name), options)
+            .map_err(zip_error)?;
+        writer.write_all(&array.to_bytes())?;
+    }
+    writer.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_shapes_are_format_errors() {
+        for shape in [vec![usize::MAX / 2, 4], vec![usize::MAX / 4]] {
+            let bytes = NpyArray {
+                shape,
+                data: NpyData::F64(Vec::new()),
+            }
+            .to_bytes();
+            assert!(matches!(NpyArray::parse(&bytes), Err(Error::Format(_))));
+        }
+    }
+
+    // A version 1 file laid out the way `numpy.save` writes one.
+    fn npy(header: &str, body: &[u8]) -> Vec<u8> {
+        let padding = 63 - (10 + header.len()) % 64;
+        let header = format!("{}{}\n", header, " ".repeat(padding));
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn f8_arrays_round_trip_bit_for_bit() {
+        let values = [1.5, f64::NAN, -0.0, f64::INFINITY, 5e-324, -1e300];
+        let matrix = Matrix::from_slice(2, 3, &values);
+        let bytes = NpyArray::from_matrix(&matrix).to_bytes();
+        let body: Vec<u8> = values.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }";
+        assert_eq!(bytes, npy(header, &body));
+
+        let read: Matrix<f64> = NpyArray::parse(&bytes).unwrap().to_matrix().unwrap();
+        assert_eq!((read.nrows(), read.ncols()), (2, 3));
+        let bits = |data: &[f64]| data.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(read.data()), bits(&values));
+
+        let vector = Vector::from_slice(&[1.0, 2.0, 3.0]);
+        let array = NpyArray::parse(&NpyArray::from_vector(&vector).to_bytes()).unwrap();
+        assert_eq!(array.shape, [3]);
+        assert_eq!(array.to_vector::<f64>().unwrap(), vector);
+    }
+
+    #[test]
+    fn fortran_order_is_read_as_rows() {
+        // np.asfortranarray(np.arange(6.0).reshape(2, 3))
+        let body: Vec<u8> = [0.0f64, 3.0, 1.0, 4.0, 2.0, 5.0]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let header = "{'descr': '<f8', 'fortran_order': True, 'shape': (2, 3), }";
+        let matrix: Matrix<f64> = NpyArray::parse(&npy(header, &body))
+            .unwrap()
+            .to_matrix()
+            .unwrap();
+        assert_eq!(
+            matrix,
+            Matrix::from_slice(2, 3, &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0])
+        );
+
+        // np.asfortranarray(np.arange(8, dtype='>i4').reshape(2, 2, 2))
+        let body: Vec<u8> = [0i32, 4, 2, 6, 1, 5, 3, 7]
+            .iter()
+            .flat_map(|x| x.to_be_bytes())
+            .collect();
+        let header = "{'descr': '>i4', 'fortran_order': True, 'shape': (2, 2, 2), }";
+        let array = NpyArray::parse(&npy(header, &body)).unwrap();
+        assert_eq!(array.data, NpyData::I32((0..8).collect()));
+        assert!(array.to_matrix::<i32>().is_err());
+    }
+
+    #[test]
+    fn npz_archives_keep_every_array_in_order() {
+        let arrays = vec![
+            (
+                "A".to_string(),
+                NpyArray::from_matrix(&Matrix::from_slice(2, 2, &[1.0, 2.0, 3.0, 4.0])),
+            ),
+            (
+                "counts".to_string(),
+                NpyArray::from_vector(&Vector::from_slice(&[7i64, -1, 0])),
+            ),
+            (
+                "z".to_string(),
+                NpyArray {
+                    shape: vec![],
+                    data: NpyData::C128(vec![Complex64::new(0.5, -2.0)]),
+                },
+            ),
+        ];
+        let name = format!("numerus-npz-{}.npz", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        write_npz(&path, &arrays).unwrap();
+        let read = read_npz(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, arrays);
+    }
+}
@@ -0,0 +1,205 @@
+use super::Error;
+use crate::interpreter::is_identifier;
+use crate::math_utilities::MatrixDouble;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const FORMAT_NAME: &str = "numerus-session";
+pub const FORMAT_VERSION: u64 = 1;
+
+/// File used by the front ends to restore the previous session when started with `--restore`.
+pub const LAST_SESSION_FILE: &str = "last_session.num";
+
+// JSON has no representation for NaN or infinities, so they are stored as strings.
+pub fn encode_number(x: f64) -> Value {
+    if x.is_nan() {
+        json!("NaN")
+    } else if x == f64::INFINITY {
+        json!("Inf")
+    } else if x == f64::NEG_INFINITY {
+        json!("-Inf")
+    } else {
+        json!(x)
+    }
+}
+
+pub fn decode_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Inf" => Some(f64::INFINITY),
+            "-Inf" => Some(f64::NEG_INFINITY),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn session_to_json(matrices: &HashMap<String, MatrixDouble>) -> Value {
+    let mut names: Vec<&String> = matrices.keys().collect();
+    names.sort();
+
+    let variables: Vec<Value> = names
+        .into_iter()
+        .map(|name| {
+            let matrix = &matrices[name];
+            json!({
+                "name": name,
+                "type": "matrix",
+                "dtype": "f64",
+                "rows": matrix.nrows(),
+                "cols": matrix.ncols(),
+                "data": matrix.data().iter().map(|x| encode_number(*x)).collect::<Vec<Value>>(),
+            })
+        })
+        .collect();
+
+    json!({
+        "format": FORMAT_NAME,
+        "version": FORMAT_VERSION,
+        "variables": variables,
+    })
+}
+
+fn variable_from_json(value: &Value) -> Result<(String, MatrixDouble), Error> {
+    let field = |key: &str| {
+        value
+            .get(key)
+            .ok_or_else(|| Error::Format(format!("variable is missing '{}'", key)))
+    };
+
+    let name = field("name")?
+        .as_str()
+        .ok_or_else(|| Error::Format("variable name must be a string".to_string()))?;
+    if !is_identifier(name) {
+        return Err(Error::Format(format!(
+            "'{}' is not a valid variable name",
+            name
+        )));
+    }
+    let kind = field("type")?.as_str().unwrap_or_default();
+    let dtype = field("dtype")?.as_str().unwrap_or_default();
+    if kind != "matrix" || dtype != "f64" {
+        return Err(Error::Format(format!(
+            "{}: unsupported variable type '{}' ({})",
+            name, kind, dtype
+        )));
+    }
+
+    let rows = field("rows")?.as_u64().unwrap_or_default() as usize;
+    let cols = field("cols")?.as_u64().unwrap_or_default() as usize;
+    let data = field("data")?
+        .as_array()
+        .ok_or_else(|| Error::Format(format!("{}: data must be an array", name)))?
+        .iter()
+        .map(decode_number)
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| Error::Format(format!("{}: data contains an invalid number", name)))?;
+    let count = rows
+        .checked_mul(cols)
+        .ok_or_else(|| Error::Format(format!("{}: {}x{} is too large", name, rows, cols)))?;
+    if data.len() != count {
+        return Err(Error::Format(format!(
+            "{}: expected {} values but found {}",
+            name,
+            count,
+            data.len()
+        )));
+    }
+
+    Ok((name.to_string(), MatrixDouble::from_slice(rows, cols, &data)))
+}
+
+pub fn session_from_json(value: &Value) -> Result<HashMap<String, MatrixDouble>, Error> {
+    if value.get("format").and_then(Value::as_str) != Some(FORMAT_NAME) {
+        return Err(Error::Format("not a Numerus session file".to_string()));
+    }
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(Error::Format(format!(
+            "unsupported session version {}",
+            version
+        )));
+    }
+
+    let variables = value
+        .get("variables")
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::Format("session has no variables".to_string()))?;
+
+    variables.iter().map(variable_from_json).collect()
+}
+
+pub fn save_session<P: AsRef<Path>>(
+    path: P,
+    matrices: &HashMap<String, MatrixDouble>,
+) -> Result<(), Error> {
+    let text = serde_json::to_string_pretty(&session_to_json(matrices))
+        .map_err(|err| Error::Format(err.to_string()))?;
+    fs::write(path, text + "\n")?;
+    Ok(())
+}
+
+pub fn load_session<P: AsRef<Path>>(path: P) -> Result<HashMap<String, MatrixDouble>, Error> {
+    let text = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&text)
+        .map_err(|err| Error::Format(err.to_string()))?;
+    session_from_json(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str, rows: u64, cols: u64, data: Value) -> Value {
+        json!({
+            "format": FORMAT_NAME,
+            "version": FORMAT_VERSION,
+            "variables": [{
+                "name": name, "type": "matrix", "dtype": "f64",
+                "rows": rows, "cols": cols, "data": data,
+            }],
+        })
+    }
+
+    fn is_format_error(session: &Value) -> bool {
+        matches!(session_from_json(session), Err(Error::Format(_)))
+    }
+
+    #[test]
+    fn save_and_load_round_trip_including_nan_and_infinities() {
+        let (inf, nan) = (f64::INFINITY, f64::NAN);
+        let matrices = HashMap::from([
+            (
+                "A".to_string(),
+                MatrixDouble::from_slice(2, 3, &[1.5, -2.0, inf, -inf, 0.0, 1e-300]),
+            ),
+            ("b".to_string(), MatrixDouble::from_slice(1, 2, &[nan, 3.0])),
+            ("empty".to_string(), MatrixDouble::new(0, 3)),
+        ]);
+        let name = format!("numerus-session-{}.num", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        save_session(&path, &matrices).unwrap();
+        let loaded = load_session(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded["A"], matrices["A"]);
+        assert_eq!(loaded["empty"].ncols(), 3);
+        assert!(loaded["b"][0][0].is_nan() && loaded["b"][0][1] == 3.0);
+        let data = &session_to_json(&matrices)["variables"][0]["data"];
+        assert_eq!((&data[2], &data[3]), (&json!("Inf"), &json!("-Inf")));
+    }
+
+    #[test]
+    fn invalid_names_and_sizes_are_format_errors() {
+        assert!(is_format_error(&variable("1 bad", 1, 1, json!([1.0]))));
+        assert!(is_format_error(&variable("A", u64::MAX, 2, json!([]))));
+        assert!(is_format_error(&variable("A", 2, 2, json!([1.0, 2.0]))));
+        assert!(is_format_error(&variable("A", 1, 1, json!(["Infinity"]))));
+        assert!(session_from_json(&variable("A_1", 1, 1, json!([1.0]))).is_ok());
+    }
+}
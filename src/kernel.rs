@@ -0,0 +1,840 @@
+use crate::cancel::{cancelled, with_token, CancelToken, CANCELLED};
+use crate::format::{matrix_to_html, matrix_to_latex, LatexBrackets};
+use crate::math_utilities::MatrixDouble;
+use crate::output::{Output, Value};
+use crate::interpreter::{
+    completion_candidates, display_options, evaluate_command, find_function, matrix_to_string,
+};
+use crate::script::{is_complete, split_statements};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Map, Value as Json};
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PROTOCOL_VERSION: &str = "5.3";
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+// How often a running execution checks the control channel for an interrupt.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Ports and signing key from the connection file Jupyter passes to the kernel.
+pub struct ConnectionInfo {
+    pub transport: String,
+    pub ip: String,
+    pub shell_port: u64,
+    pub iopub_port: u64,
+    pub stdin_port: u64,
+    pub control_port: u64,
+    pub hb_port: u64,
+    pub key: String,
+}
+
+impl ConnectionInfo {
+    pub fn parse(text: &str) -> Result<ConnectionInfo, String> {
+        let value: Json = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        let string = |key: &str| {
+            value
+                .get(key)
+                .and_then(Json::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| format!("connection file is missing '{}'", key))
+        };
+        let port = |key: &str| {
+            value
+                .get(key)
+                .and_then(Json::as_u64)
+                .ok_or_else(|| format!("connection file is missing '{}'", key))
+        };
+
+        let scheme = value
+            .get("signature_scheme")
+            .and_then(Json::as_str)
+            .unwrap_or("hmac-sha256");
+        if scheme != "hmac-sha256" {
+            return Err(format!("unsupported signature scheme '{}'", scheme));
+        }
+
+        Ok(ConnectionInfo {
+            transport: string("transport")?,
+            ip: string("ip")?,
+            shell_port: port("shell_port")?,
+            iopub_port: port("iopub_port")?,
+            stdin_port: port("stdin_port")?,
+            control_port: port("control_port")?,
+            hb_port: port("hb_port")?,
+            key: value
+                .get("key")
+                .and_then(Json::as_str)
+                .unwrap_or("")
+                .to_string(),
+        })
+    }
+
+    fn endpoint(&self, port: u64) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, port)
+    }
+}
+
+struct Message {
+    identities: Vec<Vec<u8>>,
+    header: Json,
+    content: Json,
+}
+
+impl Message {
+    fn msg_type(&self) -> &str {
+        self.header
+            .get("msg_type")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &[u8]) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    text.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn mac(key: &[u8], frames: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key");
+    for frame in frames {
+        mac.update(frame);
+    }
+    mac
+}
+
+// An empty key turns signing off, as in the Jupyter protocol.
+fn sign(key: &[u8], frames: &[&[u8]]) -> String {
+    if key.is_empty() {
+        return String::new();
+    }
+    to_hex(&mac(key, frames).finalize().into_bytes())
+}
+
+// Compares in constant time, so that timing does not reveal how much of a forged signature
+// is right.
+fn verify(key: &[u8], frames: &[&[u8]], signature: &[u8]) -> bool {
+    if key.is_empty() {
+        return signature.is_empty();
+    }
+    from_hex(signature).is_some_and(|signature| mac(key, frames).verify_slice(&signature).is_ok())
+}
+
+// Converts days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let seconds_of_day = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        now.subsec_micros()
+    )
+}
+
+/// Builds a mime bundle for a result, adding HTML and LaTeX renderings of matrix values.
+fn mime_bundle(output: &Output) -> Json {
+    let mut bundle = Map::new();
+    bundle.insert("text/plain".to_string(), json!(output.text.trim_end()));
+    let matrix = match &output.value {
+        Value::Matrix(m) => Some(m.clone()),
+        Value::Vector(v) => Some(MatrixDouble::from_slice(v.size(), 1, v.data())),
+        Value::Lu { lu, .. } => Some(lu.clone()),
+        Value::Scalar(_) | Value::None | Value::Plot(_) => None,
+    };
+    if let Some(matrix) = matrix {
+        let format = display_options().format;
+        bundle.insert(
+            "text/html".to_string(),
+            json!(matrix_to_html(&matrix, format)),
+        );
+        bundle.insert(
+            "text/latex".to_string(),
+            json!(format!(
+                "${}$",
+                matrix_to_latex(&matrix, format, LatexBrackets::Square)
+            )),
+        );
+    }
+    Json::Object(bundle)
+}
+
+// Byte range of the identifier touching the cursor, given in characters as Jupyter does.
+fn token_at(code: &str, cursor_pos: usize) -> (usize, usize) {
+    let cursor = code
+        .char_indices()
+        .nth(cursor_pos)
+        .map(|(index, _)| index)
+        .unwrap_or(code.len());
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let start = code[..cursor]
+        .rfind(|c: char| !is_ident(c))
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let end = code[cursor..]
+        .find(|c: char| !is_ident(c))
+        .map(|index| cursor + index)
+        .unwrap_or(code.len());
+    (start, end)
+}
+
+fn char_offset(code: &str, byte_index: usize) -> usize {
+    code[..byte_index].chars().count()
+}
+
+type Publish<'a, E> = dyn FnMut(&str, Json) -> Result<(), E> + 'a;
+
+fn kernel_info() -> Json {
+    json!({
+        "status": "ok",
+        "protocol_version": PROTOCOL_VERSION,
+        "implementation": "numerus",
+        "implementation_version": env!("CARGO_PKG_VERSION"),
+        "language_info": {
+            "name": "numerus",
+            "version": env!("CARGO_PKG_VERSION"),
+            "mimetype": "text/x-numerus",
+            "file_extension": ".num",
+        },
+        "banner": "Numerus - matrix operations in Rust",
+        "help_links": [],
+    })
+}
+
+type Evaluate = fn(&str, &mut HashMap<String, MatrixDouble>) -> Result<Output, String>;
+
+/// The kernel's variables and execution count, apart from the ZeroMQ transport.
+struct Workspace {
+    execution_count: u64,
+    matrices: HashMap<String, MatrixDouble>,
+    evaluate: Evaluate,
+}
+
+impl Default for Workspace {
+    fn default() -> Workspace {
+        Workspace {
+            execution_count: 0,
+            matrices: HashMap::new(),
+            evaluate: evaluate_command,
+        }
+    }
+}
+
+impl Workspace {
+    fn execute<E>(&mut self, content: &Json, publish: &mut Publish<E>) -> Result<Json, E> {
+        let code = content
+            .get("code")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+        let silent = content
+            .get("silent")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+            publish(
+                "execute_input",
+                json!({ "code": code, "execution_count": self.execution_count }),
+            )?;
+        }
+
+        let statements = split_statements(&code);
+        let last_shown = statements.iter().rposition(|statement| !statement.silent);
+        for (index, statement) in statements.iter().enumerate() {
+            // A panicking kernel must not take the Jupyter kernel down with it.
+            let result = if cancelled() {
+                Err(CANCELLED.to_string())
+            } else {
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    (self.evaluate)(&statement.text, &mut self.matrices)
+                }))
+                .unwrap_or_else(|_| {
+                    Err("Internal error while evaluating the statement.".to_string())
+                })
+            };
+            match result {
+                Ok(output) => {
+                    if silent || statement.silent {
+                        continue;
+                    }
+                    let data = mime_bundle(&output);
+                    if Some(index) == last_shown {
+                        let count = self.execution_count;
+                        publish(
+                            "execute_result",
+                            json!({ "execution_count": count, "data": data, "metadata": {} }),
+                        )?;
+                    } else {
+                        publish(
+                            "display_data",
+                            json!({ "data": data, "metadata": {}, "transient": {} }),
+                        )?;
+                    }
+                }
+                Err(message) => {
+                    let error = json!({
+                        "ename": "Error",
+                        "evalue": message.trim_end(),
+                        "traceback": [format!("line {}: {}", statement.line, message.trim_end())],
+                    });
+                    if !silent {
+                        publish("error", error.clone())?;
+                    }
+                    let mut reply = error;
+                    reply["status"] = json!("error");
+                    reply["execution_count"] = json!(self.execution_count);
+                    return Ok(reply);
+                }
+            }
+        }
+
+        Ok(json!({
+            "status": "ok",
+            "execution_count": self.execution_count,
+            "payload": [],
+            "user_expressions": {},
+        }))
+    }
+
+    fn complete(&self, content: &Json) -> Json {
+        let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+        let cursor_pos = content
+            .get("cursor_pos")
+            .and_then(Json::as_u64)
+            .unwrap_or(code.chars().count() as u64) as usize;
+        let cursor = code
+            .char_indices()
+            .nth(cursor_pos)
+            .map(|(index, _)| index)
+            .unwrap_or(code.len());
+        let (start, _) = token_at(code, cursor_pos);
+        json!({
+            "status": "ok",
+            "matches": completion_candidates(&code[start..cursor], self.matrices.keys()),
+            "cursor_start": char_offset(code, start),
+            "cursor_end": char_offset(code, cursor),
+            "metadata": {},
+        })
+    }
+
+    fn inspect(&self, content: &Json) -> Json {
+        let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+        let cursor_pos = content
+            .get("cursor_pos")
+            .and_then(Json::as_u64)
+            .unwrap_or(0) as usize;
+        let (start, end) = token_at(code, cursor_pos);
+        let name = &code[start..end];
+
+        let text = if let Some((signature, description)) = find_function(name) {
+            Some(format!("{}\n\n{}", signature, description))
+        } else {
+            self.matrices.get(name).map(|matrix| {
+                format!(
+                    "{}: {}x{} matrix\n\n{}",
+                    name,
+                    matrix.nrows(),
+                    matrix.ncols(),
+                    matrix_to_string(matrix).trim_end()
+                )
+            })
+        };
+
+        match text {
+            Some(text) => json!({
+                "status": "ok",
+                "found": true,
+                "data": { "text/plain": text },
+                "metadata": {},
+            }),
+            None => json!({ "status": "ok", "found": false, "data": {}, "metadata": {} }),
+        }
+    }
+
+    // Answers one shell or control request, or returns None for message types the kernel
+    // does not handle. IOPub messages go to `publish` as soon as they are produced.
+    fn handle<E>(
+        &mut self,
+        msg_type: &str,
+        content: &Json,
+        publish: &mut Publish<E>,
+    ) -> Result<Option<Json>, E> {
+        Ok(match msg_type {
+            "kernel_info_request" => Some(kernel_info()),
+            "execute_request" => Some(self.execute(content, publish)?),
+            "complete_request" => Some(self.complete(content)),
+            "inspect_request" => Some(self.inspect(content)),
+            "is_complete_request" => {
+                let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+                Some(if is_complete(code) {
+                    json!({ "status": "complete" })
+                } else {
+                    json!({ "status": "incomplete", "indent": "" })
+                })
+            }
+            "history_request" => Some(json!({ "status": "ok", "history": [] })),
+            "comm_info_request" => Some(json!({ "status": "ok", "comms": {} })),
+            "interrupt_request" => Some(json!({ "status": "ok" })),
+            "shutdown_request" => {
+                let restart = content.get("restart").cloned().unwrap_or(json!(false));
+                Some(json!({ "status": "ok", "restart": restart }))
+            }
+            other => {
+                eprintln!("Unhandled message type '{}'", other);
+                None
+            }
+        })
+    }
+}
+
+pub struct Kernel {
+    shell: zmq::Socket,
+    control: zmq::Socket,
+    iopub: zmq::Socket,
+    // Bound so frontends can connect, but the kernel never asks for input.
+    _stdin: zmq::Socket,
+    key: Vec<u8>,
+    session: String,
+    message_count: u64,
+    workspace: Workspace,
+    // Control requests that arrived while a cell was running, answered once it has finished.
+    deferred: VecDeque<Message>,
+}
+
+impl Kernel {
+    pub fn new(info: &ConnectionInfo) -> Result<Kernel, zmq::Error> {
+        let context = zmq::Context::new();
+        let bind = |kind, port| -> Result<zmq::Socket, zmq::Error> {
+            let socket = context.socket(kind)?;
+            socket.bind(&info.endpoint(port))?;
+            Ok(socket)
+        };
+        let shell = bind(zmq::ROUTER, info.shell_port)?;
+        let control = bind(zmq::ROUTER, info.control_port)?;
+        let iopub = bind(zmq::PUB, info.iopub_port)?;
+        let stdin = bind(zmq::ROUTER, info.stdin_port)?;
+        let heartbeat = bind(zmq::REP, info.hb_port)?;
+
+        thread::spawn(move || {
+            while let Ok(ping) = heartbeat.recv_bytes(0) {
+                if heartbeat.send(ping, 0).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Ok(Kernel {
+            shell,
+            control,
+            iopub,
+            _stdin: stdin,
+            key: info.key.as_bytes().to_vec(),
+            session: format!("{:x}-{:x}", nanos, process::id()),
+            message_count: 0,
+            workspace: Workspace::default(),
+            deferred: VecDeque::new(),
+        })
+    }
+
+    fn decode(&self, frames: Vec<Vec<u8>>) -> Result<Message, String> {
+        let delimiter = frames
+            .iter()
+            .position(|frame| frame.as_slice() == DELIMITER)
+            .ok_or("message has no delimiter")?;
+        if frames.len() < delimiter + 6 {
+            return Err("message is truncated".to_string());
+        }
+        let parts: Vec<&[u8]> = frames[delimiter + 2..delimiter + 6]
+            .iter()
+            .map(|frame| frame.as_slice())
+            .collect();
+        if !verify(&self.key, &parts, &frames[delimiter + 1]) {
+            return Err("message has an invalid signature".to_string());
+        }
+
+        let parse =
+            |frame: &[u8]| serde_json::from_slice::<Json>(frame).map_err(|err| err.to_string());
+        Ok(Message {
+            identities: frames[..delimiter].to_vec(),
+            header: parse(parts[0])?,
+            content: parse(parts[3])?,
+        })
+    }
+
+    fn header(&mut self, msg_type: &str) -> Json {
+        self.message_count += 1;
+        json!({
+            "msg_id": format!("{}-{}", self.session, self.message_count),
+            "session": self.session,
+            "username": "numerus",
+            "date": timestamp(),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        })
+    }
+
+    fn send(
+        &mut self,
+        channel: Channel,
+        identities: Vec<Vec<u8>>,
+        msg_type: &str,
+        parent: &Message,
+        content: Json,
+    ) -> Result<(), zmq::Error> {
+        let header = self.header(msg_type).to_string();
+        let parent_header = parent.header.to_string();
+        let metadata = "{}".to_string();
+        let content = content.to_string();
+        let parts = [
+            header.as_bytes(),
+            parent_header.as_bytes(),
+            metadata.as_bytes(),
+            content.as_bytes(),
+        ];
+        let signature = sign(&self.key, &parts);
+
+        let mut frames = identities;
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.push(header.into_bytes());
+        frames.push(parent_header.into_bytes());
+        frames.push(metadata.into_bytes());
+        frames.push(content.into_bytes());
+
+        let socket = match channel {
+            Channel::Shell => &self.shell,
+            Channel::Control => &self.control,
+            Channel::IoPub => &self.iopub,
+        };
+        socket.send_multipart(frames, 0)
+    }
+
+    fn reply(
+        &mut self,
+        channel: Channel,
+        request: &Message,
+        content: Json,
+    ) -> Result<(), zmq::Error> {
+        let msg_type = request.msg_type().replace("_request", "_reply");
+        self.send(
+            channel,
+            request.identities.clone(),
+            &msg_type,
+            request,
+            content,
+        )
+    }
+
+    fn publish(
+        &mut self,
+        msg_type: &str,
+        parent: &Message,
+        content: Json,
+    ) -> Result<(), zmq::Error> {
+        let topic = format!("kernel.{}.{}", self.session, msg_type).into_bytes();
+        self.send(Channel::IoPub, vec![topic], msg_type, parent, content)
+    }
+
+    fn receive(&self, channel: Channel) -> Result<Option<Message>, zmq::Error> {
+        let socket = match channel {
+            Channel::Control => &self.control,
+            _ => &self.shell,
+        };
+        let frames = socket.recv_multipart(0)?;
+        Ok(self
+            .decode(frames)
+            .map_err(|err| eprintln!("Ignoring message: {}", err))
+            .ok())
+    }
+
+    fn handle_next(&mut self, channel: Channel) -> Result<bool, zmq::Error> {
+        match self.receive(channel)? {
+            Some(request) => self.handle(channel, request),
+            None => Ok(true),
+        }
+    }
+
+    // Returns false once the frontend has asked the kernel to shut down.
+    fn handle(&mut self, channel: Channel, request: Message) -> Result<bool, zmq::Error> {
+        self.publish("status", &request, json!({ "execution_state": "busy" }))?;
+        let reply = if request.msg_type() == "execute_request" {
+            self.execute(&request)?
+        } else {
+            // The workspace is moved out so that it can publish through the kernel's sockets.
+            let mut workspace = mem::take(&mut self.workspace);
+            let reply = workspace.handle(
+                request.msg_type(),
+                &request.content,
+                &mut |msg_type, content| self.publish(msg_type, &request, content),
+            );
+            self.workspace = workspace;
+            reply?
+        };
+        if let Some(reply) = reply {
+            self.reply(channel, &request, reply)?;
+        }
+        self.publish("status", &request, json!({ "execution_state": "idle" }))?;
+        Ok(request.msg_type() != "shutdown_request")
+    }
+
+    // Runs the cell on another thread, publishing its output from here, so that an
+    // `interrupt_request` on the control channel can cancel it.
+    fn execute(&mut self, request: &Message) -> Result<Option<Json>, zmq::Error> {
+        let token = CancelToken::new();
+        let (sender, receiver) = mpsc::channel();
+        let mut workspace = mem::take(&mut self.workspace);
+        let content = request.content.clone();
+        let worker = thread::spawn({
+            let token = token.clone();
+            move || {
+                let reply = with_token(&token, || {
+                    workspace.handle("execute_request", &content, &mut |msg_type, content| {
+                        sender.send((msg_type.to_string(), content))
+                    })
+                });
+                (workspace, reply)
+            }
+        });
+
+        loop {
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok((msg_type, content)) => self.publish(&msg_type, request, content)?,
+                // The worker has finished and dropped its sender.
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if self.control.poll(zmq::POLLIN, 0)? == 0 {
+                continue;
+            }
+            let Some(control) = self.receive(Channel::Control)? else {
+                continue;
+            };
+            if control.msg_type() == "interrupt_request" {
+                token.cancel();
+                self.reply(Channel::Control, &control, json!({ "status": "ok" }))?;
+            } else {
+                self.deferred.push_back(control);
+            }
+        }
+
+        let (workspace, reply) = worker.join().expect("execute catches evaluation panics");
+        self.workspace = workspace;
+        Ok(reply.expect("the receiver outlives the worker"))
+    }
+
+    pub fn run(&mut self) -> Result<(), zmq::Error> {
+        loop {
+            if let Some(request) = self.deferred.pop_front() {
+                if !self.handle(Channel::Control, request)? {
+                    break;
+                }
+                continue;
+            }
+            let (shell_ready, control_ready) = {
+                let mut items = [
+                    self.shell.as_poll_item(zmq::POLLIN),
+                    self.control.as_poll_item(zmq::POLLIN),
+                ];
+                zmq::poll(&mut items, -1)?;
+                (items[0].is_readable(), items[1].is_readable())
+            };
+            if control_ready && !self.handle_next(Channel::Control)? {
+                break;
+            }
+            if shell_ready && !self.handle_next(Channel::Shell)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Channel {
+    Shell,
+    Control,
+    IoPub,
+}
+
+/// Runs the kernel described by a Jupyter connection file until it is shut down. Interrupts
+/// arrive as `interrupt_request` messages, so the kernelspec should set
+/// `"interrupt_mode": "message"`.
+pub fn start_kernel(connection_file: &str) -> Result<(), String> {
+    let text = fs::read_to_string(connection_file)
+        .map_err(|err| format!("{}: {}", connection_file, err))?;
+    let info = ConnectionInfo::parse(&text)?;
+    let mut kernel = Kernel::new(&info).map_err(|err| err.to_string())?;
+    kernel.run().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    // Handles one request and returns the reply with the IOPub messages it published.
+    fn request(
+        workspace: &mut Workspace,
+        msg_type: &str,
+        content: Json,
+    ) -> (Option<Json>, Vec<(String, Json)>) {
+        let mut published = Vec::new();
+        let reply = workspace
+            .handle(msg_type, &content, &mut |msg_type, content| {
+                published.push((msg_type.to_string(), content));
+                Ok::<(), Infallible>(())
+            })
+            .unwrap();
+        (reply, published)
+    }
+
+    #[test]
+    fn execute_publishes_input_and_result() {
+        let mut workspace = Workspace::default();
+        let (reply, published) = request(
+            &mut workspace,
+            "execute_request",
+            json!({ "code": "A = eye(2); A + A" }),
+        );
+        let reply = reply.unwrap();
+        assert_eq!(reply["status"], "ok");
+        assert_eq!(reply["execution_count"], 1);
+        let types: Vec<&str> = published.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(types, ["execute_input", "execute_result"]);
+        assert!(published[1].1["data"]["text/plain"]
+            .as_str()
+            .unwrap()
+            .contains('2'));
+        assert!(workspace.matrices.contains_key("A"));
+    }
+
+    #[test]
+    fn panicking_statement_is_an_error_reply() {
+        let mut workspace = Workspace {
+            evaluate: |command, matrices| match command {
+                "boom" => panic!("kernel bug"),
+                _ => evaluate_command(command, matrices),
+            },
+            ..Workspace::default()
+        };
+        let (reply, published) = request(
+            &mut workspace,
+            "execute_request",
+            json!({ "code": "B = eye(3); boom" }),
+        );
+        assert_eq!(reply.unwrap()["status"], "error");
+        assert_eq!(published.last().unwrap().0, "error");
+        // The kernel keeps its workspace and goes on answering.
+        assert!(workspace.matrices.contains_key("B"));
+        let (reply, _) = request(&mut workspace, "execute_request", json!({ "code": "B" }));
+        assert_eq!(reply.unwrap()["execution_count"], 2);
+    }
+
+    #[test]
+    fn complete_inspect_and_is_complete() {
+        let mut workspace = Workspace::default();
+        request(
+            &mut workspace,
+            "execute_request",
+            json!({ "code": "matrix1 = eye(2)" }),
+        );
+        let (reply, _) = request(
+            &mut workspace,
+            "complete_request",
+            json!({ "code": "x = matr", "cursor_pos": 8 }),
+        );
+        let reply = reply.unwrap();
+        assert!(reply["matches"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("matrix1")));
+        assert_eq!(reply["cursor_start"], 4);
+        let (reply, _) = request(
+            &mut workspace,
+            "inspect_request",
+            json!({ "code": "matrix1", "cursor_pos": 3 }),
+        );
+        assert_eq!(reply.unwrap()["found"], true);
+        let (reply, _) = request(
+            &mut workspace,
+            "is_complete_request",
+            json!({ "code": "[1 2" }),
+        );
+        assert_eq!(reply.unwrap()["status"], "incomplete");
+        let (reply, _) = request(&mut workspace, "kernel_info_request", json!({}));
+        assert_eq!(reply.unwrap()["protocol_version"], PROTOCOL_VERSION);
+        let (reply, _) = request(&mut workspace, "unknown_request", json!({}));
+        assert!(reply.is_none());
+    }
+
+    #[test]
+    fn interrupted_cells_stop_with_an_error() {
+        let mut workspace = Workspace::default();
+        let token = CancelToken::new();
+        token.cancel();
+        let (reply, _) = with_token(&token, || {
+            request(
+                &mut workspace,
+                "execute_request",
+                json!({ "code": "C = eye(2)" }),
+            )
+        });
+        let reply = reply.unwrap();
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["evalue"], CANCELLED);
+        assert!(!workspace.matrices.contains_key("C"));
+    }
+
+    #[test]
+    fn signatures_are_checked() {
+        let frames: [&[u8]; 2] = [b"{}", b"{\"a\": 1}"];
+        let signature = sign(b"key", &frames);
+        assert_eq!(signature.len(), 64);
+        assert!(verify(b"key", &frames, signature.as_bytes()));
+        assert!(!verify(b"other", &frames, signature.as_bytes()));
+        assert!(!verify(b"key", &frames, &signature.as_bytes()[1..]));
+        assert!(!verify(b"key", &frames, b""));
+        assert!(verify(b"", &frames, b""));
+        assert!(!verify(b"", &frames, signature.as_bytes()));
+    }
+}
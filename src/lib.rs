@@ -1,3 +1,51 @@
-// src/lib.rs
-pub mod math_utilities;
+/// Cooperative cancellation of long computations.
+pub mod cancel;
+/// Matrix decompositions.
+pub mod decomp;
+/// Matrices and vectors whose dimensions are checked at compile time.
+pub mod fixed_matrix;
+/// Number formats and the text, LaTeX, Markdown and HTML rendering of matrices.
+pub mod format;
+/// Rotations, quaternions and rigid transforms in three dimensions.
+pub mod geometry;
+/// The desktop notebook.
+#[cfg(feature = "gui")]
+pub mod gui;
+#[cfg(feature = "gui")]
+mod inspector;
+/// The command language shared by the REPL, scripts, the GUI and the kernel.
+pub mod interpreter;
+/// Reading and writing matrices in CSV, MAT, Matrix Market and NumPy files, and sessions.
+pub mod io;
+/// A Jupyter kernel.
+#[cfg(feature = "kernel")]
+pub mod kernel;
+/// LU decomposition with partial pivoting.
 pub mod lu_decomposition;
+/// The dynamically sized `Matrix` and `Vector` types and small numeric helpers.
+pub mod math_utilities;
+/// Matrix exponential, logarithm, square root and powers.
+pub mod matrix_functions;
+/// Matrix addition, subtraction and multiplication.
+pub mod matrix_operations;
+/// Notebooks stored as scripts with cell markers, and their export.
+pub mod notebook;
+/// Matrix arithmetic, matrix functions and constructors of special matrices.
+pub mod ops;
+/// Results of commands and their JSON form.
+pub mod output;
+/// Line, scatter, histogram, image and spy plots.
+pub mod plot;
+/// Seeded random number generation.
+pub mod random;
+/// The interactive terminal.
+#[cfg(feature = "repl")]
+pub mod repl;
+#[cfg(feature = "repl")]
+mod repl_helper;
+/// Splitting scripts into statements and running them.
+pub mod script;
+/// A JSON-RPC server over stdio or TCP.
+pub mod server;
+/// Identity, magic, Hilbert and Vandermonde matrices, and evenly spaced vectors.
+pub mod special_matrices;
@@ -1,3 +1,4 @@
+use crate::cancel::{cancelled, CANCELLED};
 use crate::math_utilities::{MatrixDouble, VectorDouble, VectorInt};
 
 pub struct LU {
@@ -5,16 +6,27 @@ pub struct LU {
     lu: MatrixDouble,
     index: VectorInt,
     d: f64,
+    // Largest absolute row sum of the factored matrix.
+    norm: f64,
+    // Set when a zero pivot had to be replaced by a tiny one.
+    zero_pivot: bool,
 }
 
 impl LU {
-    pub fn new(a: &MatrixDouble) -> LU {
+    /// Factors `a` with partial pivoting. Fails for a non-square matrix, a matrix with a zero
+    /// row and when the computation is cancelled.
+    pub fn new(a: &MatrixDouble) -> Result<LU, &'static str> {
         let n = a.nrows();
+        if a.ncols() != n {
+            return Err("LU decomposition needs a square matrix");
+        }
         let mut lu = a.clone();
         let mut index = VectorInt::new(n);
         let mut d = 1.0;
         let mut vv = VectorDouble::new(n);
         let tiny = 1.0e-40;
+        let mut norm: f64 = 0.0;
+        let mut zero_pivot = false;
 
         for i in 0..n {
             let mut big = 0.0;
@@ -24,13 +36,17 @@ impl LU {
                     big = temp;
                 }
             }
+            norm = norm.max(lu[i].iter().map(|x| x.abs()).sum());
             if big == 0.0 {
-                panic!("Singular matrix in LU decomposition");
+                return Err("Singular matrix in LU decomposition");
             }
             vv[i] = 1.0 / big;
         }
 
         for k in 0..n {
+            if cancelled() {
+                return Err(CANCELLED);
+            }
             let mut big = 0.0;
             let mut imax = k;
             for i in k..n {
@@ -50,6 +66,7 @@ impl LU {
             index[k] = imax as i32;
             if lu[k][k] == 0.0 {
                 lu[k][k] = tiny;
+                zero_pivot = true;
             }
             for i in (k + 1)..n {
                 lu[i][k] /= lu[k][k];
@@ -60,7 +77,14 @@ impl LU {
             }
         }
 
-        LU { n, lu, index, d }
+        Ok(LU {
+            n,
+            lu,
+            index,
+            d,
+            norm,
+            zero_pivot,
+        })
     }
 
     pub fn solve(&self, b: &VectorDouble, x: &mut VectorDouble) {
@@ -98,7 +122,7 @@ impl LU {
         }
     }
 
-    pub fn solve_matrix(&self, b: &MatrixDouble, x: &mut MatrixDouble) {
+    pub fn solve_matrix(&self, b: &MatrixDouble, x: &mut MatrixDouble) -> Result<(), &'static str> {
         if b.nrows() != self.n || x.nrows() != self.n || b.ncols() != x.ncols() {
             panic!("LU::solve bad sizes");
         }
@@ -106,6 +130,9 @@ impl LU {
         let mut xx = VectorDouble::new(self.n);
 
         for j in 0..b.ncols() {
+            if cancelled() {
+                return Err(CANCELLED);
+            }
             for i in 0..self.n {
                 xx[i] = b[i][j];
             }
@@ -117,9 +144,10 @@ impl LU {
                 }
             }
         }
+        Ok(())
     }
 
-    pub fn inverse(&self, ainv: &mut MatrixDouble) {
+    pub fn inverse(&self, ainv: &mut MatrixDouble) -> Result<(), &'static str> {
         ainv.resize(self.n, self.n);
         for i in 0..self.n {
             for j in 0..self.n {
@@ -128,8 +156,9 @@ impl LU {
             ainv[i][i] = 1.0;
         }
         let mut ainv_mut = ainv.clone();
-        self.solve_matrix(&ainv, &mut ainv_mut);
+        self.solve_matrix(ainv, &mut ainv_mut)?;
         *ainv = ainv_mut;
+        Ok(())
     }
 
     pub fn det(&self) -> f64 {
@@ -140,6 +169,17 @@ impl LU {
         dd
     }
 
+    /// True when a pivot is zero (and was replaced by a tiny value) or too small relative to
+    /// the matrix to give meaningful solutions, i.e. below n * eps * ||A||.
+    pub fn is_singular(&self) -> bool {
+        let tolerance = self.n as f64 * f64::EPSILON * self.norm;
+        self.zero_pivot || (0..self.n).any(|i| self.lu[i][i].abs() <= tolerance)
+    }
+
+    pub fn pivots(&self) -> Vec<usize> {
+        (0..self.n).map(|i| self.index[i] as usize).collect()
+    }
+
     pub fn lu_decomposition(&self, _b: &MatrixDouble, x: &mut MatrixDouble) {
         for i in 0..self.n {
             for j in 0..self.n {
@@ -149,3 +189,22 @@ impl LU {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lu(rows: &[[f64; 2]]) -> LU {
+        LU::new(&MatrixDouble::from_rows(rows).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn is_singular_looks_at_the_pivots() {
+        assert!(lu(&[[1.0, 2.0], [2.0, 4.0]]).is_singular());
+        assert!(lu(&[[0.1, 0.3], [0.2, 0.6]]).is_singular());
+        assert!(!lu(&[[4.0, 3.0], [6.0, 3.0]]).is_singular());
+        // Small but perfectly conditioned.
+        assert!(!lu(&[[1e-200, 0.0], [0.0, 1e-200]]).is_singular());
+        assert!(LU::new(&MatrixDouble::zeros(2, 2)).is_err());
+        assert!(LU::new(&MatrixDouble::from_slice(2, 1, &[1.0, 2.0])).is_err());
+    }
+}
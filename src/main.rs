@@ -1,15 +1,12 @@
-mod gui;
-mod lu_decomposition;
-mod math_utilities;
-mod matrix_operations;
-mod repl;
-
-use clap::{Arg, Command};
-use eframe::egui;
-use gui::NumerusApp;
+use clap::{Arg, ArgAction, Command};
+use numerus::output::OutputFormat;
+use numerus::{repl, script, server};
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read};
+use std::process;
 
 fn main() {
-    let matches = Command::new("Numerus")
+    let command = Command::new("Numerus")
         .version("0.1.0")
         .author("Your Name <your.email@example.com>")
         .about("Matrix operations in Rust")
@@ -21,23 +18,148 @@ fn main() {
                 .help("Sets the mode to run in (repl or gui)")
                 .default_value("repl"),
         )
-        .get_matches();
+        .arg(
+            Arg::new("eval")
+                .short('e')
+                .long("eval")
+                .value_name("STATEMENTS")
+                .help("Runs the given statements and exits"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Sets the output format (text or json)"),
+        )
+        .arg(
+            Arg::new("restore")
+                .long("restore")
+                .action(ArgAction::SetTrue)
+                .help("Restores the previous session on startup and saves it on exit"),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Runs a script of REPL statements")
+                .arg(Arg::new("script").required(true).value_name("FILE")),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serves JSON-RPC 2.0 requests, one JSON message per line")
+                .arg(
+                    Arg::new("stdio")
+                        .long("stdio")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("listen")
+                        .help("Reads requests from stdin and writes responses to stdout"),
+                )
+                .arg(
+                    Arg::new("listen")
+                        .long("listen")
+                        .value_name("ADDRESS")
+                        .help("Listens for TCP connections, e.g. 127.0.0.1:7878"),
+                )
+                .arg(
+                    Arg::new("allow-remote")
+                        .long("allow-remote")
+                        .action(ArgAction::SetTrue)
+                        .requires("listen")
+                        .help("Allows --listen on addresses other than loopback ones"),
+                ),
+        );
+    #[cfg(feature = "kernel")]
+    let command = command.subcommand(
+        Command::new("kernel")
+            .about("Runs as a Jupyter kernel")
+            .arg(
+                Arg::new("connection-file")
+                    .long("connection-file")
+                    .short('f')
+                    .required(true)
+                    .value_name("FILE")
+                    .help("Connection file written by Jupyter"),
+            ),
+    );
+    let matches = command.get_matches();
+
+    let restore = matches.get_flag("restore");
+    let format = match matches.get_one::<String>("output").map(|s| s.as_str()) {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Text,
+    };
+
+    if let Some(("run", run_matches)) = matches.subcommand() {
+        let path = run_matches.get_one::<String>("script").unwrap();
+        match std::fs::read_to_string(path) {
+            Ok(source) => run_batch(&source, path, format),
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                process::exit(2);
+            }
+        }
+    }
+
+    #[cfg(feature = "kernel")]
+    if let Some(("kernel", kernel_matches)) = matches.subcommand() {
+        let path = kernel_matches.get_one::<String>("connection-file").unwrap();
+        if let Err(err) = numerus::kernel::start_kernel(path) {
+            eprintln!("kernel: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if let Some(("serve", serve_matches)) = matches.subcommand() {
+        let result = match serve_matches.get_one::<String>("listen") {
+            Some(address) => server::serve_tcp(address, serve_matches.get_flag("allow-remote")),
+            None if serve_matches.get_flag("stdio") => server::serve_stdio(),
+            None => {
+                eprintln!("serve: expected --stdio or --listen ADDRESS");
+                process::exit(2);
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("serve: {}", err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if let Some(statements) = matches.get_one::<String>("eval") {
+        run_batch(statements, "-e", format);
+    }
 
     match matches
         .get_one::<String>("mode")
         .map(|s| s.as_str())
         .unwrap()
     {
+        #[cfg(feature = "gui")]
         "gui" => {
             let options = eframe::NativeOptions::default();
             eframe::run_native(
                 "Numerus",
                 options,
-                Box::new(|_cc| Box::new(NumerusApp::default())),
+                Box::new(move |_cc| Box::new(numerus::gui::NumerusApp::new(restore))),
             );
         }
+        #[cfg(not(feature = "gui"))]
+        "gui" => {
+            eprintln!("This build of numerus has no GUI. Rebuild it with the `gui` feature.");
+            process::exit(2);
+        }
+        "repl" if !std::io::stdin().is_terminal() => {
+            let mut source = String::new();
+            if let Err(err) = std::io::stdin().read_to_string(&mut source) {
+                eprintln!("<stdin>: {}", err);
+                process::exit(2);
+            }
+            run_batch(&source, "<stdin>", format);
+        }
         "repl" => {
-            repl::start_repl();
+            repl::start_repl(restore, format);
         }
         _ => {
             println!("Unknown mode. Use 'repl' or 'gui'.");
@@ -45,3 +167,15 @@ fn main() {
     }
 }
 
+fn run_batch(source: &str, name: &str, format: OutputFormat) -> ! {
+    let mut matrices = HashMap::new();
+    match script::run_source(source, name, &mut matrices, format) {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            if format == OutputFormat::Text {
+                eprintln!("{}", err);
+            }
+            process::exit(1);
+        }
+    }
+}
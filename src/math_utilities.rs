@@ -1,11 +1,9 @@
 use num_traits::Num;
 
-#[allow(dead_code)]
 pub fn sqr<T: Copy + std::ops::Mul<Output = T>>(a: T) -> T {
     a * a
 }
 
-#[allow(dead_code)]
 pub fn max<T: Copy + PartialOrd>(a: T, b: T) -> T {
     if b > a {
         b
@@ -14,7 +12,6 @@ pub fn max<T: Copy + PartialOrd>(a: T, b: T) -> T {
     }
 }
 
-#[allow(dead_code)]
 pub fn min<T: Copy + PartialOrd>(a: T, b: T) -> T {
     if b < a {
         b
@@ -23,7 +20,6 @@ pub fn min<T: Copy + PartialOrd>(a: T, b: T) -> T {
     }
 }
 
-#[allow(dead_code)]
 pub fn sign<T: Copy + PartialOrd + std::ops::Neg<Output = T> + Num>(a: T, b: T) -> T {
     if b >= T::zero() {
         if a >= T::zero() {
@@ -40,12 +36,11 @@ pub fn sign<T: Copy + PartialOrd + std::ops::Neg<Output = T> + Num>(a: T, b: T)
     }
 }
 
-#[allow(dead_code)]
 pub fn swap<T>(a: &mut T, b: &mut T) {
     std::mem::swap(a, b);
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Vector<T> {
     data: Vec<T>,
 }
@@ -60,7 +55,6 @@ impl<T> Vector<T> {
         }
     }
 
-    #[allow(dead_code)]
     pub fn with_value(size: usize, value: T) -> Vector<T>
     where
         T: Clone,
@@ -90,7 +84,6 @@ impl<T> Vector<T> {
         self.data.resize(new_size, T::default());
     }
 
-    #[allow(dead_code)]
     pub fn assign(&mut self, new_size: usize, value: T)
     where
         T: Clone,
@@ -117,7 +110,11 @@ impl<T> std::ops::IndexMut<usize> for Vector<T> {
     }
 }
 
-#[derive(Clone)]
+/// The most elements that constructors and loaders allocate for one matrix, so that a typo
+/// or a corrupt file is an error rather than an overflow or an out-of-memory abort.
+pub const MAX_ELEMENTS: usize = 100_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Matrix<T> {
     rows: usize,
     cols: usize,
@@ -136,7 +133,6 @@ where
         }
     }
 
-    #[allow(dead_code)]
     pub fn with_value(rows: usize, cols: usize, value: T) -> Matrix<T> {
         Matrix {
             rows,
@@ -162,7 +158,6 @@ where
         self.data.resize(new_rows * new_cols, T::default());
     }
 
-    #[allow(dead_code)]
     pub fn assign(&mut self, new_rows: usize, new_cols: usize, value: T)
     where
         T: Clone,
@@ -190,6 +185,73 @@ where
     }
 }
 
+impl<T> Matrix<T> {
+    /// Builds a matrix whose element (i, j) is `f(i, j)`.
+    pub fn from_fn<F>(rows: usize, cols: usize, mut f: F) -> Matrix<T>
+    where
+        F: FnMut(usize, usize) -> T,
+    {
+        let mut data = Vec::with_capacity(rows * cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                data.push(f(i, j));
+            }
+        }
+        Matrix { rows, cols, data }
+    }
+
+    /// Builds a matrix from equally long rows.
+    pub fn from_rows<R: AsRef<[T]>>(rows: &[R]) -> Result<Matrix<T>, &'static str>
+    where
+        T: Clone,
+    {
+        let cols = rows.first().map_or(0, |row| row.as_ref().len());
+        if rows.iter().any(|row| row.as_ref().len() != cols) {
+            return Err("All rows must have the same length");
+        }
+        Ok(Matrix::from_fn(rows.len(), cols, |i, j| {
+            rows[i].as_ref()[j].clone()
+        }))
+    }
+
+    /// Builds a matrix from equally long columns.
+    pub fn from_cols<C: AsRef<[T]>>(cols: &[C]) -> Result<Matrix<T>, &'static str>
+    where
+        T: Clone,
+    {
+        let rows = cols.first().map_or(0, |col| col.as_ref().len());
+        if cols.iter().any(|col| col.as_ref().len() != rows) {
+            return Err("All columns must have the same length");
+        }
+        Ok(Matrix::from_fn(rows, cols.len(), |i, j| {
+            cols[j].as_ref()[i].clone()
+        }))
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: Num + Copy,
+{
+    pub fn zeros(rows: usize, cols: usize) -> Matrix<T> {
+        Matrix::from_fn(rows, cols, |_, _| T::zero())
+    }
+
+    pub fn ones(rows: usize, cols: usize) -> Matrix<T> {
+        Matrix::from_fn(rows, cols, |_, _| T::one())
+    }
+
+    pub fn identity(n: usize) -> Matrix<T> {
+        Matrix::from_fn(n, n, |i, j| if i == j { T::one() } else { T::zero() })
+    }
+
+    /// Builds a square matrix with `diag` on the diagonal and zeros elsewhere.
+    pub fn from_diag(diag: &[T]) -> Matrix<T> {
+        let n = diag.len();
+        Matrix::from_fn(n, n, |i, j| if i == j { diag[i] } else { T::zero() })
+    }
+}
+
 impl<T> std::ops::Index<usize> for Matrix<T> {
     type Output = [T];
 
@@ -0,0 +1,331 @@
+use crate::cancel::{cancelled, CANCELLED};
+use crate::lu_decomposition::LU;
+use crate::math_utilities::MatrixDouble;
+use crate::matrix_operations::{add_matrices, multiply_matrices, subtract_matrices};
+
+const MAX_ITERATIONS: usize = 100;
+
+// Padé coefficients for the [13/13] approximant of exp(x) (Higham, 2005).
+const PADE_13: [f64; 14] = [
+    64764752532480000.0,
+    32382376266240000.0,
+    7771770303897600.0,
+    1187353796428800.0,
+    129060195264000.0,
+    10559470521600.0,
+    670442572800.0,
+    33522128640.0,
+    1323241920.0,
+    40840800.0,
+    960960.0,
+    16380.0,
+    182.0,
+    1.0,
+];
+
+// Largest 1-norm for which the [13/13] approximant is accurate to double precision.
+const THETA_13: f64 = 5.371920351148152;
+
+const MAX_SQUARINGS: i32 = 1100;
+
+fn scale(a: &MatrixDouble, factor: f64) -> MatrixDouble {
+    let mut result = a.clone();
+    for i in 0..a.nrows() {
+        for j in 0..a.ncols() {
+            result[i][j] *= factor;
+        }
+    }
+    result
+}
+
+fn one_norm(a: &MatrixDouble) -> f64 {
+    let mut norm: f64 = 0.0;
+    for j in 0..a.ncols() {
+        let mut sum = 0.0;
+        for i in 0..a.nrows() {
+            sum += a[i][j].abs();
+        }
+        norm = norm.max(sum);
+    }
+    norm
+}
+
+fn is_finite(a: &MatrixDouble) -> bool {
+    a.data().iter().all(|x| x.is_finite())
+}
+
+fn factor(a: &MatrixDouble) -> Result<LU, &'static str> {
+    if !is_finite(a) {
+        return Err("Matrix has infinite or NaN elements");
+    }
+    LU::new(a).map_err(|err| {
+        if err == CANCELLED {
+            err
+        } else {
+            "Matrix is singular"
+        }
+    })
+}
+
+fn inverse(a: &MatrixDouble) -> Result<MatrixDouble, &'static str> {
+    let lu = factor(a)?;
+    if lu.is_singular() {
+        return Err("Matrix is singular");
+    }
+    let mut result = MatrixDouble::new(a.nrows(), a.ncols());
+    lu.inverse(&mut result)?;
+    if !is_finite(&result) {
+        return Err("Matrix is singular");
+    }
+    Ok(result)
+}
+
+fn check_square(a: &MatrixDouble) -> Result<(), &'static str> {
+    if a.nrows() != a.ncols() {
+        return Err("Matrix must be square");
+    }
+    Ok(())
+}
+
+/// Matrix exponential computed by scaling and squaring with a [13/13] Padé approximant.
+pub fn expm(a: &MatrixDouble) -> Result<MatrixDouble, &'static str> {
+    check_square(a)?;
+    if !is_finite(a) {
+        return Err("Matrix has infinite or NaN elements");
+    }
+    let n = a.nrows();
+
+    // A finite norm is below 2^1024, which bounds the number of squarings.
+    let norm = one_norm(a);
+    let mut s = 0;
+    if norm > THETA_13 {
+        s = ((norm / THETA_13).log2().ceil() as i32).min(MAX_SQUARINGS);
+    }
+    let a = scale(a, 0.5f64.powi(s));
+
+    let ident = MatrixDouble::identity(n);
+    let a2 = multiply_matrices(&a, &a)?;
+    let a4 = multiply_matrices(&a2, &a2)?;
+    let a6 = multiply_matrices(&a4, &a2)?;
+    let b = &PADE_13;
+
+    let mut u_inner = add_matrices(&scale(&a6, b[13]), &scale(&a4, b[11]))?;
+    u_inner = add_matrices(&u_inner, &scale(&a2, b[9]))?;
+    let mut u = multiply_matrices(&a6, &u_inner)?;
+    u = add_matrices(&u, &scale(&a6, b[7]))?;
+    u = add_matrices(&u, &scale(&a4, b[5]))?;
+    u = add_matrices(&u, &scale(&a2, b[3]))?;
+    u = add_matrices(&u, &scale(&ident, b[1]))?;
+    let u = multiply_matrices(&a, &u)?;
+
+    let mut v_inner = add_matrices(&scale(&a6, b[12]), &scale(&a4, b[10]))?;
+    v_inner = add_matrices(&v_inner, &scale(&a2, b[8]))?;
+    let mut v = multiply_matrices(&a6, &v_inner)?;
+    v = add_matrices(&v, &scale(&a6, b[6]))?;
+    v = add_matrices(&v, &scale(&a4, b[4]))?;
+    v = add_matrices(&v, &scale(&a2, b[2]))?;
+    v = add_matrices(&v, &scale(&ident, b[0]))?;
+
+    // Solve (V - U) R = (V + U) for the Padé approximant R.
+    let p = add_matrices(&v, &u)?;
+    let q = subtract_matrices(&v, &u)?;
+    let lu = factor(&q)?;
+    let mut r = MatrixDouble::new(n, n);
+    lu.solve_matrix(&p, &mut r)?;
+
+    for _ in 0..s {
+        if cancelled() {
+            return Err(CANCELLED);
+        }
+        r = multiply_matrices(&r, &r)?;
+    }
+
+    if !is_finite(&r) {
+        return Err("Matrix exponential overflows");
+    }
+    Ok(r)
+}
+
+/// Principal matrix square root computed by the Denman–Beavers iteration.
+pub fn sqrtm(a: &MatrixDouble) -> Result<MatrixDouble, &'static str> {
+    check_square(a)?;
+    let n = a.nrows();
+
+    let mut y = a.clone();
+    let mut z = MatrixDouble::identity(n);
+
+    for _ in 0..MAX_ITERATIONS {
+        if cancelled() {
+            return Err(CANCELLED);
+        }
+        let y_inv = inverse(&y)?;
+        let z_inv = inverse(&z)?;
+        let y_next = scale(&add_matrices(&y, &z_inv)?, 0.5);
+        let z_next = scale(&add_matrices(&z, &y_inv)?, 0.5);
+
+        let change = one_norm(&subtract_matrices(&y_next, &y)?);
+        y = y_next;
+        z = z_next;
+
+        if !is_finite(&y) {
+            break;
+        }
+        if change <= 1e-14 * one_norm(&y).max(1.0) {
+            return Ok(y);
+        }
+    }
+
+    Err("Matrix square root did not converge")
+}
+
+/// Principal matrix logarithm computed by inverse scaling and squaring.
+pub fn logm(a: &MatrixDouble) -> Result<MatrixDouble, &'static str> {
+    check_square(a)?;
+    let n = a.nrows();
+    let ident = MatrixDouble::identity(n);
+
+    // Take square roots until A is close enough to the identity for the series to converge quickly.
+    let mut x = a.clone();
+    let mut k = 0;
+    while one_norm(&subtract_matrices(&x, &ident)?) > 0.25 {
+        if cancelled() {
+            return Err(CANCELLED);
+        }
+        if k == 64 {
+            return Err("Matrix logarithm did not converge");
+        }
+        x = sqrtm(&x)?;
+        k += 1;
+    }
+
+    // log(X) = 2 atanh(Z) with Z = (X - I)(X + I)^-1.
+    let z = multiply_matrices(
+        &subtract_matrices(&x, &ident)?,
+        &inverse(&add_matrices(&x, &ident)?)?,
+    )?;
+    let z2 = multiply_matrices(&z, &z)?;
+    let mut term = z.clone();
+    let mut sum = z;
+    for j in 1..MAX_ITERATIONS {
+        if cancelled() {
+            return Err(CANCELLED);
+        }
+        term = multiply_matrices(&term, &z2)?;
+        let contribution = scale(&term, 1.0 / (2 * j + 1) as f64);
+        sum = add_matrices(&sum, &contribution)?;
+        if one_norm(&contribution) <= 1e-17 * one_norm(&sum).max(1.0) {
+            break;
+        }
+    }
+
+    let result = scale(&sum, 2.0 * 2f64.powi(k));
+    if !is_finite(&result) {
+        return Err("Matrix logarithm is not defined for this matrix");
+    }
+    Ok(result)
+}
+
+/// Integer matrix power computed by repeated squaring. Negative powers invert the matrix first.
+pub fn powm_int(a: &MatrixDouble, p: i64) -> Result<MatrixDouble, &'static str> {
+    check_square(a)?;
+
+    let mut base = if p < 0 { inverse(a)? } else { a.clone() };
+    let mut exponent = p.unsigned_abs();
+    let mut result = MatrixDouble::identity(a.nrows());
+
+    while exponent > 0 {
+        if cancelled() {
+            return Err(CANCELLED);
+        }
+        if exponent & 1 == 1 {
+            result = multiply_matrices(&result, &base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = multiply_matrices(&base, &base)?;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Real matrix power. Integer exponents use repeated squaring, others use expm(p * logm(A)).
+pub fn powm(a: &MatrixDouble, p: f64) -> Result<MatrixDouble, &'static str> {
+    check_square(a)?;
+
+    if p.fract() == 0.0 && p.abs() <= i64::MAX as f64 {
+        return powm_int(a, p as i64);
+    }
+    if p == 0.5 {
+        return sqrtm(a);
+    }
+
+    expm(&scale(&logm(a)?, p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[[f64; 2]]) -> MatrixDouble {
+        MatrixDouble::from_rows(rows).unwrap()
+    }
+
+    fn assert_close(a: &MatrixDouble, b: &MatrixDouble, tol: f64) {
+        let diff = subtract_matrices(a, b).unwrap();
+        assert!(one_norm(&diff) <= tol, "{:?} != {:?}", a.data(), b.data());
+    }
+
+    #[test]
+    fn expm_matches_closed_form() {
+        // The eigenvalues are -1 and -2, so every element is a combination of e^-1 and e^-2.
+        let (e1, e2) = ((-1f64).exp(), (-2f64).exp());
+        let expected = matrix(&[
+            [2.0 * e1 - e2, e1 - e2],
+            [2.0 * e2 - 2.0 * e1, 2.0 * e2 - e1],
+        ]);
+        assert_close(
+            &expm(&matrix(&[[0.0, 1.0], [-2.0, -3.0]])).unwrap(),
+            &expected,
+            1e-14,
+        );
+        // A large norm goes through the squaring phase.
+        let rotation = expm(&matrix(&[[0.0, -30.0], [30.0, 0.0]])).unwrap();
+        let expected = matrix(&[[30f64.cos(), -30f64.sin()], [30f64.sin(), 30f64.cos()]]);
+        assert_close(&rotation, &expected, 1e-11);
+    }
+
+    #[test]
+    fn logm_inverts_expm() {
+        let a = matrix(&[[0.5, 0.2], [0.1, -0.3]]);
+        assert_close(&logm(&expm(&a).unwrap()).unwrap(), &a, 1e-13);
+        let b = matrix(&[[2.0, 1.0], [0.0, 3.0]]);
+        assert_close(&logm(&expm(&b).unwrap()).unwrap(), &b, 1e-12);
+    }
+
+    #[test]
+    fn sqrtm_and_powm_match_known_roots() {
+        let a = matrix(&[[33.0, 24.0], [48.0, 57.0]]);
+        let root = matrix(&[[5.0, 2.0], [4.0, 7.0]]);
+        assert_close(&sqrtm(&a).unwrap(), &root, 1e-12);
+        assert_close(&powm(&a, 0.5).unwrap(), &root, 1e-12);
+        assert_close(
+            &powm(&a, 1.5).unwrap(),
+            &multiply_matrices(&a, &root).unwrap(),
+            1e-9,
+        );
+        assert_close(&powm(&root, 2.0).unwrap(), &a, 1e-12);
+        let product = multiply_matrices(&powm(&root, -1.0).unwrap(), &root).unwrap();
+        assert_close(&product, &MatrixDouble::identity(2), 1e-14);
+    }
+
+    #[test]
+    fn singular_and_non_finite_input_is_an_error() {
+        assert!(sqrtm(&matrix(&[[0.0, 0.0], [0.0, 0.0]])).is_err());
+        assert!(powm(&matrix(&[[1.0, 2.0], [0.0, 0.0]]), -1.0).is_err());
+        assert!(expm(&matrix(&[[f64::INFINITY, 0.0], [0.0, 1.0]])).is_err());
+        assert!(expm(&matrix(&[[f64::NAN, 0.0], [0.0, 1.0]])).is_err());
+        assert!(expm(&matrix(&[[1e308, 0.0], [0.0, 1.0]])).is_err());
+        assert!(expm(&MatrixDouble::zeros(2, 3)).is_err());
+    }
+}
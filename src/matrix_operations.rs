@@ -1,3 +1,4 @@
+use crate::cancel::{cancelled, CANCELLED};
 use crate::math_utilities::MatrixDouble;
 
 pub fn add_matrices(a: &MatrixDouble, b: &MatrixDouble) -> Result<MatrixDouble, &'static str> {
@@ -37,6 +38,9 @@ pub fn multiply_matrices(a: &MatrixDouble, b: &MatrixDouble) -> Result<MatrixDou
 
     let mut result = MatrixDouble::new(a.nrows(), b.ncols());
     for i in 0..a.nrows() {
+        if cancelled() {
+            return Err(CANCELLED);
+        }
         for j in 0..b.ncols() {
             for k in 0..a.ncols() {
                 result[i][j] += a[i][k] * b[k][j];
@@ -0,0 +1,286 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Where the GUI keeps its notebook between runs when started with `--restore`.
+pub const LAST_NOTEBOOK_FILE: &str = "last_notebook.num";
+
+const CODE_MARKER: &str = "# %%";
+const MARKDOWN_MARKER: &str = "# %% [markdown]";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellKind {
+    Code,
+    Markdown,
+}
+
+/// A notebook cell. Outputs are not saved with the notebook, so documents diff cleanly and
+/// are re-created by running the cells.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub kind: CellKind,
+    pub source: String,
+}
+
+impl Cell {
+    pub fn code(source: impl Into<String>) -> Cell {
+        Cell {
+            kind: CellKind::Code,
+            source: source.into(),
+        }
+    }
+
+    pub fn markdown(source: impl Into<String>) -> Cell {
+        Cell {
+            kind: CellKind::Markdown,
+            source: source.into(),
+        }
+    }
+}
+
+fn push_cell(cells: &mut Vec<Cell>, kind: CellKind, lines: &[&str]) {
+    let source = lines.join("\n").trim_matches('\n').to_string();
+    if !source.trim().is_empty() {
+        cells.push(Cell { kind, source });
+    }
+}
+
+/// Parses a notebook. Notebooks are scripts in which `# %%` lines start code cells and
+/// `# %% [markdown]` lines start Markdown cells, whose lines are commented out with `#`, so
+/// `numerus run` executes a notebook as it stands. A plain script is a single code cell.
+pub fn parse_notebook(text: &str) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    let mut kind = CellKind::Code;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let marker = line.trim_end();
+        if marker == MARKDOWN_MARKER || marker == CODE_MARKER {
+            push_cell(&mut cells, kind, &lines);
+            lines.clear();
+            kind = if marker == MARKDOWN_MARKER {
+                CellKind::Markdown
+            } else {
+                CellKind::Code
+            };
+        } else if kind == CellKind::Markdown {
+            let line = line.strip_prefix('#').unwrap_or(line);
+            lines.push(line.strip_prefix(' ').unwrap_or(line));
+        } else {
+            lines.push(line);
+        }
+    }
+    push_cell(&mut cells, kind, &lines);
+    cells
+}
+
+pub fn format_notebook(cells: &[Cell]) -> String {
+    let mut text = String::new();
+    for (index, cell) in cells.iter().enumerate() {
+        if index > 0 {
+            text.push('\n');
+        }
+        match cell.kind {
+            CellKind::Code => {
+                text.push_str(CODE_MARKER);
+                text.push('\n');
+                text.push_str(&cell.source);
+                text.push('\n');
+            }
+            CellKind::Markdown => {
+                text.push_str(MARKDOWN_MARKER);
+                text.push('\n');
+                for line in cell.source.lines() {
+                    if line.is_empty() {
+                        text.push_str("#\n");
+                    } else {
+                        text.push_str(&format!("# {}\n", line));
+                    }
+                }
+            }
+        }
+    }
+    text
+}
+
+pub fn load_notebook<P: AsRef<Path>>(path: P) -> io::Result<Vec<Cell>> {
+    fs::read_to_string(path).map(|text| parse_notebook(&text))
+}
+
+pub fn save_notebook<P: AsRef<Path>>(path: P, cells: &[Cell]) -> io::Result<()> {
+    fs::write(path, format_notebook(cells))
+}
+
+/// Block-level Markdown, which is all that notes in a notebook need.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    Heading(usize, String),
+    Item(String),
+    Paragraph(String),
+    Code(String),
+}
+
+/// Splits Markdown into headings, bullet items, fenced code and paragraphs. Consecutive
+/// lines of text are joined into one paragraph.
+pub fn markdown_blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut code: Option<Vec<&str>> = None;
+
+    let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>| {
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(paragraph.join(" ")));
+            paragraph.clear();
+        }
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(lines) = code.as_mut() {
+            if trimmed.starts_with("```") {
+                blocks.push(Block::Code(lines.join("\n")));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if trimmed.starts_with("```") {
+            flush(&mut paragraph, &mut blocks);
+            code = Some(Vec::new());
+        } else if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Heading(level, trimmed[level..].trim().to_string()));
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Item(item.trim().to_string()));
+        } else if trimmed.is_empty() {
+            flush(&mut paragraph, &mut blocks);
+        } else {
+            paragraph.push(trimmed);
+        }
+    }
+    flush(&mut paragraph, &mut blocks);
+    if let Some(lines) = code {
+        blocks.push(Block::Code(lines.join("\n")));
+    }
+    blocks
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Exports cells and the output texts of their last run as a Markdown document.
+pub fn export_markdown(cells: &[(&Cell, Vec<&str>)]) -> String {
+    let mut text = String::new();
+    for (cell, outputs) in cells {
+        match cell.kind {
+            CellKind::Markdown => text.push_str(&format!("{}\n\n", cell.source)),
+            CellKind::Code => {
+                text.push_str(&format!("```matlab\n{}\n```\n\n", cell.source));
+                for output in outputs {
+                    text.push_str(&format!("```text\n{}\n```\n\n", output.trim_end()));
+                }
+            }
+        }
+    }
+    text
+}
+
+/// Exports cells and the output texts of their last run as a standalone HTML page.
+pub fn export_html(title: &str, cells: &[(&Cell, Vec<&str>)]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; }}\n\
+         pre {{ padding: 0.5em; overflow-x: auto; }}\n\
+         pre.input {{ background: #f0f0f0; }}\n\
+         pre.output {{ border-left: 3px solid #ccc; }}\n\
+         </style>\n</head>\n<body>\n",
+        escape_html(title)
+    );
+    for (cell, outputs) in cells {
+        match cell.kind {
+            CellKind::Markdown => {
+                let mut in_list = false;
+                for block in markdown_blocks(&cell.source) {
+                    let item = matches!(block, Block::Item(_));
+                    if item && !in_list {
+                        html.push_str("<ul>\n");
+                    } else if !item && in_list {
+                        html.push_str("</ul>\n");
+                    }
+                    in_list = item;
+                    html.push_str(&match block {
+                        Block::Heading(level, text) => {
+                            format!("<h{0}>{1}</h{0}>\n", level, escape_html(&text))
+                        }
+                        Block::Item(text) => format!("<li>{}</li>\n", escape_html(&text)),
+                        Block::Paragraph(text) => format!("<p>{}</p>\n", escape_html(&text)),
+                        Block::Code(text) => format!("<pre>{}</pre>\n", escape_html(&text)),
+                    });
+                }
+                if in_list {
+                    html.push_str("</ul>\n");
+                }
+            }
+            CellKind::Code => {
+                html.push_str(&format!(
+                    "<pre class=\"input\">{}</pre>\n",
+                    escape_html(&cell.source)
+                ));
+                for output in outputs {
+                    html.push_str(&format!(
+                        "<pre class=\"output\">{}</pre>\n",
+                        escape_html(output.trim_end())
+                    ));
+                }
+            }
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_notebooks_load_back() {
+        let cells = vec![
+            Cell::markdown("# Title\n\nSome *notes*:\n\n- one\n    indented"),
+            Cell::code("A = magic(3)\n\n% a comment\ndet(A)"),
+            Cell::markdown("#no space"),
+            Cell::code("B = A'"),
+        ];
+        let name = format!("numerus-notebook-{}.num", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        save_notebook(&path, &cells).unwrap();
+        let loaded = load_notebook(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, cells);
+    }
+
+    #[test]
+    fn markdown_is_commented_out() {
+        let cells = [Cell::markdown("Notes\n\nmore"), Cell::code("x = 1")];
+        assert_eq!(
+            format_notebook(&cells),
+            "# %% [markdown]\n# Notes\n#\n# more\n\n# %%\nx = 1\n"
+        );
+        // A plain script is one code cell, and empty cells are dropped.
+        assert_eq!(
+            parse_notebook("x = 1\ny = 2\n# %%\n\n# %% [markdown]\n"),
+            [Cell::code("x = 1\ny = 2")]
+        );
+    }
+}
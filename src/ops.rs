@@ -0,0 +1,4 @@
+pub use crate::matrix_functions::{expm, logm, powm, powm_int, sqrtm};
+pub use crate::matrix_operations::{add_matrices, multiply_matrices, subtract_matrices};
+pub use crate::random::{rand, randn, Rng};
+pub use crate::special_matrices::{eye, hilb, linspace, magic, vander};
@@ -0,0 +1,215 @@
+use crate::io::session::encode_number;
+use crate::math_utilities::{MatrixDouble, VectorDouble};
+use crate::plot::Plot;
+use serde_json::{json, Value as Json};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    None,
+    Scalar(f64),
+    Vector(VectorDouble),
+    Matrix(MatrixDouble),
+    // Packed LU factors and the row interchanged at each elimination step.
+    Lu { lu: MatrixDouble, pivots: Vec<usize> },
+    Plot(Plot),
+}
+
+/// Result of a successful command: the text shown to the user and the value it produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    pub text: String,
+    pub value: Value,
+}
+
+impl Output {
+    pub fn new(text: impl Into<String>, value: Value) -> Output {
+        Output {
+            text: text.into(),
+            value,
+        }
+    }
+
+    pub fn message(text: impl Into<String>) -> Output {
+        Output::new(text, Value::None)
+    }
+}
+
+pub fn matrix_to_json(matrix: &MatrixDouble) -> Json {
+    let rows: Vec<Json> = (0..matrix.nrows())
+        .map(|i| Json::Array(matrix[i].iter().map(|x| encode_number(*x)).collect()))
+        .collect();
+    json!({
+        "type": "matrix",
+        "dtype": "f64",
+        "shape": [matrix.nrows(), matrix.ncols()],
+        "data": rows,
+    })
+}
+
+pub fn plot_to_json(plot: &Plot) -> Json {
+    let series: Vec<Json> = plot
+        .series
+        .iter()
+        .map(|series| {
+            json!({
+                "name": series.name,
+                "x": series.x.iter().map(|x| encode_number(*x)).collect::<Vec<Json>>(),
+                "y": series.y.iter().map(|y| encode_number(*y)).collect::<Vec<Json>>(),
+            })
+        })
+        .collect();
+    json!({
+        "type": "plot",
+        "kind": plot.kind.name(),
+        "title": plot.title,
+        "xlabel": plot.x_label,
+        "ylabel": plot.y_label,
+        "logx": plot.log_x,
+        "logy": plot.log_y,
+        "series": series,
+        "matrix": plot.matrix.as_ref().map_or(Json::Null, matrix_to_json),
+    })
+}
+
+pub fn value_to_json(value: &Value) -> Json {
+    match value {
+        Value::None => Json::Null,
+        Value::Scalar(x) => json!({
+            "type": "scalar",
+            "dtype": "f64",
+            "shape": [],
+            "data": encode_number(*x),
+        }),
+        Value::Vector(v) => json!({
+            "type": "vector",
+            "dtype": "f64",
+            "shape": [v.size()],
+            "data": v.data().iter().map(|x| encode_number(*x)).collect::<Vec<Json>>(),
+        }),
+        Value::Matrix(m) => matrix_to_json(m),
+        Value::Lu { lu, pivots } => json!({
+            "type": "factorization",
+            "kind": "lu",
+            "shape": [lu.nrows(), lu.ncols()],
+            "lu": matrix_to_json(lu),
+            "pivots": pivots,
+        }),
+        Value::Plot(plot) => plot_to_json(plot),
+    }
+}
+
+pub fn output_to_json(statement: &str, output: &Output) -> Json {
+    json!({
+        "status": "ok",
+        "statement": statement,
+        "text": output.text.trim_end(),
+        "result": value_to_json(&output.value),
+    })
+}
+
+pub fn error_to_json(statement: &str, message: &str, location: Option<(&str, usize)>) -> Json {
+    let mut error = json!({
+        "status": "error",
+        "statement": statement,
+        "message": message.trim_end(),
+    });
+    if let Some((file, line)) = location {
+        error["file"] = json!(file);
+        error["line"] = json!(line);
+    }
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_have_a_fixed_json_shape() {
+        assert_eq!(value_to_json(&Value::None), Json::Null);
+        assert_eq!(
+            value_to_json(&Value::Scalar(f64::NAN)),
+            json!({ "type": "scalar", "dtype": "f64", "shape": [], "data": "NaN" })
+        );
+        assert_eq!(
+            value_to_json(&Value::Vector(VectorDouble::from_slice(&[
+                1.5,
+                f64::INFINITY,
+                f64::NEG_INFINITY
+            ]))),
+            json!({
+                "type": "vector",
+                "dtype": "f64",
+                "shape": [3],
+                "data": [1.5, "Inf", "-Inf"],
+            })
+        );
+        let matrix = MatrixDouble::from_slice(2, 2, &[1.0, 2.0, f64::NAN, -0.5]);
+        assert_eq!(
+            value_to_json(&Value::Matrix(matrix.clone())),
+            json!({
+                "type": "matrix",
+                "dtype": "f64",
+                "shape": [2, 2],
+                "data": [[1.0, 2.0], ["NaN", -0.5]],
+            })
+        );
+        assert_eq!(
+            value_to_json(&Value::Lu {
+                lu: matrix,
+                pivots: vec![1, 1],
+            }),
+            json!({
+                "type": "factorization",
+                "kind": "lu",
+                "shape": [2, 2],
+                "lu": {
+                    "type": "matrix",
+                    "dtype": "f64",
+                    "shape": [2, 2],
+                    "data": [[1.0, 2.0], ["NaN", -0.5]],
+                },
+                "pivots": [1, 1],
+            })
+        );
+        // Keys keep their order, and numbers their full precision, in the serialized text.
+        assert_eq!(
+            value_to_json(&Value::Scalar(0.1 + 0.2)).to_string(),
+            r#"{"type":"scalar","dtype":"f64","shape":[],"data":0.30000000000000004}"#
+        );
+    }
+
+    #[test]
+    fn outputs_and_errors() {
+        let output = Output::new("x =\n   2\n", Value::Scalar(2.0));
+        assert_eq!(
+            output_to_json("x = 1 + 1", &output),
+            json!({
+                "status": "ok",
+                "statement": "x = 1 + 1",
+                "text": "x =\n   2",
+                "result": { "type": "scalar", "dtype": "f64", "shape": [], "data": 2.0 },
+            })
+        );
+        assert_eq!(
+            error_to_json("inv(A)", "Matrix is singular\n", Some(("run.num", 4))),
+            json!({
+                "status": "error",
+                "statement": "inv(A)",
+                "message": "Matrix is singular",
+                "file": "run.num",
+                "line": 4,
+            })
+        );
+        assert_eq!(
+            error_to_json("B", "Matrix B is not defined.", None),
+            json!({ "status": "error", "statement": "B", "message": "Matrix B is not defined." })
+        );
+    }
+}
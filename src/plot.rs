@@ -0,0 +1,498 @@
+use crate::format::{format_number, NumberFormat};
+use crate::math_utilities::MatrixDouble;
+
+/// Size of terminal plots in characters, not counting the axes.
+pub const TEXT_WIDTH: usize = 60;
+pub const TEXT_HEIGHT: usize = 15;
+
+// Darkest to brightest, for image plots in the terminal.
+const SHADES: &[char] = &[' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlotKind {
+    Line,
+    Scatter,
+    /// Bars of the given width centred on the x values of the single series.
+    Histogram {
+        bin_width: f64,
+    },
+    /// Colour-mapped matrix, like MATLAB's `imagesc`.
+    Image,
+    /// Nonzero pattern of a matrix.
+    Spy,
+}
+
+impl PlotKind {
+    /// Name of the command that makes this kind of plot.
+    pub fn name(self) -> &'static str {
+        match self {
+            PlotKind::Line => "plot",
+            PlotKind::Scatter => "scatter",
+            PlotKind::Histogram { .. } => "hist",
+            PlotKind::Image => "imagesc",
+            PlotKind::Spy => "spy",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plot {
+    pub kind: PlotKind,
+    pub series: Vec<Series>,
+    /// Matrix shown by image and sparsity plots.
+    pub matrix: Option<MatrixDouble>,
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub log_x: bool,
+    pub log_y: bool,
+}
+
+impl Plot {
+    fn new(kind: PlotKind, series: Vec<Series>, matrix: Option<MatrixDouble>) -> Plot {
+        Plot {
+            kind,
+            series,
+            matrix,
+            title: String::new(),
+            x_label: String::new(),
+            y_label: String::new(),
+            log_x: false,
+            log_y: false,
+        }
+    }
+
+    pub fn lines(series: Vec<Series>) -> Plot {
+        Plot::new(PlotKind::Line, series, None)
+    }
+
+    pub fn scatter(series: Vec<Series>) -> Plot {
+        Plot::new(PlotKind::Scatter, series, None)
+    }
+
+    /// Counts the finite values in `bins` equal bins between their minimum and maximum.
+    pub fn histogram(name: &str, values: &[f64], bins: usize) -> Plot {
+        let finite: Vec<f64> = values.iter().copied().filter(|x| x.is_finite()).collect();
+        let min = finite.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = finite.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if finite.is_empty() {
+            (0.0, 1.0)
+        } else if min == max {
+            (min - 0.5, max + 0.5)
+        } else {
+            (min, max)
+        };
+
+        let bin_width = (max - min) / bins as f64;
+        let mut counts = vec![0.0; bins];
+        for x in finite {
+            let bin = (((x - min) / bin_width) as usize).min(bins - 1);
+            counts[bin] += 1.0;
+        }
+        let centers = (0..bins)
+            .map(|k| min + bin_width * (k as f64 + 0.5))
+            .collect();
+        let series = Series {
+            name: name.to_string(),
+            x: centers,
+            y: counts,
+        };
+        Plot::new(PlotKind::Histogram { bin_width }, vec![series], None)
+    }
+
+    pub fn image(matrix: MatrixDouble) -> Plot {
+        Plot::new(PlotKind::Image, Vec::new(), Some(matrix))
+    }
+
+    pub fn spy(matrix: MatrixDouble) -> Plot {
+        Plot::new(PlotKind::Spy, Vec::new(), Some(matrix))
+    }
+}
+
+/// Row and column of every nonzero element, row by row.
+pub fn nonzeros(matrix: &MatrixDouble) -> Vec<(usize, usize)> {
+    (0..matrix.nrows())
+        .flat_map(|i| (0..matrix.ncols()).map(move |j| (i, j)))
+        .filter(|&(i, j)| matrix[i][j] != 0.0)
+        .collect()
+}
+
+/// Smallest and largest finite elements, or `None` if there are none.
+pub fn finite_range(matrix: &MatrixDouble) -> Option<(f64, f64)> {
+    matrix
+        .data()
+        .iter()
+        .filter(|x| x.is_finite())
+        .fold(None, |range, &x| match range {
+            None => Some((x, x)),
+            Some((min, max)) => Some((f64::min(min, x), f64::max(max, x))),
+        })
+}
+
+/// Colour of `t` in [0, 1] on a viridis-like colour map.
+pub fn colormap(t: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 5] = [
+        [68.0, 1.0, 84.0],
+        [59.0, 82.0, 139.0],
+        [33.0, 145.0, 140.0],
+        [94.0, 201.0, 98.0],
+        [253.0, 231.0, 37.0],
+    ];
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let k = (t.floor() as usize).min(STOPS.len() - 2);
+    let f = t - k as f64;
+    let channel = |c: usize| (STOPS[k][c] + (STOPS[k + 1][c] - STOPS[k][c]) * f).round() as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+/// Position of a value on a linear or logarithmic axis; `None` for values that cannot be shown.
+pub fn axis_value(x: f64, log: bool) -> Option<f64> {
+    match (log, x.is_finite()) {
+        (true, true) if x > 0.0 => Some(x.log10()),
+        (false, true) => Some(x),
+        _ => None,
+    }
+}
+
+/// Tick label for a position on a linear or logarithmic axis.
+pub fn axis_label(position: f64, log: bool) -> String {
+    let value = if log { 10f64.powf(position) } else { position };
+    format_number(value, NumberFormat::Short)
+}
+
+// Unicode braille characters give a grid of 2x4 dots per character cell.
+struct Canvas {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            cells: vec![0; width * height],
+        }
+    }
+
+    fn dot_width(&self) -> usize {
+        self.width * 2
+    }
+
+    fn dot_height(&self) -> usize {
+        self.height * 4
+    }
+
+    // Sets the dot at (x, y) counted from the top left; dots off the canvas are ignored.
+    fn set(&mut self, x: i64, y: i64) {
+        const BITS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+        if x < 0 || y < 0 || x as usize >= self.dot_width() || y as usize >= self.dot_height() {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        self.cells[(y / 4) * self.width + x / 2] |= BITS[y % 4][x % 2];
+    }
+
+    // Bresenham's line algorithm.
+    fn line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64)) {
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+        loop {
+            self.set(x, y);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    fn rows(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.width)
+            .map(|row| {
+                row.iter()
+                    .map(|&bits| char::from_u32(0x2800 + bits as u32).unwrap_or(' '))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// Data range of one axis, widened when all values coincide.
+fn range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| {
+        (min.min(x), max.max(x))
+    });
+    if min > max {
+        (0.0, 1.0)
+    } else if min == max {
+        (min - 1.0, max + 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+fn centered(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.chars().count()) / 2;
+    format!("{}{}", " ".repeat(padding), text)
+}
+
+// Draws `rows` inside a left and bottom axis labelled with the data ranges.
+fn frame(plot: &Plot, rows: &[String], x_range: (f64, f64), y_range: (f64, f64)) -> String {
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    let top = axis_label(y_range.1, plot.log_y);
+    let bottom = axis_label(y_range.0, plot.log_y);
+    let margin = top.len().max(bottom.len());
+
+    let mut text = String::new();
+    if !plot.title.is_empty() {
+        text.push_str(&format!("{}\n", centered(&plot.title, margin + 2 + width)));
+    }
+    if !plot.y_label.is_empty() {
+        text.push_str(&format!("{}\n", plot.y_label));
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let (label, tick) = if i == 0 {
+            (top.as_str(), '┤')
+        } else if i + 1 == rows.len() {
+            (bottom.as_str(), '┤')
+        } else {
+            ("", '│')
+        };
+        text.push_str(&format!("{:>margin$} {}{}\n", label, tick, row));
+    }
+    text.push_str(&format!("{} └{}\n", " ".repeat(margin), "─".repeat(width)));
+
+    let left = axis_label(x_range.0, plot.log_x);
+    let right = axis_label(x_range.1, plot.log_x);
+    let gap = (width + 1).saturating_sub(left.len() + right.len()).max(1);
+    text.push_str(&format!(
+        "{}  {}{}{}\n",
+        " ".repeat(margin),
+        left,
+        " ".repeat(gap),
+        right
+    ));
+    if !plot.x_label.is_empty() {
+        text.push_str(&format!(
+            "{}{}\n",
+            " ".repeat(margin + 2),
+            centered(&plot.x_label, width)
+        ));
+    }
+    text
+}
+
+fn render_chart(plot: &Plot, width: usize, height: usize) -> String {
+    let points: Vec<Vec<Option<(f64, f64)>>> = plot
+        .series
+        .iter()
+        .map(|series| {
+            series
+                .x
+                .iter()
+                .zip(&series.y)
+                .map(|(&x, &y)| Some((axis_value(x, plot.log_x)?, axis_value(y, plot.log_y)?)))
+                .collect()
+        })
+        .collect();
+    let visible = || points.iter().flatten().flatten();
+
+    let (mut x_range, mut y_range) = (range(visible().map(|p| p.0)), range(visible().map(|p| p.1)));
+    if let PlotKind::Histogram { bin_width } = plot.kind {
+        x_range = (x_range.0 - bin_width / 2.0, x_range.1 + bin_width / 2.0);
+        y_range = (0.0, y_range.1);
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    let (dot_width, dot_height) = (canvas.dot_width() as f64, canvas.dot_height() as f64);
+    let to_dot = |(x, y): (f64, f64)| {
+        (
+            ((x - x_range.0) / (x_range.1 - x_range.0) * (dot_width - 1.0)).round() as i64,
+            ((y_range.1 - y) / (y_range.1 - y_range.0) * (dot_height - 1.0)).round() as i64,
+        )
+    };
+
+    for series in &points {
+        match plot.kind {
+            PlotKind::Line => {
+                for pair in series.windows(2) {
+                    match (pair[0], pair[1]) {
+                        (Some(a), Some(b)) => canvas.line(to_dot(a), to_dot(b)),
+                        (Some(a), None) => canvas.set(to_dot(a).0, to_dot(a).1),
+                        _ => {}
+                    }
+                }
+                if let [.., Some(last)] = series.as_slice() {
+                    let (x, y) = to_dot(*last);
+                    canvas.set(x, y);
+                }
+            }
+            PlotKind::Histogram { bin_width } => {
+                for &(x, count) in series.iter().flatten() {
+                    let (left, top) = to_dot((x - bin_width / 2.0, count));
+                    let (right, base) = to_dot((x + bin_width / 2.0, 0.0));
+                    // The right edge belongs to the next bar.
+                    for column in left..right.max(left + 1) {
+                        canvas.line((column, top), (column, base));
+                    }
+                }
+            }
+            _ => {
+                for &point in series.iter().flatten() {
+                    let (x, y) = to_dot(point);
+                    canvas.set(x, y);
+                }
+            }
+        }
+    }
+
+    let mut text = frame(plot, &canvas.rows(), x_range, y_range);
+    if plot.series.len() > 1 {
+        let names: Vec<&str> = plot.series.iter().map(|s| s.name.as_str()).collect();
+        text.push_str(&format!("Series: {}\n", names.join(", ")));
+    }
+    text
+}
+
+fn render_image(plot: &Plot, matrix: &MatrixDouble, width: usize, height: usize) -> String {
+    let Some((min, max)) = finite_range(matrix) else {
+        return format!("   [] ({}x{})\n", matrix.nrows(), matrix.ncols());
+    };
+    // Each element is two characters wide so that it comes out roughly square.
+    let cols = matrix.ncols().min(width / 2);
+    let rows = matrix.nrows().min(height);
+    let shade = |x: f64| {
+        if !x.is_finite() {
+            ' '
+        } else if max == min {
+            SHADES[SHADES.len() / 2]
+        } else {
+            SHADES[((x - min) / (max - min) * (SHADES.len() - 1) as f64).round() as usize]
+        }
+    };
+
+    let mut text = String::new();
+    if !plot.title.is_empty() {
+        text.push_str(&format!("{}\n", centered(&plot.title, cols * 2 + 2)));
+    }
+    text.push_str(&format!("┌{}┐\n", "─".repeat(cols * 2)));
+    for r in 0..rows {
+        let i = r * matrix.nrows() / rows;
+        let line: String = (0..cols)
+            .flat_map(|c| {
+                let shade = shade(matrix[i][c * matrix.ncols() / cols]);
+                [shade, shade]
+            })
+            .collect();
+        text.push_str(&format!("│{}│\n", line));
+    }
+    text.push_str(&format!("└{}┘\n", "─".repeat(cols * 2)));
+    text.push_str(&format!(
+        "{} {} {}\n",
+        format_number(min, NumberFormat::Short),
+        SHADES[1..].iter().collect::<String>(),
+        format_number(max, NumberFormat::Short)
+    ));
+    text
+}
+
+fn render_spy(plot: &Plot, matrix: &MatrixDouble, width: usize, height: usize) -> String {
+    let (nrows, ncols) = (matrix.nrows(), matrix.ncols());
+    let nonzeros = nonzeros(matrix);
+
+    let rows: Vec<String> = if nrows <= height && ncols * 2 <= width {
+        (0..nrows)
+            .map(|i| {
+                (0..ncols)
+                    .map(|j| if matrix[i][j] != 0.0 { "● " } else { "· " })
+                    .collect()
+            })
+            .collect()
+    } else {
+        let mut canvas = Canvas::new(width, height);
+        for &(i, j) in &nonzeros {
+            let x = j * canvas.dot_width() / ncols;
+            let y = i * canvas.dot_height() / nrows;
+            canvas.set(x as i64, y as i64);
+        }
+        canvas.rows()
+    };
+
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    let mut text = String::new();
+    if !plot.title.is_empty() {
+        text.push_str(&format!("{}\n", centered(&plot.title, width + 2)));
+    }
+    text.push_str(&format!("┌{}┐\n", "─".repeat(width)));
+    for row in &rows {
+        text.push_str(&format!("│{}│\n", row));
+    }
+    text.push_str(&format!("└{}┘\n", "─".repeat(width)));
+    text.push_str(&format!("{}x{}, nz = {}\n", nrows, ncols, nonzeros.len()));
+    text
+}
+
+/// Renders a plot for the terminal with braille and block characters, within `width` by
+/// `height` characters plus axes and labels.
+pub fn render_text(plot: &Plot, width: usize, height: usize) -> String {
+    match (&plot.kind, &plot.matrix) {
+        (PlotKind::Image, Some(matrix)) => render_image(plot, matrix, width, height),
+        (PlotKind::Spy, Some(matrix)) => render_spy(plot, matrix, width, height),
+        _ => render_chart(plot, width, height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_bins() {
+        let plot = Plot::histogram("v", &[0.0, 1.0, 1.5, 4.0, f64::NAN, f64::INFINITY], 4);
+        assert_eq!(plot.kind, PlotKind::Histogram { bin_width: 1.0 });
+        assert_eq!(plot.series[0].x, [0.5, 1.5, 2.5, 3.5]);
+        // The maximum falls in the last bin; NaN and Inf are not counted.
+        assert_eq!(plot.series[0].y, [1.0, 2.0, 0.0, 1.0]);
+
+        let constant = Plot::histogram("c", &[2.0, 2.0], 1);
+        assert_eq!(constant.series[0].x, [2.0]);
+        assert_eq!(constant.series[0].y, [2.0]);
+    }
+
+    #[test]
+    fn matrices_and_axes() {
+        let matrix = MatrixDouble::from_slice(2, 2, &[0.0, -1.0, f64::NAN, 0.0]);
+        assert_eq!(nonzeros(&matrix), [(0, 1), (1, 0)]);
+        assert_eq!(finite_range(&matrix), Some((-1.0, 0.0)));
+        assert_eq!(
+            finite_range(&MatrixDouble::from_slice(1, 1, &[f64::NAN])),
+            None
+        );
+
+        assert_eq!(axis_value(100.0, true), Some(2.0));
+        assert_eq!(axis_value(0.0, true), None);
+        assert_eq!(axis_value(-3.0, false), Some(-3.0));
+        assert_eq!(axis_value(f64::INFINITY, false), None);
+        assert_eq!(axis_label(2.0, true), "100");
+        assert_eq!(colormap(-1.0), [68, 1, 84]);
+        assert_eq!(colormap(1.0), [253, 231, 37]);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::math_utilities::MatrixDouble;
+
+// Advances a SplitMix64 state; used to expand a single seed into the generator state.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// xoshiro256** pseudo-random generator. The same seed always gives the same sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut s = seed;
+        Rng {
+            state: [
+                splitmix64(&mut s),
+                splitmix64(&mut s),
+                splitmix64(&mut s),
+                splitmix64(&mut s),
+            ],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        result
+    }
+
+    /// Uniformly distributed in [0, 1).
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, by the Box–Muller transform.
+    pub fn normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+pub fn rand(rows: usize, cols: usize, rng: &mut Rng) -> MatrixDouble {
+    MatrixDouble::from_fn(rows, cols, |_, _| rng.uniform())
+}
+
+pub fn randn(rows: usize, cols: usize, rng: &mut Rng) -> MatrixDouble {
+    MatrixDouble::from_fn(rows, cols, |_, _| rng.normal())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_gives_the_same_matrices() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        assert_eq!(rand(3, 4, &mut a), rand(3, 4, &mut b));
+        assert_eq!(randn(4, 3, &mut a), randn(4, 3, &mut b));
+        assert_ne!(rand(3, 4, &mut Rng::new(1)), rand(3, 4, &mut Rng::new(2)));
+        // Pinned, so that a change to the generator shows up as a failing test.
+        assert_eq!(Rng::new(0).next_u64(), 11091344671253066420);
+    }
+
+    #[test]
+    fn uniform_and_normal_have_the_expected_moments() {
+        let mut rng = Rng::new(7);
+        let n = 100_000;
+        let uniform = rand(1, n, &mut rng);
+        assert!(uniform.data().iter().all(|&x| (0.0..1.0).contains(&x)));
+        let mean = uniform.data().iter().sum::<f64>() / n as f64;
+        assert!((mean - 0.5).abs() < 0.01);
+
+        let normal = randn(1, n, &mut rng);
+        let mean = normal.data().iter().sum::<f64>() / n as f64;
+        let variance = normal
+            .data()
+            .iter()
+            .map(|x| (x - mean).powi(2))
+            .sum::<f64>()
+            / n as f64;
+        assert!(mean.abs() < 0.02 && (variance - 1.0).abs() < 0.02);
+    }
+}
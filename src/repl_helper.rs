@@ -0,0 +1,261 @@
+use crate::interpreter::{completion_candidates, find_function, FUNCTIONS};
+use crate::script::is_complete;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+
+const NUMBER_COLOR: &str = "\x1b[33m";
+const BRACKET_COLOR: &str = "\x1b[1;36m";
+const UNKNOWN_COLOR: &str = "\x1b[31m";
+const HINT_COLOR: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+// Words understood by the REPL or by scalar expressions besides functions and variables.
+const KEYWORDS: &[&str] = &[
+    "load", "save", "format", "disp", "help", "exit", "quit", "pi", "e", "sqrt", "exp", "ln",
+    "abs", "sin", "cos", "tan", "asin", "acos", "atan", "atan2", "sinh", "cosh", "tanh", "asinh",
+    "acosh", "atanh", "floor", "ceil", "round", "signum", "max", "min", "rat", "bmatrix", "pmatrix",
+    "title", "xlabel", "ylabel", "legend", "logx", "logy", "loglog",
+];
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Start of the identifier that ends at `pos`.
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .rfind(|c: char| !is_identifier_char(c))
+        .map(|index| index + 1)
+        .unwrap_or(0)
+}
+
+// File names and options after `load` and `save` are neither hinted nor highlighted.
+fn is_file_command(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("load ") || line.starts_with("save ")
+}
+
+/// Completion, signature hints, highlighting and multi-line input for the REPL.
+#[derive(Default)]
+pub struct NumerusHelper {
+    files: FilenameCompleter,
+    // Names of the workspace variables, refreshed after every command.
+    pub variables: Vec<String>,
+}
+
+impl NumerusHelper {
+    pub fn new() -> NumerusHelper {
+        NumerusHelper::default()
+    }
+
+    fn is_known(&self, word: &str) -> bool {
+        KEYWORDS.contains(&word)
+            || find_function(word).is_some()
+            || self.variables.iter().any(|name| name == word)
+    }
+}
+
+impl Completer for NumerusHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if line.trim_start().starts_with("load ") {
+            return self.files.complete(line, pos, ctx);
+        }
+
+        let start = word_start(line, pos);
+        let candidates = completion_candidates(&line[start..pos], &self.variables)
+            .into_iter()
+            .map(|name| Pair {
+                display: name.clone(),
+                replacement: name,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for NumerusHelper {
+    type Hint = String;
+
+    // Shows the rest of a function's signature while its name or arguments are being typed.
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() || is_file_command(line) {
+            return None;
+        }
+
+        let start = word_start(line, pos);
+        let word = &line[start..pos];
+        if !word.is_empty() {
+            let mut matches = FUNCTIONS
+                .iter()
+                .filter(|(name, _, _)| name.starts_with(word));
+            return match (matches.next(), matches.next()) {
+                (Some((_, signature, _)), None) => Some(signature[word.len()..].to_string()),
+                _ => None,
+            };
+        }
+
+        let name_end = line.strip_suffix('(')?.len();
+        let name = &line[word_start(line, name_end)..name_end];
+        find_function(name).map(|(signature, _)| signature[name.len() + 1..].to_string())
+    }
+}
+
+impl Highlighter for NumerusHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if is_file_command(line) {
+            return Cow::Borrowed(line);
+        }
+
+        let mut highlighted = String::with_capacity(line.len());
+        let mut changed = false;
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let mut end = start + c.len_utf8();
+            let color = if c.is_ascii_digit()
+                || (c == '.' && chars.peek().is_some_and(|(_, next)| next.is_ascii_digit()))
+            {
+                let mut previous = c;
+                while let Some(&(index, next)) = chars.peek() {
+                    let exponent_sign =
+                        (next == '+' || next == '-') && (previous == 'e' || previous == 'E');
+                    if !(next.is_ascii_digit()
+                        || next == '.'
+                        || next == 'e'
+                        || next == 'E'
+                        || exponent_sign)
+                    {
+                        break;
+                    }
+                    previous = next;
+                    end = index + next.len_utf8();
+                    chars.next();
+                }
+                Some(NUMBER_COLOR)
+            } else if c.is_alphabetic() || c == '_' {
+                while let Some(&(index, next)) = chars.peek() {
+                    if !is_identifier_char(next) {
+                        break;
+                    }
+                    end = index + next.len_utf8();
+                    chars.next();
+                }
+                let rest = line[end..].trim_start();
+                let assigned = rest.starts_with('=') && !rest.starts_with("==");
+                // The word being typed is not flagged until the cursor leaves it.
+                if assigned || end == pos || self.is_known(&line[start..end]) {
+                    None
+                } else {
+                    Some(UNKNOWN_COLOR)
+                }
+            } else if "[]()".contains(c) {
+                Some(BRACKET_COLOR)
+            } else {
+                None
+            };
+
+            match color {
+                Some(color) => {
+                    changed = true;
+                    highlighted.push_str(color);
+                    highlighted.push_str(&line[start..end]);
+                    highlighted.push_str(RESET);
+                }
+                None => highlighted.push_str(&line[start..end]),
+            }
+        }
+
+        if changed {
+            Cow::Owned(highlighted)
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{}{}{}", HINT_COLOR, hint, RESET))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Validator for NumerusHelper {
+    // Keeps reading lines while a matrix literal or call is still open.
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_complete(ctx.input()) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+impl Helper for NumerusHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::History;
+
+    fn helper() -> NumerusHelper {
+        NumerusHelper {
+            variables: vec!["mass".to_string(), "A".to_string()],
+            ..NumerusHelper::default()
+        }
+    }
+
+    #[test]
+    fn completes_functions_then_variables() {
+        let history = History::new();
+        let (start, pairs) = helper()
+            .complete("x = ma", 6, &Context::new(&history))
+            .unwrap();
+        let names: Vec<&str> = pairs.iter().map(|pair| pair.replacement.as_str()).collect();
+        assert_eq!(start, 4);
+        assert_eq!(names, ["magic", "markdown", "mass"]);
+    }
+
+    #[test]
+    fn hints_the_rest_of_a_signature() {
+        let history = History::new();
+        let ctx = Context::new(&history);
+        let helper = helper();
+        assert_eq!(
+            helper.hint("lins", 4, &ctx).as_deref(),
+            Some("pace(a, b, n)")
+        );
+        assert_eq!(helper.hint("x = inv(", 8, &ctx).as_deref(), Some("A)"));
+        // Several functions start with `m`, and the cursor must be at the end of the line.
+        assert_eq!(helper.hint("m", 1, &ctx), None);
+        assert_eq!(helper.hint("lins", 2, &ctx), None);
+        assert_eq!(helper.hint("save lins", 9, &ctx), None);
+    }
+
+    #[test]
+    fn highlights_numbers_brackets_and_unknown_names() {
+        let line = "A = [1 2.5e-3] + foo";
+        let expected = format!(
+            "A = {b}[{r}{n}1{r} {n}2.5e-3{r}{b}]{r} + {u}foo{r}",
+            b = BRACKET_COLOR,
+            n = NUMBER_COLOR,
+            u = UNKNOWN_COLOR,
+            r = RESET
+        );
+        assert_eq!(helper().highlight(line, 0), expected);
+        // The word under the cursor is still being typed.
+        assert!(!helper().highlight(line, line.len()).contains(UNKNOWN_COLOR));
+        assert_eq!(helper().highlight("save out.csv", 0), "save out.csv");
+    }
+}
@@ -0,0 +1,207 @@
+use crate::math_utilities::MatrixDouble;
+use crate::output::{error_to_json, output_to_json, OutputFormat};
+use crate::interpreter::evaluate_command;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub text: String,
+    pub line: usize,
+    // Set when the statement ends with `;`, which suppresses its output.
+    pub silent: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(['#', '%']) {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+fn strip_continuation(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    line.strip_suffix("...").or_else(|| line.strip_suffix('\\'))
+}
+
+/// Splits source text into statements. `#` and `%` start comments, a trailing `...` or `\`
+/// or an unclosed bracket continues a line, and `;` outside brackets and parentheses ends a
+/// statement silently.
+pub fn split_statements(source: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 0;
+    let mut depth: i32 = 0;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut line = strip_comment(raw_line);
+        let continued = match strip_continuation(line) {
+            Some(rest) => {
+                line = rest;
+                true
+            }
+            None => false,
+        };
+
+        for c in line.chars() {
+            if current.trim().is_empty() {
+                start_line = line_number;
+            }
+            match c {
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth -= 1,
+                _ => {}
+            }
+            if c == ';' && depth <= 0 {
+                push_statement(&mut statements, &mut current, start_line, true);
+                depth = 0;
+            } else {
+                current.push(c);
+            }
+        }
+
+        if continued {
+            current.push(' ');
+        } else if depth > 0 {
+            // An unclosed `[` or `(` carries the statement over to the next line.
+            current.push('\n');
+        } else {
+            push_statement(&mut statements, &mut current, start_line, false);
+            depth = 0;
+        }
+    }
+    push_statement(&mut statements, &mut current, start_line, false);
+
+    statements
+}
+
+/// Returns false while `source` still has an unclosed `[` or `(`, or ends with a continuation.
+pub fn is_complete(source: &str) -> bool {
+    let mut depth: i32 = 0;
+    let mut continued = false;
+    for raw_line in source.lines() {
+        let line = strip_comment(raw_line);
+        continued = strip_continuation(line).is_some();
+        for c in line.chars() {
+            match c {
+                '[' | '(' => depth += 1,
+                ']' | ')' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    depth <= 0 && !continued
+}
+
+fn push_statement(
+    statements: &mut Vec<Statement>,
+    current: &mut String,
+    line: usize,
+    silent: bool,
+) {
+    let text = current.trim();
+    if !text.is_empty() {
+        statements.push(Statement {
+            text: text.to_string(),
+            line,
+            silent,
+        });
+    }
+    current.clear();
+}
+
+/// Executes every statement in `source`, printing the output of those not ended by `;`.
+/// Stops at the first failing statement or at `exit`. In JSON mode every result, and the
+/// error that stopped the script, is printed as one JSON object per line.
+pub fn run_source(
+    source: &str,
+    file: &str,
+    matrices: &mut HashMap<String, MatrixDouble>,
+    format: OutputFormat,
+) -> Result<(), ScriptError> {
+    for statement in split_statements(source) {
+        if statement.text == "exit" || statement.text == "quit" {
+            break;
+        }
+        match evaluate_command(&statement.text, matrices) {
+            Ok(output) => {
+                if !statement.silent {
+                    match format {
+                        OutputFormat::Text => println!("{}", output.text.trim_end()),
+                        OutputFormat::Json => {
+                            println!("{}", output_to_json(&statement.text, &output))
+                        }
+                    }
+                }
+            }
+            Err(message) => {
+                if format == OutputFormat::Json {
+                    println!(
+                        "{}",
+                        error_to_json(&statement.text, &message, Some((file, statement.line)))
+                    );
+                }
+                return Err(ScriptError {
+                    file: file.to_string(),
+                    line: statement.line,
+                    message,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Result<(), ScriptError> {
+        run_source(source, "test.num", &mut HashMap::new(), OutputFormat::Text)
+    }
+
+    #[test]
+    fn non_square_input_is_an_error_at_its_line() {
+        for (statement, message) in [
+            (
+                "det([1;2])",
+                "Matrix [1;2] is not square and has no determinant.",
+            ),
+            (
+                "solve([1 2 3;4 5 6],[1;2])",
+                "Matrix [1 2 3;4 5 6] is not square, so the system has no unique solution.",
+            ),
+            (
+                "lu_decomposition([1 2 3])",
+                "Matrix [1 2 3] is not square and cannot be factored.",
+            ),
+        ] {
+            let error = run(&format!("A = [1 2; 3 4];\n\n{}\nB = A;", statement)).unwrap_err();
+            assert_eq!(
+                error,
+                ScriptError {
+                    file: "test.num".to_string(),
+                    line: 3,
+                    message: message.to_string(),
+                }
+            );
+            assert_eq!(error.to_string(), format!("test.num:3: {}", message));
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::io::session::decode_number;
+use crate::math_utilities::MatrixDouble;
+use crate::output::{matrix_to_json, output_to_json, Output};
+use crate::interpreter::{evaluate_command, is_identifier, with_settings, Settings};
+use crate::script::split_statements;
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const EVALUATION_ERROR: i64 = -32000;
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Json>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+fn error_response(id: Json, error: RpcError) -> Json {
+    let mut body = json!({ "code": error.code, "message": error.message });
+    if let Some(data) = error.data {
+        body["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": body })
+}
+
+/// Accepts either `{"shape": [r, c], "data": [[...], ...]}` or a bare list of rows.
+fn matrix_from_json(value: &Json) -> Result<MatrixDouble, String> {
+    let rows = match value.get("data") {
+        Some(data) => data,
+        None => value,
+    };
+    let rows = rows
+        .as_array()
+        .ok_or_else(|| "matrix must be a list of rows".to_string())?;
+
+    let mut data = Vec::new();
+    let mut ncols = None;
+    for row in rows {
+        let row: Vec<&Json> = match row.as_array() {
+            Some(row) => row.iter().collect(),
+            None => vec![row],
+        };
+        if ncols.is_some_and(|n| n != row.len()) {
+            return Err("All rows must have the same number of columns".to_string());
+        }
+        ncols = Some(row.len());
+        for x in row {
+            data.push(decode_number(x).ok_or_else(|| format!("invalid number {}", x))?);
+        }
+    }
+
+    Ok(MatrixDouble::from_slice(
+        rows.len(),
+        ncols.unwrap_or(0),
+        &data,
+    ))
+}
+
+fn string_param<'a>(params: &'a Json, key: &str) -> Result<&'a str, RpcError> {
+    params.get(key).and_then(Json::as_str).ok_or_else(|| {
+        RpcError::new(
+            INVALID_PARAMS,
+            format!("missing string parameter '{}'", key),
+        )
+    })
+}
+
+type Evaluate = fn(&str, &mut HashMap<String, MatrixDouble>) -> Result<Output, String>;
+
+/// One interpreter workspace, with its own display format and random generator. Each stdio
+/// process or TCP connection gets its own session.
+pub struct Session {
+    matrices: HashMap<String, MatrixDouble>,
+    settings: Settings,
+    evaluate: Evaluate,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session {
+            matrices: HashMap::new(),
+            settings: Settings::default(),
+            evaluate: evaluate_command,
+        }
+    }
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    fn call(&mut self, method: &str, params: &Json) -> Result<Json, RpcError> {
+        match method {
+            "eval" => {
+                let code = string_param(params, "code")?;
+                let mut outputs = Vec::new();
+                for statement in split_statements(code) {
+                    // A panicking kernel must not take the session, or the server, down.
+                    let (evaluate, matrices) = (self.evaluate, &mut self.matrices);
+                    let result = with_settings(&mut self.settings, || {
+                        panic::catch_unwind(AssertUnwindSafe(|| {
+                            evaluate(&statement.text, matrices)
+                                .map(|output| output_to_json(&statement.text, &output))
+                        }))
+                        .unwrap_or_else(|_| {
+                            Err("Internal error while evaluating the statement.".to_string())
+                        })
+                    });
+                    match result {
+                        Ok(output) => {
+                            if !statement.silent {
+                                outputs.push(output);
+                            }
+                        }
+                        Err(message) => {
+                            return Err(RpcError {
+                                code: EVALUATION_ERROR,
+                                message: message.trim_end().to_string(),
+                                data: Some(json!({
+                                    "line": statement.line,
+                                    "statement": statement.text,
+                                    "outputs": outputs,
+                                })),
+                            })
+                        }
+                    }
+                }
+                Ok(json!({ "outputs": outputs }))
+            }
+            "set_variable" => {
+                let name = string_param(params, "name")?;
+                if !is_identifier(name) {
+                    return Err(RpcError::new(
+                        INVALID_PARAMS,
+                        format!("Invalid variable name: {}", name),
+                    ));
+                }
+                let value = params
+                    .get("value")
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing parameter 'value'"))?;
+                let matrix =
+                    matrix_from_json(value).map_err(|err| RpcError::new(INVALID_PARAMS, err))?;
+                let result = matrix_to_json(&matrix);
+                self.matrices.insert(name.to_string(), matrix);
+                Ok(result)
+            }
+            "get_variable" => {
+                let name = string_param(params, "name")?;
+                self.matrices.get(name).map(matrix_to_json).ok_or_else(|| {
+                    RpcError::new(EVALUATION_ERROR, format!("Matrix {} is not defined.", name))
+                })
+            }
+            "list_variables" => {
+                let mut names: Vec<&String> = self.matrices.keys().collect();
+                names.sort();
+                Ok(Json::Array(
+                    names
+                        .into_iter()
+                        .map(|name| {
+                            let matrix = &self.matrices[name];
+                            json!({
+                                "name": name,
+                                "type": "matrix",
+                                "dtype": "f64",
+                                "shape": [matrix.nrows(), matrix.ncols()],
+                            })
+                        })
+                        .collect(),
+                ))
+            }
+            "reset" => {
+                self.matrices.clear();
+                self.settings = Settings::default();
+                Ok(Json::Null)
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("method '{}' not found", method),
+            )),
+        }
+    }
+
+    // Returns `None` for notifications, which get no response.
+    fn handle_request(&mut self, request: &Json) -> Option<Json> {
+        let id = request.get("id").cloned();
+        let method = request.get("method").and_then(Json::as_str);
+        if request.get("jsonrpc").and_then(Json::as_str) != Some("2.0") || method.is_none() {
+            return Some(error_response(
+                id.unwrap_or(Json::Null),
+                RpcError::new(INVALID_REQUEST, "invalid request"),
+            ));
+        }
+
+        let params = request.get("params").cloned().unwrap_or(Json::Null);
+        let result = self.call(method.unwrap(), &params);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_response(id, error),
+        })
+    }
+
+    /// Handles one line of input, which may hold a single request or a batch.
+    pub fn handle_line(&mut self, line: &str) -> Option<String> {
+        let request: Json = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => {
+                return Some(
+                    error_response(Json::Null, RpcError::new(PARSE_ERROR, err.to_string()))
+                        .to_string(),
+                )
+            }
+        };
+
+        let response = match request {
+            Json::Array(batch) if batch.is_empty() => Some(error_response(
+                Json::Null,
+                RpcError::new(INVALID_REQUEST, "empty batch"),
+            )),
+            Json::Array(batch) => {
+                let responses: Vec<Json> = batch
+                    .iter()
+                    .filter_map(|request| self.handle_request(request))
+                    .collect();
+                (!responses.is_empty()).then_some(Json::Array(responses))
+            }
+            request => self.handle_request(&request),
+        };
+        response.map(|response| response.to_string())
+    }
+}
+
+/// Serves newline-delimited JSON-RPC requests until the input is closed.
+pub fn serve<R: BufRead, W: Write>(reader: R, mut writer: W) -> io::Result<()> {
+    let mut session = Session::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = session.handle_line(&line) {
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+pub fn serve_stdio() -> io::Result<()> {
+    serve(io::stdin().lock(), io::stdout().lock())
+}
+
+fn serve_connection(stream: TcpStream) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    serve(reader, stream)
+}
+
+/// Listens on `address`. The server has no authentication, so addresses other than loopback
+/// ones are refused unless `allow_remote` is set.
+pub fn serve_tcp(address: &str, allow_remote: bool) -> io::Result<()> {
+    let addresses: Vec<_> = address.to_socket_addrs()?.collect();
+    if !allow_remote && addresses.iter().any(|address| !address.ip().is_loopback()) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "refusing to listen on {}, which is not a loopback address; \
+                 pass --allow-remote to accept connections from other machines",
+                address
+            ),
+        ));
+    }
+    let listener = TcpListener::bind(&addresses[..])?;
+    if !listener.local_addr()?.ip().is_loopback() {
+        eprintln!("Warning: anyone who can reach this address can run code in the server.");
+    }
+    eprintln!(
+        "Numerus JSON-RPC server listening on {}",
+        listener.local_addr()?
+    );
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            if let Err(err) = serve_connection(stream) {
+                eprintln!("Connection error: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(session: &mut Session, request: Json) -> Json {
+        let response = session.handle_line(&request.to_string()).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn panicking_evaluation_is_an_error_response() {
+        let mut session = Session::new();
+        session.evaluate = |command, matrices| match command {
+            "boom" => panic!("kernel bug"),
+            _ => evaluate_command(command, matrices),
+        };
+        let code = "A = eye(2); boom";
+        let response = call(
+            &mut session,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "eval", "params": {"code": code}}),
+        );
+        assert_eq!(response["error"]["code"], EVALUATION_ERROR);
+        // The session survives, with the statements before the panic applied.
+        let response = call(
+            &mut session,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "list_variables"}),
+        );
+        assert_eq!(response["result"][0]["name"], "A");
+    }
+
+    #[test]
+    fn set_variable_rejects_invalid_names() {
+        let mut session = Session::new();
+        let params = json!({"name": "1bad", "value": [[1.0]]});
+        let response = call(
+            &mut session,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "set_variable", "params": params}),
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn sessions_have_their_own_settings() {
+        let eval = |session: &mut Session, code: &str| {
+            let params = json!({"code": code});
+            call(
+                session,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "eval", "params": params}),
+            )["result"]["outputs"]
+                .clone()
+        };
+        let mut first = Session::new();
+        let mut second = Session::new();
+        eval(&mut first, "format long; rng(7)");
+        let drawn = eval(&mut first, "rand(2)")[0]["result"].clone();
+        assert_eq!(eval(&mut second, "pi"), eval(&mut Session::new(), "pi"));
+        assert_ne!(eval(&mut first, "pi"), eval(&mut second, "pi"));
+        eval(&mut second, "rng(7)");
+        assert_eq!(eval(&mut second, "rand(2)")[0]["result"], drawn);
+    }
+
+    #[test]
+    fn remote_addresses_need_a_flag() {
+        let error = serve_tcp("0.0.0.0:0", false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::math_utilities::MatrixDouble;
+
+/// Rectangular identity: ones on the main diagonal, zeros elsewhere.
+pub fn eye(rows: usize, cols: usize) -> MatrixDouble {
+    MatrixDouble::from_fn(rows, cols, |i, j| if i == j { 1.0 } else { 0.0 })
+}
+
+/// `n` evenly spaced points from `a` to `b` inclusive, as a row vector.
+pub fn linspace(a: f64, b: f64, n: usize) -> MatrixDouble {
+    let step = if n > 1 { (b - a) / (n - 1) as f64 } else { 0.0 };
+    MatrixDouble::from_fn(
+        1,
+        n,
+        |_, j| {
+            if j + 1 == n {
+                b
+            } else {
+                a + step * j as f64
+            }
+        },
+    )
+}
+
+/// Hilbert matrix, H(i, j) = 1 / (i + j + 1) counting from zero.
+pub fn hilb(n: usize) -> MatrixDouble {
+    MatrixDouble::from_fn(n, n, |i, j| 1.0 / (i + j + 1) as f64)
+}
+
+/// Vandermonde matrix whose columns are decreasing powers of `v`.
+pub fn vander(v: &[f64]) -> MatrixDouble {
+    let n = v.len();
+    MatrixDouble::from_fn(n, n, |i, j| v[i].powi((n - 1 - j) as i32))
+}
+
+/// Magic square of order `n`: every row, column and diagonal has the same sum. Uses the
+/// same construction as MATLAB, so the results match element for element.
+pub fn magic(n: usize) -> MatrixDouble {
+    if n % 2 == 1 {
+        // Siamese method.
+        MatrixDouble::from_fn(n, n, |i, j| {
+            let a = (i + j + n - (n - 1) / 2) % n;
+            let b = (i + 2 * j + 1) % n;
+            (n * a + b + 1) as f64
+        })
+    } else if n.is_multiple_of(4) {
+        // Reverse the numbering on cells whose row and column fall in the same half of their
+        // 4x4 block pattern.
+        MatrixDouble::from_fn(n, n, |i, j| {
+            let value = i * n + j + 1;
+            if ((i + 1) % 4) / 2 == ((j + 1) % 4) / 2 {
+                (n * n + 1 - value) as f64
+            } else {
+                value as f64
+            }
+        })
+    } else {
+        // LUX method: four copies of a half-size odd square, then swap some columns.
+        let p = n / 2;
+        let half = magic(p);
+        let offset = [[0, 2], [3, 1]];
+        let mut m = MatrixDouble::from_fn(n, n, |i, j| {
+            half[i % p][j % p] + (offset[i / p][j / p] * p * p) as f64
+        });
+
+        let k = (n - 2) / 4;
+        let mut columns: Vec<usize> = (0..k).collect();
+        columns.extend(n - k + 1..n);
+        for i in 0..p {
+            for &j in &columns {
+                let upper = m[i][j];
+                m[i][j] = m[i + p][j];
+                m[i + p][j] = upper;
+            }
+        }
+        // Columns 0 and k, which coincide for n = 2; MATLAB swaps that column once.
+        let middle = if k == 0 { vec![0] } else { vec![0, k] };
+        for j in middle {
+            let upper = m[k][j];
+            m[k][j] = m[k + p][j];
+            m[k + p][j] = upper;
+        }
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[f64]]) -> MatrixDouble {
+        let data: Vec<f64> = rows.iter().flat_map(|row| row.iter().copied()).collect();
+        MatrixDouble::from_slice(rows.len(), rows[0].len(), &data)
+    }
+
+    #[test]
+    fn magic_matches_matlab() {
+        assert_eq!(magic(1), matrix(&[&[1.0]]));
+        assert_eq!(magic(2), matrix(&[&[4.0, 3.0], &[1.0, 2.0]]));
+        assert_eq!(
+            magic(3),
+            matrix(&[&[8.0, 1.0, 6.0], &[3.0, 5.0, 7.0], &[4.0, 9.0, 2.0]])
+        );
+        assert_eq!(
+            magic(4),
+            matrix(&[
+                &[16.0, 2.0, 3.0, 13.0],
+                &[5.0, 11.0, 10.0, 8.0],
+                &[9.0, 7.0, 6.0, 12.0],
+                &[4.0, 14.0, 15.0, 1.0],
+            ])
+        );
+        assert_eq!(
+            magic(6),
+            matrix(&[
+                &[35.0, 1.0, 6.0, 26.0, 19.0, 24.0],
+                &[3.0, 32.0, 7.0, 21.0, 23.0, 25.0],
+                &[31.0, 9.0, 2.0, 22.0, 27.0, 20.0],
+                &[8.0, 28.0, 33.0, 17.0, 10.0, 15.0],
+                &[30.0, 5.0, 34.0, 12.0, 14.0, 16.0],
+                &[4.0, 36.0, 29.0, 13.0, 18.0, 11.0],
+            ])
+        );
+    }
+
+    #[test]
+    fn magic_squares_have_equal_sums() {
+        for n in [3, 5, 6, 7, 8, 10, 12, 14] {
+            let m = magic(n);
+            let sum = (n * (n * n + 1) / 2) as f64;
+            for i in 0..n {
+                assert_eq!(m[i].iter().sum::<f64>(), sum, "row {} of magic({})", i, n);
+                assert_eq!((0..n).map(|j| m[j][i]).sum::<f64>(), sum);
+            }
+            assert_eq!((0..n).map(|i| m[i][i]).sum::<f64>(), sum);
+            assert_eq!((0..n).map(|i| m[i][n - 1 - i]).sum::<f64>(), sum);
+            let mut values = m.data().to_vec();
+            values.sort_by(f64::total_cmp);
+            assert!(values.iter().enumerate().all(|(i, &x)| x == (i + 1) as f64));
+        }
+    }
+
+    #[test]
+    fn hilb_and_vander() {
+        assert_eq!(
+            hilb(3),
+            matrix(&[
+                &[1.0, 1.0 / 2.0, 1.0 / 3.0],
+                &[1.0 / 2.0, 1.0 / 3.0, 1.0 / 4.0],
+                &[1.0 / 3.0, 1.0 / 4.0, 1.0 / 5.0],
+            ])
+        );
+        assert_eq!(
+            vander(&[2.0, 3.0, -1.0]),
+            matrix(&[&[4.0, 2.0, 1.0], &[9.0, 3.0, 1.0], &[1.0, -1.0, 1.0]])
+        );
+        assert_eq!(eye(2, 3), matrix(&[&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]]));
+    }
+
+    #[test]
+    fn linspace_includes_both_ends() {
+        assert_eq!(
+            linspace(0.0, 1.0, 5),
+            matrix(&[&[0.0, 0.25, 0.5, 0.75, 1.0]])
+        );
+        // The last point is exactly `b`, even where accumulated rounding would miss it.
+        let points = linspace(0.0, 0.3, 4);
+        assert_eq!(points[0][3], 0.3);
+        assert_eq!(linspace(2.0, 5.0, 1), matrix(&[&[5.0]]));
+        assert_eq!(linspace(2.0, 5.0, 0).ncols(), 0);
+    }
+}
@@ -0,0 +1,88 @@
+"""Writes matlab_v6.mat and matlab_v7.mat, the fixtures for the MAT-file reader.
+
+No MATLAB was available to save them, so the bytes are assembled here following the
+layout MATLAB itself writes, which differs from what Numerus writes:
+
+* the header text names a MATLAB platform and the subsystem offset is blank;
+* names and data of at most four bytes use the small data element format;
+* sparse index arrays are miINT32, and logical arrays are uint8 with the logical flag;
+* -v7 wraps each variable in its own zlib-compressed miCOMPRESSED element.
+
+The variables, as MATLAB would create them:
+
+    A = [1 2 3; 4 5 6];
+    S = sparse([1 3], [1 2], [5 -1], 3, 2);
+    L = logical([1 0 1]);
+    z = int16([-3 7]);
+    c = 'hi';               % char arrays are skipped with a warning
+    save -v6 matlab_v6.mat A S L z c
+    save -v7 matlab_v7.mat A S L z c
+
+Run it from this directory to regenerate both files.
+"""
+
+import struct
+import zlib
+
+MI_INT8, MI_UINT8, MI_INT16, MI_INT32, MI_UINT16 = 1, 2, 3, 5, 4
+MI_UINT32, MI_DOUBLE, MI_MATRIX, MI_COMPRESSED = 6, 9, 14, 15
+MX_CHAR, MX_SPARSE, MX_DOUBLE, MX_UINT8, MX_INT16 = 4, 5, 6, 9, 10
+LOGICAL = 0x0200
+
+
+def element(data_type, data):
+    if 0 < len(data) <= 4:
+        return struct.pack("<HH", data_type, len(data)) + data.ljust(4, b"\0")
+    padding = b"\0" * (-len(data) % 8)
+    return struct.pack("<II", data_type, len(data)) + data + padding
+
+
+def matrix(name, class_code, dims, parts, flags=0, nzmax=0):
+    payload = element(MI_UINT32, struct.pack("<II", class_code | flags, nzmax))
+    payload += element(MI_INT32, struct.pack("<%di" % len(dims), *dims))
+    payload += element(MI_INT8, name.encode())
+    for data_type, data in parts:
+        payload += element(data_type, data)
+    return struct.pack("<II", MI_MATRIX, len(payload)) + payload
+
+
+def doubles(values):
+    return struct.pack("<%dd" % len(values), *values)
+
+
+def int32s(values):
+    return struct.pack("<%di" % len(values), *values)
+
+
+VARIABLES = [
+    matrix("A", MX_DOUBLE, [2, 3], [(MI_DOUBLE, doubles([1, 4, 2, 5, 3, 6]))]),
+    matrix(
+        "S",
+        MX_SPARSE,
+        [3, 2],
+        [(MI_INT32, int32s([0, 2])), (MI_INT32, int32s([0, 1, 2])), (MI_DOUBLE, doubles([5, -1]))],
+        nzmax=2,
+    ),
+    matrix("L", MX_UINT8, [1, 3], [(MI_UINT8, bytes([1, 0, 1]))], flags=LOGICAL),
+    matrix("z", MX_INT16, [1, 2], [(MI_INT16, struct.pack("<2h", -3, 7))]),
+    matrix("c", MX_CHAR, [1, 2], [(MI_UINT16, struct.pack("<2H", ord("h"), ord("i")))]),
+]
+
+
+def header():
+    text = "MATLAB 5.0 MAT-file, Platform: GLNXA64, Created on: Mon Jan  6 12:00:00 2025"
+    return text.ljust(116).encode() + b" " * 8 + struct.pack("<H", 0x0100) + b"IM"
+
+
+def main():
+    with open("matlab_v6.mat", "wb") as f:
+        f.write(header() + b"".join(VARIABLES))
+    with open("matlab_v7.mat", "wb") as f:
+        f.write(header())
+        for variable in VARIABLES:
+            compressed = zlib.compress(variable)
+            f.write(struct.pack("<II", MI_COMPRESSED, len(compressed)) + compressed)
+
+
+if __name__ == "__main__":
+    main()